@@ -0,0 +1,429 @@
+//! Generates `ConnectionPool` methods and their key positions from
+//! `src/commands.json`, a dump of `COMMAND DOCS` with the flags of
+//! `COMMAND INFO` merged in. Regenerate it with `scripts/dump_commands.py`.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    env,
+    fmt::Write,
+    fs,
+    path::Path,
+};
+
+use serde_json::{Map, Value};
+
+const PYTHON_KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+fn flags(arg: &Map<String, Value>) -> Vec<&str> {
+    arg.get("flags")
+        .and_then(Value::as_array)
+        .map(|flags| flags.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default()
+}
+
+fn has_flag(arg: &Map<String, Value>, flag: &str) -> bool {
+    flags(arg).contains(&flag)
+}
+
+fn str_field<'a>(arg: &'a Map<String, Value>, field: &str) -> Option<&'a str> {
+    arg.get(field).and_then(Value::as_str)
+}
+
+fn children(arg: &Map<String, Value>) -> Vec<&Map<String, Value>> {
+    arg.get("arguments")
+        .and_then(Value::as_array)
+        .map(|args| args.iter().filter_map(Value::as_object).collect())
+        .unwrap_or_default()
+}
+
+/// The Rust type of an argument that is a single value.
+fn simple_type(ty: &str) -> Option<&'static str> {
+    match ty {
+        "key" | "string" | "pattern" => Some("RedisValuePy"),
+        "integer" | "unix-time" => Some("i64"),
+        "double" => Some("f64"),
+        _ => None,
+    }
+}
+
+/// How an argument is exposed to Python.
+enum Kind {
+    /// A token that is always sent.
+    Token,
+    /// An optional token, exposed as a bool.
+    Flag,
+    /// A single value, possibly preceded by a token.
+    Value(&'static str),
+    /// One of several tokens, passed by name.
+    Choice(Vec<String>),
+    /// A required block whose arguments become separate parameters.
+    Inline,
+    /// Anything else, passed as a list of raw arguments.
+    Raw,
+}
+
+fn kind(arg: &Map<String, Value>) -> Kind {
+    let ty = str_field(arg, "type").unwrap_or_default();
+    let optional = has_flag(arg, "optional");
+    let multiple = has_flag(arg, "multiple");
+
+    match ty {
+        "pure-token" if optional => Kind::Flag,
+        "pure-token" => Kind::Token,
+        "block" if !optional && !multiple => Kind::Inline,
+        "oneof" if !multiple => {
+            let options = children(arg);
+            if options
+                .iter()
+                .all(|o| str_field(o, "type") == Some("pure-token"))
+            {
+                Kind::Choice(
+                    options
+                        .iter()
+                        .filter_map(|o| str_field(o, "token"))
+                        .map(String::from)
+                        .collect(),
+                )
+            } else if options.iter().all(|o| {
+                let ty = str_field(o, "type").unwrap_or_default();
+                ty == "pure-token" || (simple_type(ty).is_some() && !o.contains_key("token"))
+            }) {
+                Kind::Value("RedisValuePy")
+            } else {
+                Kind::Raw
+            }
+        }
+        // A list of values is passed as one argument
+        _ if multiple && simple_type(ty).is_some() => Kind::Value("RedisValuePy"),
+        _ => simple_type(ty).map_or(Kind::Raw, Kind::Value),
+    }
+}
+
+struct Param {
+    name: String,
+    ty: String,
+    default: Option<&'static str>,
+}
+
+#[derive(Default)]
+struct Method {
+    names: HashSet<String>,
+    required: Vec<Param>,
+    optional: Vec<Param>,
+    body: String,
+}
+
+impl Method {
+    fn param_name(&mut self, name: &str) -> String {
+        let mut name = name.replace('-', "_").to_lowercase();
+        if PYTHON_KEYWORDS.contains(&name.as_str()) || RUST_KEYWORDS.contains(&name.as_str()) {
+            name.push('_');
+        }
+
+        let mut unique = name.clone();
+        let mut n = 2;
+        while !self.names.insert(unique.clone()) {
+            unique = format!("{name}{n}");
+            n += 1;
+        }
+        unique
+    }
+
+    fn token(&mut self, indent: &str, arg: &Map<String, Value>) {
+        if let Some(token) = str_field(arg, "token") {
+            let _res = writeln!(self.body, "{indent}redis_cmd.arg({token:?});");
+        }
+    }
+
+    fn add(&mut self, arg: &Map<String, Value>) {
+        let name = str_field(arg, "name").unwrap_or("arg");
+        let optional = has_flag(arg, "optional");
+
+        match kind(arg) {
+            Kind::Token => self.token("        ", arg),
+            Kind::Flag => {
+                let name = self.param_name(name);
+                let _res = writeln!(self.body, "        if {name} {{");
+                self.token("            ", arg);
+                self.body.push_str("        }\n");
+                self.optional.push(Param {
+                    name,
+                    ty: "bool".into(),
+                    default: Some("false"),
+                });
+            }
+            Kind::Inline => {
+                self.token("        ", arg);
+                for child in children(arg) {
+                    self.add(child);
+                }
+            }
+            kind => {
+                let name = self.param_name(name);
+                let (ty, value) = match kind {
+                    Kind::Value(ty) => (ty.to_string(), name.clone()),
+                    Kind::Choice(tokens) => {
+                        ("&str".to_string(), format!("token({name}, &{tokens:?})?"))
+                    }
+                    _ if has_flag(arg, "multiple") => {
+                        ("Vec<Vec<RedisValuePy>>".to_string(), name.clone())
+                    }
+                    _ => ("Vec<RedisValuePy>".to_string(), name.clone()),
+                };
+
+                let indent = if optional { "            " } else { "        " };
+                if optional {
+                    let _res = writeln!(self.body, "        if let Some({name}) = {name} {{");
+                }
+                if ty == "Vec<Vec<RedisValuePy>>" && has_flag(arg, "multiple_token") {
+                    let _res = writeln!(self.body, "{indent}for item in {name} {{");
+                    self.token(&format!("{indent}    "), arg);
+                    let _res = writeln!(self.body, "{indent}    redis_cmd.arg(item);");
+                    let _res = writeln!(self.body, "{indent}}}");
+                } else {
+                    self.token(indent, arg);
+                    let _res = writeln!(self.body, "{indent}redis_cmd.arg({value});");
+                }
+                if optional {
+                    self.body.push_str("        }\n");
+                    self.optional.push(Param {
+                        name,
+                        ty: format!("Option<{ty}>"),
+                        default: Some("None"),
+                    });
+                } else {
+                    self.required.push(Param {
+                        name,
+                        ty,
+                        default: None,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Record the positions of keys in `args`, starting at `pos`. Returns `false`
+/// if a key follows an argument of variable length.
+fn key_positions(
+    args: &[&Map<String, Value>],
+    pos: &mut Option<usize>,
+    keys: &mut Vec<usize>,
+) -> bool {
+    fn contains_key(arg: &Map<String, Value>) -> bool {
+        str_field(arg, "type") == Some("key") || children(arg).into_iter().any(contains_key)
+    }
+
+    for arg in args {
+        let fixed = !has_flag(arg, "optional") && !has_flag(arg, "multiple");
+        let Some(p) = pos.as_mut().filter(|_| fixed) else {
+            if contains_key(arg) {
+                return false;
+            }
+            *pos = None;
+            continue;
+        };
+        if arg.contains_key("token") && str_field(arg, "type") != Some("pure-token") {
+            *p += 1;
+        }
+
+        match (str_field(arg, "type").unwrap_or_default(), kind(arg)) {
+            (_, Kind::Inline) => {
+                if !key_positions(&children(arg), pos, keys) {
+                    return false;
+                }
+            }
+            ("key", _) => {
+                keys.push(*p);
+                *p += 1;
+            }
+            (_, Kind::Token | Kind::Value(_) | Kind::Choice(_)) => *p += 1,
+            _ => {
+                if contains_key(arg) {
+                    return false;
+                }
+                *pos = None;
+            }
+        }
+    }
+
+    true
+}
+
+fn generate(commands: &Map<String, Value>) -> (String, String) {
+    let mut methods = String::new();
+    // Commands by key positions, with the subcommands that have them
+    let mut specs: BTreeMap<(usize, usize), BTreeMap<String, Vec<String>>> = BTreeMap::new();
+    let mut arg_names = BTreeSet::new();
+
+    for (name, doc) in commands {
+        let Some(doc) = doc.as_object() else {
+            continue;
+        };
+        let words: Vec<&str> = name.split('|').collect();
+        let mut fn_name = name.to_lowercase().replace(['|', '.', '-'], "_");
+        if PYTHON_KEYWORDS.contains(&fn_name.as_str()) {
+            fn_name.push('_');
+        } else if RUST_KEYWORDS.contains(&fn_name.as_str()) {
+            fn_name.insert_str(0, "r#");
+        }
+        let readonly = has_flag(doc, "readonly");
+        let args = children(doc);
+
+        let mut method = Method::default();
+        for word in &words {
+            let _res = writeln!(method.body, "        redis_cmd.arg({word:?});");
+        }
+        for arg in &args {
+            method.add(arg);
+        }
+
+        let mut keys = Vec::new();
+        if key_positions(&args, &mut Some(words.len()), &mut keys) && !keys.is_empty() {
+            let (first, last) = (keys[0], keys[keys.len() - 1]);
+            if last - first + 1 == keys.len() {
+                let (command, subcommand) = match words.as_slice() {
+                    [command, subcommand] => (*command, Some(format!("b{subcommand:?}"))),
+                    _ => (name.as_str(), None),
+                };
+                specs
+                    .entry((first, last))
+                    .or_default()
+                    .entry(format!("b{command:?}"))
+                    .or_default()
+                    .extend(subcommand);
+            }
+        }
+
+        let mut signature: Vec<String> = method.required.iter().map(|p| p.name.clone()).collect();
+        let mut text_signature = vec!["$self".to_string()];
+        text_signature.extend(signature.iter().cloned());
+        if !method.optional.is_empty() || readonly {
+            signature.push("*".into());
+            text_signature.push("*".into());
+        }
+        for param in &method.optional {
+            let default = param.default.unwrap_or("None");
+            signature.push(format!("{} = {default}", param.name));
+            let py_default = if default == "false" { "False" } else { default };
+            text_signature.push(format!("{} = {py_default}", param.name));
+        }
+        if readonly {
+            signature.push("prefer = Prefer::Replica".into());
+            text_signature.push("prefer = \"replica\"".into());
+        }
+
+        // Values that can't be sent raise an `ArgumentError` naming the
+        // argument
+        let mut params: Vec<String> = method
+            .required
+            .iter()
+            .chain(&method.optional)
+            .map(|p| {
+                if p.ty.contains("RedisValuePy") {
+                    arg_names.insert(p.name.clone());
+                    format!(
+                        "#[pyo3(from_py_with = \"args::{0}\")] {0}: {1}",
+                        p.name, p.ty
+                    )
+                } else {
+                    format!("{}: {}", p.name, p.ty)
+                }
+            })
+            .collect();
+        if readonly {
+            params.push("prefer: Prefer".into());
+        }
+
+        let summary = str_field(doc, "summary").unwrap_or_default();
+        let _res = writeln!(methods, "    /// {summary}");
+        let _res = writeln!(
+            methods,
+            "    #[pyo3(signature = ({}))]",
+            signature.join(", ")
+        );
+        let _res = writeln!(
+            methods,
+            "    #[pyo3(text_signature = {:?})]",
+            format!("({})", text_signature.join(", "))
+        );
+        let _res = writeln!(
+            methods,
+            "    fn {fn_name}(&self, {}) -> PyResult<PyObject> {{",
+            params.join(", ")
+        );
+        methods.push_str("        let mut redis_cmd = Cmd::new();\n");
+        methods.push_str(&method.body);
+        if readonly {
+            methods.push_str("        self.read_cmd(redis_cmd, prefer)\n");
+        } else {
+            methods.push_str("        self.query_cmd(redis_cmd)\n");
+        }
+        methods.push_str("    }\n\n");
+    }
+
+    let mut key_specs = String::from(
+        "fn generated_spec(name: &[u8], subcommand: Option<&[u8]>) -> Option<Spec> {\n    \
+         let name = name.to_ascii_uppercase();\n    \
+         let subcommand = subcommand.map(<[u8]>::to_ascii_uppercase);\n    \
+         let (first, last) = match (name.as_slice(), subcommand.as_deref()) {\n",
+    );
+    for ((first, last), commands) in &specs {
+        let (commands, subcommands): (Vec<_>, Vec<_>) = commands
+            .iter()
+            .partition(|(_, subcommands)| subcommands.is_empty());
+        let mut patterns = Vec::new();
+        if !commands.is_empty() {
+            let names: Vec<&str> = commands.iter().map(|(name, _)| name.as_str()).collect();
+            patterns.push(format!("({}, _)", names.join(" | ")));
+        }
+        for (name, subcommands) in subcommands {
+            patterns.push(format!("({name}, Some({}))", subcommands.join(" | ")));
+        }
+        let _res = writeln!(
+            key_specs,
+            "        {} => ({first}, {last}),",
+            patterns.join(" | ")
+        );
+    }
+    key_specs.push_str(
+        "        _ => return None,\n    };\n\n    \
+         Some(Spec::Range {\n        first,\n        last,\n        step: 1,\n    })\n}\n",
+    );
+
+    let arg_names: Vec<String> = arg_names.into_iter().collect();
+    let methods = format!(
+        "#[allow(clippy::too_many_arguments, clippy::needless_pass_by_value, clippy::fn_params_excessive_bools)]\n\
+         #[pymethods]\nimpl ConnectionPool {{\n{}}}\n\n\
+         mod args {{\n    crate::conversion::named_args!({});\n}}\n",
+        methods.trim_end_matches('\n').to_string() + "\n",
+        arg_names.join(", ")
+    );
+
+    (methods, key_specs)
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/commands.json");
+
+    let json = fs::read_to_string("src/commands.json").expect("failed to read src/commands.json");
+    let commands: Map<String, Value> =
+        serde_json::from_str(&json).expect("src/commands.json is not a JSON object");
+    let (methods, key_specs) = generate(&commands);
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("commands.rs"), methods).unwrap();
+    fs::write(Path::new(&out_dir).join("key_specs.rs"), key_specs).unwrap();
+}
@@ -0,0 +1,98 @@
+"""Regenerate src/commands.json from a running redis server.
+
+Usage: python scripts/dump_commands.py [redis://localhost:6379]
+
+The commands in the existing file are dumped again, with the flags of
+COMMAND INFO merged into the reply of COMMAND DOCS. Add a name to the file
+by hand (e.g. "XAUTOCLAIM": {}) to generate a method for it. Module commands
+are only dumped if the module is loaded, so run it against a server with
+RedisJSON. The file is written in the layout it is checked in with, so
+running this against an unchanged server leaves it untouched.
+"""
+
+import json
+import sys
+from pathlib import Path
+
+import redis
+
+PATH = Path(__file__).parent.parent / "src" / "commands.json"
+FIELDS = ("summary", "since", "group", "module", "flags", "arguments")
+ARGUMENT_FIELDS = ("name", "type", "key_spec_index", "token", "since", "flags", "arguments")
+
+
+def decode(value):
+    if isinstance(value, bytes):
+        return value.decode()
+    if isinstance(value, list):
+        return [decode(v) for v in value]
+    if isinstance(value, dict):
+        return {decode(k): decode(v) for k, v in value.items()}
+    return value
+
+
+def pairs(reply):
+    """COMMAND DOCS replies with flat lists of keys and values in RESP2."""
+    if isinstance(reply, dict):
+        return reply
+    return dict(zip(reply[::2], reply[1::2]))
+
+
+def argument(arg):
+    arg = pairs(arg)
+    if "arguments" in arg:
+        arg["arguments"] = [argument(a) for a in arg["arguments"]]
+    if not arg.get("flags"):
+        arg.pop("flags", None)
+    return {field: arg[field] for field in ARGUMENT_FIELDS if field in arg}
+
+
+def dump(client, name):
+    command = name.replace("|", " ").split()
+    docs = pairs(decode(client.execute_command("COMMAND", "DOCS", command[0])))
+    doc = pairs(docs[command[0].lower()])
+    if len(command) == 2:
+        doc = pairs(pairs(doc["subcommands"])["|".join(command).lower()])
+
+    info = decode(client.execute_command("COMMAND", "INFO", command[0]))[0]
+    if len(command) == 2:
+        info = next(s for s in info[9] if s[0] == "|".join(command).lower())
+
+    doc["flags"] = info[2]
+    doc["arguments"] = [argument(a) for a in doc.get("arguments", [])]
+    return {field: doc[field] for field in FIELDS if field in doc}
+
+
+def nested(value):
+    return isinstance(value, list) and any(isinstance(v, dict) for v in value)
+
+
+def render(value, depth=0):
+    """Objects with nested arguments and the lists of them are spread over
+    several lines, everything else is kept on one."""
+    inner = "  " * (depth + 1)
+    if isinstance(value, dict) and (depth == 0 or any(nested(v) for v in value.values())):
+        items = [f"{inner}{json.dumps(k)}: {render(v, depth + 1)}" for k, v in value.items()]
+        return "{\n" + ",\n".join(items) + "\n" + "  " * depth + "}"
+    if nested(value):
+        items = [inner + render(v, depth + 1) for v in value]
+        return "[\n" + ",\n".join(items) + "\n" + "  " * depth + "]"
+    return json.dumps(value, ensure_ascii=False)
+
+
+def main():
+    url = sys.argv[1] if len(sys.argv) > 1 else "redis://localhost:6379"
+    client = redis.Redis.from_url(url, protocol=2)
+    commands = json.loads(PATH.read_text())
+
+    for name in commands:
+        try:
+            commands[name] = dump(client, name)
+        except (KeyError, StopIteration, redis.ResponseError):
+            print(f"{name} is unknown to the server, keeping it", file=sys.stderr)
+
+    PATH.write_text(render(commands) + "\n")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,505 @@
+"""Generate zangy.pyi from the pyo3 definitions in src/.
+
+Usage: python scripts/generate_stubs.py [--check]
+
+The methods generated by build.rs are read from the newest build output in
+target/, so build the crate first. With --check, the script exits with an
+error instead of writing if zangy.pyi is out of date.
+"""
+
+import keyword
+import os
+import re
+import sys
+from pathlib import Path
+
+ROOT = Path(__file__).parent.parent
+SRC = ROOT / "src"
+STUB = ROOT / "zangy.pyi"
+
+HEADER = '''\
+# Generated by scripts/generate_stubs.py, do not edit.
+
+from datetime import date
+from decimal import Decimal
+from typing import (
+    AbstractSet,
+    Any,
+    Awaitable,
+    Callable,
+    Iterator,
+    Literal,
+    Mapping,
+    Sequence,
+    final,
+)
+
+from typing_extensions import Self
+
+RedisValue = (
+    None
+    | bool
+    | bytes
+    | bytearray
+    | memoryview
+    | str
+    | int
+    | float
+    | Decimal
+    | date
+    | Sequence["RedisValue"]
+    | AbstractSet["RedisValue"]
+    | Mapping["RedisValue", "RedisValue"]
+)
+Pairs = Mapping[RedisValue, Any] | Sequence[tuple[RedisValue, Any]]
+'''
+
+# Methods returning a PyObject whose type can't be told from the body
+RETURNS = {
+    "ConnectionPool.__aenter__": "Awaitable[Self]",
+    "ConnectionPool.__aexit__": "Awaitable[None]",
+    "PubSubContext.__aenter__": "Awaitable[Self]",
+    "PubSubContext.__aexit__": "Awaitable[None]",
+    "PubSubContext.__anext__": "Awaitable[Message | KeyspaceEvent]",
+    "PubSubContext.__enter__": "Self",
+    "PubSubContext.__aiter__": "Self",
+    "ConnectionPool.health": "list[dict[str, Any]]",
+    "ConnectionPool.bulk_set": "Awaitable[dict[str, Any]]",
+    "ConnectionPool.bulk": "Awaitable[dict[str, Any]]",
+    "Lock.acquire": "Awaitable[bool]",
+    "Lock.release": "Awaitable[None]",
+    "Lock.extend": "Awaitable[None]",
+    "Lock.__aenter__": "Awaitable[Self]",
+    "Lock.__aexit__": "Awaitable[None]",
+    "Message.__iter__": "Iterator[Any]",
+    "KeyspaceEvent.__iter__": "Iterator[Any]",
+    "create_pool": "Awaitable[ConnectionPool]",
+}
+
+# Attributes set at runtime
+ATTRIBUTES = {
+    "RedisError": ["code: str | None", "detail: str | None"],
+}
+
+# Types of arguments that are converted by hand
+ARGUMENTS = {
+    "PubSubContext.run.handlers": "Mapping[str, Callable[[Message], Any]]",
+    "create_pool.on_span": "Callable[[dict[str, Any]], Any] | None",
+}
+
+# Slots with a different name and signature in Python
+SLOTS = {
+    "__getbuffer__": ("__buffer__", ["self", "flags: int", "/"], "memoryview"),
+}
+
+SHAPES = {
+    "Dict": "dict[Any, Any]",
+    "Scored": "list[tuple[Any, float]]",
+    "Bool": "bool",
+    "Float": "float | None",
+}
+
+FUTURES = re.compile(
+    r"create_future|self\.(query_cmd|query_cmd_as|read_cmd|read_cmd_as|exec_cmd|update|wait_closed)\("
+)
+
+INTS = {"i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize"}
+
+
+def skip_literal(text, i):
+    """Return the index after the string, char or comment at `i`, or `i`."""
+    if text.startswith("//", i):
+        return text.find("\n", i) + 1 or len(text)
+    if text.startswith("/*", i):
+        return text.index("*/", i) + 2
+    raw = re.match(r'r(#*)"', text[i:])
+    if raw and (i == 0 or not (text[i - 1].isalnum() or text[i - 1] == "_")):
+        end = '"' + raw.group(1)
+        return text.index(end, i + raw.end()) + len(end)
+    if text[i] == '"':
+        i += 1
+        while text[i] != '"':
+            i += 2 if text[i] == "\\" else 1
+        return i + 1
+    char = re.match(r"'(\\.|[^\\'])'", text[i:])
+    if char:
+        return i + char.end()
+    return i
+
+
+def balanced(text, i):
+    """Return the index after the bracket group starting at `i`."""
+    depth = 0
+    while True:
+        j = skip_literal(text, i)
+        if j != i:
+            i = j
+            continue
+        if text[i] in "([{":
+            depth += 1
+        elif text[i] in ")]}":
+            depth -= 1
+            if depth == 0:
+                return i + 1
+        i += 1
+
+
+def split(text, sep=","):
+    """Split `text` at `sep` outside of brackets."""
+    parts, depth, start = [], 0, 0
+    for i, c in enumerate(text):
+        if c in "([{<":
+            depth += 1
+        elif c in ")]}>" and not text[i - 1 : i + 1] == "->":
+            depth -= 1
+        elif c == sep and depth == 0:
+            parts.append(text[start:i].strip())
+            start = i + 1
+    parts.append(text[start:].strip())
+    return [p for p in parts if p]
+
+
+def items(text):
+    """Yield the doc, attributes, header and body of each item in `text`."""
+    docs, attrs, i = [], [], 0
+    while i < len(text):
+        if text[i].isspace():
+            i += 1
+        elif text.startswith("///", i):
+            end = text.find("\n", i)
+            docs.append(text[i + 3 : end].strip())
+            i = end
+        elif text.startswith("//", i) or text.startswith("/*", i):
+            i = skip_literal(text, i)
+        elif text.startswith("#![", i):
+            i = balanced(text, i + 2)
+        elif text.startswith("#[", i):
+            end = balanced(text, i + 1)
+            attrs.append(text[i + 2 : end - 1])
+            i = end
+        else:
+            # The header ends at the body or at a semicolon
+            j = i
+            while text[j] not in "{;":
+                k = skip_literal(text, j)
+                j = balanced(text, j) if text[j] in "([" else max(k, j + 1)
+            header = text[i:j]
+            end = balanced(text, j) if text[j] == "{" else j + 1
+            yield docs, attrs, header, text[j + 1 : end - 1]
+            docs, attrs, i = [], [], end
+
+
+def parse_type(ty):
+    """Parse a Rust type into its name and type arguments."""
+    ty = re.sub(r"'\w+\s*,?\s*", "", ty).replace("&", "").replace("mut ", "").strip()
+    if ty == "()":
+        return "()", []
+    if ty.startswith("("):
+        return "tuple", [parse_type(t) for t in split(ty[1:-1])]
+    name, _, args = ty.partition("<")
+    name = name.split("::")[-1].strip()
+    return name, [parse_type(t) for t in split(args[:-1])] if args else []
+
+
+def py_type(ty, classes, argument=False):
+    """The Python type of a Rust type."""
+    name, args = ty
+    if name == "Option":
+        return f"{py_type(args[0], classes, argument)} | None"
+    if name == "Vec" and args and args[0][0] == "u8":
+        return "bytes"
+    if name == "Vec":
+        inner = py_type(args[0], classes, argument)
+        return f"Sequence[{inner}]" if argument else f"list[{inner}]"
+    if name == "HashMap":
+        key, value = (py_type(a, classes, argument) for a in args)
+        return f"Mapping[{key}, {value}]" if argument else f"dict[{key}, {value}]"
+    if name == "tuple":
+        return "tuple[" + ", ".join(py_type(a, classes, argument) for a in args) + "]"
+    if name in ("Py", "Bound", "PyRef", "PyRefMut") and args and args[0][0] in classes:
+        return args[0][0]
+    if name in ("Py", "PyRef", "PyRefMut") and args and args[0][0] == "Self":
+        return "Self"
+    if name == "Bound" and args:
+        return {
+            "PyList": "list[Any]",
+            "PyDict": "dict[str, Any]",
+            "PyString": "str",
+            "PyBytes": "bytes",
+        }.get(args[0][0], "Any")
+    if name in INTS:
+        return "int"
+    if name in ("f32", "f64"):
+        return "float"
+    return {
+        "bool": "bool",
+        "str": "str",
+        "String": "str",
+        "RedisValuePy": "RedisValue",
+        "Pairs": "Pairs",
+        "Prefer": 'Literal["replica", "primary"]',
+        "()": "None",
+    }.get(name, name if name in classes else "Any")
+
+
+def signature_attr(attrs):
+    for attr in attrs:
+        match = re.match(r"pyo3\((.*)\)$", attr, re.S)
+        if not match:
+            continue
+        for option in split(match.group(1)):
+            key, _, value = option.partition("=")
+            if key.strip() == "signature":
+                return split(value.strip()[1:-1])
+    return None
+
+
+def parse_function(qualname, docs, attrs, header, body, classes):
+    name = re.search(r"fn\s+(?:r#)?(\w+)", header).group(1)
+    if name in SLOTS:
+        return *SLOTS[name][:1], [], *SLOTS[name][1:], docs
+    qualname = f"{qualname}.{name}" if qualname else name
+    params_start = header.index("(", header.index(name))
+    params_end = balanced(header, params_start)
+    params = split(header[params_start + 1 : params_end - 1])
+    ret = header[params_end:].partition("->")[2].partition(" where ")[0].strip()
+
+    types = {}
+    is_method = False
+    for param in params:
+        # Attributes like `#[pyo3(from_py_with = "...")]`
+        param = re.sub(r"^#\[.*?\]\s*", "", param.strip(), flags=re.S)
+        pname, _, ty = param.partition(":")
+        pname = pname.replace("mut ", "").strip()
+        if pname in ("self", "&self", "&mut self", "slf"):
+            is_method = True
+        elif not ty.strip().startswith("Python"):
+            types[pname] = ty.strip()
+
+    signature = signature_attr(attrs)
+    if signature is None:
+        # Trailing optional arguments default to None
+        signature, optional = [], True
+        for pname in reversed(list(types)):
+            optional = optional and parse_type(types[pname])[0] == "Option"
+            signature.insert(0, f"{pname} = None" if optional else pname)
+
+    args = ["self"] if is_method else []
+    positional = 0
+    for entry in signature:
+        pname, _, default = (s.strip() for s in entry.partition("="))
+        bare = pname.lstrip("*")
+        if bare not in types:
+            args.append(pname)
+            continue
+        ty = ARGUMENTS.get(f"{qualname}.{bare}")
+        if ty is None:
+            rust = parse_type(types[bare])
+            if pname.startswith("*") and rust == ("Bound", [("PyTuple", [])]):
+                # Extracted in the body, e.g. `extract_arg::<Vec<RedisValuePy>>(args, "args")`
+                extracted = re.search(
+                    rf"extract_arg::<(.+?)>\(\s*{bare}\b|let {bare}: (.+?) = extract_arg\(", body
+                )
+                if extracted:
+                    rust = parse_type(extracted.group(1) or extracted.group(2))
+            if pname.startswith("*") and rust[0] == "Vec":
+                rust = rust[1][0]
+            ty = py_type(rust, classes, argument=True)
+        if keyword.iskeyword(bare):
+            # Can only be passed by position
+            positional = len(args) + 1
+            pname += "_"
+        args.append(f"{pname}: {ty}" + (" = ..." if default else ""))
+    if positional:
+        args.insert(positional, "/")
+
+    rtype = parse_type(ret) if ret else ("()", [])
+    if rtype[0] == "PyResult":
+        rtype = rtype[1][0]
+    if qualname in RETURNS:
+        returns = RETURNS[qualname]
+    elif rtype[0] == "PyObject" and FUTURES.search(body):
+        shape = re.search(r"ResponseShape::(\w+)", body)
+        if shape:
+            returns = f"Awaitable[{SHAPES.get(shape.group(1), 'Any')}]"
+        elif "set_fut_result_none" in body and "set_fut_result_with_gil" not in body:
+            returns = "Awaitable[None]"
+        else:
+            returns = "Awaitable[Any]"
+    elif rtype[0] == "PyObject" and "pubsub_context(" in body:
+        returns = "PubSubContext"
+    else:
+        returns = py_type(rtype, classes)
+
+    decorators = []
+    if any(a.strip() == "getter" for a in attrs):
+        decorators.append("@property")
+    elif any(a.strip() == "staticmethod" for a in attrs):
+        decorators.append("@staticmethod")
+    if any(a.strip() == "new" for a in attrs):
+        name, args, returns = "__init__", ["self", *args], "None"
+
+    return name, decorators, args, returns, docs
+
+
+def parse_fields(body, all_fields, classes):
+    """The fields of a pyclass that are readable from Python."""
+    fields, docs, get, code = [], [], all_fields, ""
+    for line in body.splitlines():
+        line = line.strip()
+        if line.startswith("///"):
+            docs.append(line[3:].strip())
+        elif line.startswith("#["):
+            get = get or line.startswith("#[pyo3(get")
+        elif line and not line.startswith("//"):
+            code += " " + line
+            if code.endswith(",") and len(split(code)) == 1:
+                fname, _, ty = code.rstrip(",").partition(":")
+                fname = fname.replace("pub", "").strip()
+                if get:
+                    fields.append((fname, py_type(parse_type(ty.strip()), classes), docs))
+                docs, get, code = [], all_fields, ""
+    return fields
+
+
+def parse(sources):
+    """Collect the exceptions, classes and functions defined in `sources`."""
+    exceptions, classes, functions, exports = [], {}, [], []
+
+    for text in sources:
+        for name, base in re.findall(r"create_exception!\(\s*zangy,\s*(\w+),\s*(\w+)\s*\)", text):
+            exceptions.append((name, "Exception" if base == "PyException" else base))
+        exports += re.findall(r'add_class::<(?:\w+::)*(\w+)>|m\.add\(\s*"(\w+)"', text)
+        exports += [(f, "") for f in re.findall(r"wrap_pyfunction!\((\w+)", text)]
+        for docs, attrs, header, body in items_with_nested(text):
+            pyclass = next((a for a in attrs if a.startswith("pyclass")), None)
+            struct = re.match(r"(?:pub\s+)?struct\s+(\w+)", header)
+            if pyclass and struct:
+                fields = parse_fields(body, "get_all" in pyclass, classes)
+                classes[struct.group(1)] = {"docs": docs, "fields": fields, "methods": []}
+
+    for text in sources:
+        for docs, attrs, header, body in items_with_nested(text):
+            impl = re.match(r"impl\s+(\w+)", header)
+            if "pymethods" in attrs and impl:
+                for item in items(body):
+                    if re.search(r"\bfn\b", item[2]):
+                        method = parse_function(impl.group(1), *item, classes)
+                        classes[impl.group(1)]["methods"].append(method)
+            elif "pyfunction" in attrs:
+                functions.append(parse_function("", docs, attrs, header, body, classes))
+
+    exports = [a or b for a, b in exports]
+    return exceptions, classes, functions, exports
+
+
+def items_with_nested(text):
+    """Like `items`, but also yields the items of `mod` blocks."""
+    for item in items(text):
+        yield item
+        if re.match(r"(?:pub\s+)?mod\s+\w+", item[2]):
+            yield from items_with_nested(item[3])
+
+
+def docstring(docs, indent):
+    if not docs:
+        return []
+    text = "\n".join(docs).replace('"""', '\\"\\"\\"')
+    lines = text.splitlines()
+    if len(lines) == 1:
+        return [f'{indent}"""{text}"""']
+    return [f'{indent}"""{lines[0]}', *(f"{indent}{line}" if line else "" for line in lines[1:]), f'{indent}"""']
+
+
+def function(name, decorators, args, returns, docs, indent=""):
+    if keyword.iskeyword(name):
+        return [f"{indent}# {name}({', '.join(args)}) -> {returns}, called with getattr"]
+    out = [f"{indent}{d}" for d in decorators]
+    line = f"{indent}def {name}({', '.join(args)}) -> {returns}:"
+    if len(line) > 100:
+        out.append(f"{indent}def {name}(")
+        out += [f"{indent}    {arg}," for arg in args]
+        line = f"{indent}) -> {returns}:"
+    doc = docstring(docs, indent + "    ")
+    if doc:
+        out += [line, *doc]
+    else:
+        out.append(line + " ...")
+    return out
+
+
+def render(exceptions, classes, functions, exports):
+    out = [HEADER]
+    for name, base in exceptions:
+        attrs = ATTRIBUTES.get(name)
+        if attrs:
+            out.append(f"class {name}({base}):")
+            out += [f"    {attr}" for attr in attrs]
+        else:
+            out.append(f"class {name}({base}): ...")
+        out.append("")
+
+    # Exported classes first, then the ones only returned by methods
+    names = [n for n in exports if n in classes]
+    names += [n for n in classes if n not in names]
+    rendered = "\n".join(str(m) for n in names[: len(exports)] for m in classes[n]["methods"])
+    for name in names:
+        cls = classes[name]
+        if name not in exports and name not in rendered:
+            continue
+        out.append("")
+        out.append("@final")
+        out.append(f"class {name}:")
+        body = docstring(cls["docs"], "    ")
+        for fname, ty, docs in cls["fields"]:
+            body.append(f"    {fname}: {ty}")
+            body += docstring(docs, "    ")
+        for method in cls["methods"]:
+            if body:
+                body.append("")
+            body += function(*method, indent="    ")
+        out += body or ["    ..."]
+        out.append("")
+
+    for func in functions:
+        out.append("")
+        out += function(*func)
+
+    return "\n".join(out).rstrip("\n") + "\n"
+
+
+def generated_sources():
+    """The build.rs output included with `include!`, newest build first."""
+    out_dir = os.environ.get("OUT_DIR")
+    if out_dir:
+        return Path(out_dir)
+    candidates = sorted(
+        (ROOT / "target").glob("**/build/zangy-*/out/commands.rs"),
+        key=lambda p: p.stat().st_mtime,
+    )
+    if not candidates:
+        sys.exit("no build output found in target/, build the crate first")
+    return candidates[-1].parent
+
+
+def main():
+    out_dir = generated_sources()
+    sources, generated = [], []
+    for path in sorted(SRC.glob("*.rs")):
+        text = path.read_text()
+        sources.append(text)
+        for include in re.findall(r'include!\(concat!\(env!\("OUT_DIR"\), "/(\w+\.rs)"\)\)', text):
+            generated.append((out_dir / include).read_text())
+    # After the hand-written methods
+    sources += generated
+
+    stub = render(*parse(sources))
+    if "--check" in sys.argv:
+        if not STUB.exists() or STUB.read_text() != stub:
+            sys.exit("zangy.pyi is out of date, run scripts/generate_stubs.py")
+    else:
+        STUB.write_text(stub)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,154 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use pyo3::prelude::PyResult;
+use tokio::sync::Notify;
+
+use crate::{exceptions::ArgumentError, pubsub::Event};
+
+/// What to do with a message that arrives while the buffer is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    DropOldest,
+    DropNewest,
+    Disconnect,
+}
+
+impl Overflow {
+    pub fn from_name(name: &str) -> PyResult<Self> {
+        match name {
+            "drop_oldest" => Ok(Self::DropOldest),
+            "drop_newest" => Ok(Self::DropNewest),
+            "disconnect" => Ok(Self::Disconnect),
+            _ => Err(ArgumentError::new_err(format!(
+                "unknown overflow policy {name:?}, expected one of \"drop_oldest\", \"drop_newest\" or \"disconnect\""
+            ))),
+        }
+    }
+}
+
+struct State {
+    events: VecDeque<Event>,
+    messages: usize,
+    closed: bool,
+}
+
+/// Buffers events between the task reading from a pubsub connection and its
+/// consumer, holding at most `max_pending` messages if set.
+pub struct EventQueue {
+    state: Mutex<State>,
+    available: Notify,
+    max_pending: Option<usize>,
+    overflow: Overflow,
+    dropped: AtomicU64,
+}
+
+impl EventQueue {
+    pub fn new(max_pending: Option<usize>, overflow: Overflow) -> Self {
+        Self {
+            state: Mutex::new(State {
+                events: VecDeque::new(),
+                messages: 0,
+                closed: false,
+            }),
+            available: Notify::new(),
+            max_pending,
+            overflow,
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// The number of messages dropped because the buffer was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// The number of messages waiting to be consumed.
+    pub fn pending(&self) -> usize {
+        self.state.lock().unwrap().messages
+    }
+
+    /// Queue an event. Returns `false` if the buffer overflowed and the
+    /// connection should be closed.
+    pub fn push(&self, event: Event) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return false;
+        }
+
+        if matches!(event, Event::Message(_)) {
+            if self.max_pending.is_some_and(|max| state.messages >= max) {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+
+                match self.overflow {
+                    Overflow::DropOldest => {
+                        if let Some(idx) = state
+                            .events
+                            .iter()
+                            .position(|e| matches!(e, Event::Message(_)))
+                        {
+                            state.events.remove(idx);
+                            state.messages -= 1;
+                        }
+                    }
+                    Overflow::DropNewest => return true,
+                    Overflow::Disconnect => {
+                        state.events.push_back(Event::Overflow);
+                        state.closed = true;
+                        drop(state);
+                        self.available.notify_one();
+                        return false;
+                    }
+                }
+            }
+
+            state.messages += 1;
+        }
+
+        state.events.push_back(event);
+        drop(state);
+        self.available.notify_one();
+
+        true
+    }
+
+    /// Stop accepting events, consumers receive `None` once the buffer is
+    /// drained.
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.available.notify_waiters();
+        self.available.notify_one();
+    }
+
+    pub async fn recv(&self) -> Option<Event> {
+        loop {
+            let available = self.available.notified();
+
+            {
+                let mut state = self.state.lock().unwrap();
+                if let Some(event) = state.events.pop_front() {
+                    if matches!(event, Event::Message(_)) {
+                        state.messages -= 1;
+                    }
+                    let more = !state.events.is_empty() || state.closed;
+                    drop(state);
+                    // Pass the wakeup on to other consumers
+                    if more {
+                        self.available.notify_one();
+                    }
+                    return Some(event);
+                }
+                if state.closed {
+                    return None;
+                }
+            }
+
+            available.await;
+        }
+    }
+}
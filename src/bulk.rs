@@ -0,0 +1,196 @@
+use std::{sync::Arc, time::Instant};
+
+use pyo3::{
+    intern,
+    prelude::{Py, PyObject, PyResult, Python},
+    types::{PyDict, PyDictMethods, PyIterator},
+    Bound, IntoPy, PyAny,
+};
+use redis::{RedisError, Value};
+use tokio::task::JoinSet;
+
+use crate::{
+    asyncio::{create_future, set_fut_exc, set_fut_result_with_gil},
+    conversion::{Compressor, Request},
+    exceptions::{redis_error, ArgumentError},
+    health::Slot,
+    metrics::Metrics,
+    pool::ConnectionPool,
+    runtime::RUNTIME,
+};
+
+/// Send the commands built by `build` from the items of `items`, taking
+/// `chunk_size` items at a time. Each chunk is split evenly over the healthy
+/// connections of the pool and sent as one pipeline per connection, and the
+/// next chunk is only taken once all of them were answered. Resolves to a
+/// dict with the number of commands sent as `count` and the errors as
+/// `errors`.
+pub fn spawn<F>(
+    pool: &Bound<'_, ConnectionPool>,
+    items: &Bound<'_, PyIterator>,
+    chunk_size: usize,
+    build: F,
+) -> PyResult<PyObject>
+where
+    F: Fn(&ConnectionPool, Bound<'_, PyAny>) -> PyResult<Request> + Send + 'static,
+{
+    if chunk_size == 0 {
+        return Err(ArgumentError::new_err("chunk_size must be at least 1"));
+    }
+
+    let (in_flight, slots, metrics, compressor) = {
+        let pool = pool.borrow();
+        (
+            pool.lifecycle.start()?,
+            pool.pool.clone(),
+            pool.metrics.clone(),
+            pool.compressor,
+        )
+    };
+    let (fut, res_fut) = create_future()?;
+    let pool = pool.clone().unbind();
+    let items = items.clone().unbind();
+
+    RUNTIME.spawn(async move {
+        let _in_flight = in_flight;
+        let mut count = 0;
+        let mut errors = Vec::new();
+
+        loop {
+            let chunk = Python::with_gil(|py| next_chunk(py, &pool, &items, chunk_size, &build));
+            let chunk = match chunk {
+                Ok(chunk) if chunk.is_empty() => break,
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    if let Err(e2) = set_fut_exc(&fut, e) {
+                        eprintln!("{e2:?}");
+                    }
+                    return;
+                }
+            };
+
+            count += chunk.len();
+            errors.extend(send(&slots, &metrics, compressor, chunk).await);
+        }
+
+        Python::with_gil(|py| {
+            let errors: Vec<PyObject> = errors
+                .iter()
+                .map(|e| redis_error(e).into_value(py).into_any())
+                .collect();
+            let summary = PyDict::new_bound(py);
+            let res = summary
+                .set_item(intern!(py, "count"), count)
+                .and_then(|()| summary.set_item(intern!(py, "errors"), errors))
+                .and_then(|()| set_fut_result_with_gil(&fut, summary.into_py(py), py));
+            if let Err(e) = res {
+                eprintln!("{e:?}");
+            }
+        });
+    });
+
+    Ok(res_fut)
+}
+
+/// Build the commands for the next `chunk_size` items. Returns an empty list
+/// once the iterator is exhausted.
+fn next_chunk<F>(
+    py: Python,
+    pool: &Py<ConnectionPool>,
+    items: &Py<PyIterator>,
+    chunk_size: usize,
+    build: &F,
+) -> PyResult<Vec<Request>>
+where
+    F: Fn(&ConnectionPool, Bound<'_, PyAny>) -> PyResult<Request>,
+{
+    let pool = pool.borrow(py);
+    pool.lifecycle.ensure_open()?;
+
+    items
+        .bind(py)
+        .clone()
+        .take(chunk_size)
+        .map(|item| {
+            let Request { cmd, mut stored } = build(&pool, item?)?;
+            let len = cmd.args_iter().len();
+            let cmd = pool.middleware.before(cmd)?;
+            // Like in `send_cmd`, stored values that were moved by the
+            // middleware are sent uncompressed
+            if cmd.args_iter().len() != len {
+                stored.clear();
+            }
+            Ok(Request { cmd, stored })
+        })
+        .collect()
+}
+
+/// Send `cmds` as one pipeline per connection and return the errors. Redis
+/// runs all commands of a pipeline, but only the first error of each is
+/// reported. The commands are not traced and the replies are dropped
+/// without running the `after` hooks of the middleware, since a failed
+/// pipeline has no reply per command to pass to them.
+async fn send(
+    slots: &Arc<Vec<Slot>>,
+    metrics: &Arc<Metrics>,
+    compressor: Option<Compressor>,
+    cmds: Vec<Request>,
+) -> Vec<RedisError> {
+    let mut healthy: Vec<&Slot> = slots.iter().filter(|slot| slot.is_healthy()).collect();
+    if healthy.is_empty() {
+        healthy = slots.iter().collect();
+    }
+    if healthy.is_empty() {
+        // Closed while the chunk was built
+        return vec![RedisError::from((
+            redis::ErrorKind::ClientError,
+            "pool has been closed",
+        ))];
+    }
+    let per_connection = cmds.len().div_ceil(healthy.len());
+
+    let mut cmds = cmds.into_iter();
+    let mut tasks = JoinSet::new();
+    for slot in healthy {
+        let mut pipe = redis::pipe();
+        for Request { cmd, stored } in cmds.by_ref().take(per_connection) {
+            match compressor {
+                Some(compressor) if !stored.is_empty() => {
+                    pipe.add_command(compressor.compress_args(&cmd, &stored));
+                }
+                _ => {
+                    pipe.add_command(cmd);
+                }
+            }
+        }
+        if pipe.cmd_iter().next().is_none() {
+            break;
+        }
+        let (mut conn, busy) = slot.acquire();
+        let metrics = metrics.clone();
+
+        tasks.spawn(async move {
+            let start = Instant::now();
+            let res = pipe.query_async::<_, Value>(&mut conn).await;
+            let elapsed = start.elapsed();
+            drop(busy);
+
+            // Commands in a pipeline share the round trip, record each with
+            // its share of it
+            let len = pipe.cmd_iter().count();
+            let share = elapsed / u32::try_from(len).unwrap_or(u32::MAX);
+            for cmd in pipe.cmd_iter() {
+                metrics.record(cmd, share, res.is_ok());
+            }
+            res.err()
+        });
+    }
+
+    let mut errors = Vec::new();
+    while let Some(res) = tasks.join_next().await {
+        if let Ok(Some(e)) = res {
+            errors.push(e);
+        }
+    }
+    errors
+}
@@ -0,0 +1,207 @@
+use std::{str::from_utf8, sync::Arc};
+
+use pyo3::{
+    exceptions::PyStopAsyncIteration,
+    prelude::{pyclass, pymethods, Py, PyObject, PyRef, PyResult, Python},
+};
+use redis::{Cmd, Value};
+use tokio::sync::Mutex;
+
+use crate::{
+    asyncio::{create_future, set_fut_exc, set_fut_result_with_gil},
+    conversion::{re_to_object_shaped, Compressor, RedisValuePy, ResponseShape},
+    exceptions::{redis_error, ArgumentError},
+    pool::{ConnectionPool, Prefer},
+    runtime::RUNTIME,
+};
+
+/// The collection a `Chunks` iterator reads.
+#[derive(Clone, Copy)]
+pub enum Source {
+    List,
+    SortedSet { withscores: bool },
+    Hash,
+}
+
+impl Source {
+    /// The command reading the chunk at `position`, which is an index for
+    /// lists and sorted sets and a cursor for hashes.
+    fn cmd(self, key: &RedisValuePy, position: u64, chunk_size: usize) -> Cmd {
+        let start = isize::try_from(position).unwrap_or(isize::MAX);
+        let stop = start.saturating_add_unsigned(chunk_size - 1);
+        match self {
+            Self::List => Cmd::lrange(key, start, stop),
+            Self::SortedSet { withscores: false } => Cmd::zrange(key, start, stop),
+            Self::SortedSet { withscores: true } => Cmd::zrange_withscores(key, start, stop),
+            Self::Hash => {
+                let mut cmd = redis::cmd("HSCAN");
+                cmd.arg(key).arg(position).arg("COUNT").arg(chunk_size);
+                cmd
+            }
+        }
+    }
+
+    fn shape(self) -> ResponseShape {
+        match self {
+            Self::List => ResponseShape::Values,
+            Self::SortedSet { withscores: false } => ResponseShape::Generic,
+            Self::SortedSet { withscores: true } => ResponseShape::Scored,
+            Self::Hash => ResponseShape::Dict,
+        }
+    }
+
+    /// Split a reply into the chunk and the position of the next one, or
+    /// `None` if it was the last.
+    fn split(self, reply: Value, position: u64, chunk_size: usize) -> (Value, Option<u64>) {
+        match (self, reply) {
+            (Self::Hash, Value::Bulk(mut reply)) if reply.len() == 2 => {
+                let items = reply.pop().unwrap();
+                let cursor = match &reply[0] {
+                    Value::Data(cursor) => from_utf8(cursor).ok().and_then(|c| c.parse().ok()),
+                    _ => None,
+                };
+                (items, cursor.filter(|&cursor| cursor != 0))
+            }
+            (Self::List | Self::SortedSet { .. }, Value::Bulk(items)) => {
+                let len = match self {
+                    Self::SortedSet { withscores: true } => items.len() / 2,
+                    _ => items.len(),
+                };
+                let next = (len == chunk_size).then_some(position + len as u64);
+                (Value::Bulk(items), next)
+            }
+            (_, reply) => (reply, None),
+        }
+    }
+}
+
+struct State {
+    position: u64,
+    done: bool,
+}
+
+/// An async iterator that reads a collection in chunks of `chunk_size`
+/// elements with one command each, returned by `lrange_iter`, `zrange_iter`
+/// and `hgetall_iter`. Every chunk is converted as soon as it arrives, so
+/// only one is held in memory at a time. Changes made to the collection
+/// while iterating may cause elements to be skipped or returned twice.
+#[pyclass(module = "zangy")]
+pub struct Chunks {
+    pool: Py<ConnectionPool>,
+    key: RedisValuePy,
+    source: Source,
+    chunk_size: usize,
+    prefer: Prefer,
+    state: Arc<Mutex<State>>,
+}
+
+impl Chunks {
+    pub fn new(
+        pool: Py<ConnectionPool>,
+        key: RedisValuePy,
+        source: Source,
+        chunk_size: usize,
+        prefer: Prefer,
+    ) -> PyResult<Self> {
+        if chunk_size == 0 {
+            return Err(ArgumentError::new_err("chunk_size must be at least 1"));
+        }
+
+        Ok(Self {
+            pool,
+            key,
+            source,
+            chunk_size,
+            prefer,
+            state: Arc::new(Mutex::new(State {
+                position: 0,
+                done: false,
+            })),
+        })
+    }
+}
+
+#[pymethods]
+impl Chunks {
+    fn __aiter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    /// Resolves to the next chunk: a list for lists and sorted sets, with
+    /// `(member, score)` tuples if `withscores` is set, and a dict for
+    /// hashes.
+    fn __anext__(&self, py: Python) -> PyResult<PyObject> {
+        let (fut, res_fut) = create_future()?;
+        let pool = self.pool.clone_ref(py);
+        let key = self.key.clone();
+        let source = self.source;
+        let chunk_size = self.chunk_size;
+        let prefer = self.prefer;
+        let state = self.state.clone();
+
+        RUNTIME.spawn(async move {
+            // Held until the chunk is converted so that concurrent calls read
+            // consecutive chunks
+            let mut state = state.lock().await;
+
+            let res = loop {
+                if state.done {
+                    break Err(PyStopAsyncIteration::new_err(()));
+                }
+
+                let cmd = source.cmd(&key, state.position, chunk_size);
+                let sent = Python::with_gil(|py| {
+                    let pool = pool.borrow(py);
+                    pool.send_cmd(cmd, prefer).map(|reply| {
+                        (
+                            reply,
+                            pool.serializer,
+                            pool.compressor,
+                            pool.middleware.clone(),
+                        )
+                    })
+                });
+                let (reply, serializer, compressor, middleware) = match sent {
+                    Ok(sent) => sent,
+                    Err(e) => break Err(e),
+                };
+
+                let (cmd, reply) = reply.await;
+                let mut chunk = match reply {
+                    Ok(reply) => {
+                        let (chunk, next) = source.split(reply, state.position, chunk_size);
+                        match next {
+                            Some(next) => state.position = next,
+                            None => state.done = true,
+                        }
+                        chunk
+                    }
+                    Err(e) => break Err(redis_error(&e)),
+                };
+
+                // Hashes can have empty pages before the end
+                if matches!(&chunk, Value::Bulk(items) if items.is_empty()) {
+                    continue;
+                }
+
+                if compressor.is_some() {
+                    Compressor::decompress_reply(&mut chunk, source.shape());
+                }
+                break Python::with_gil(|py| {
+                    let chunk = re_to_object_shaped(chunk, source.shape(), serializer, py);
+                    middleware.after_py(py, &cmd, chunk)
+                });
+            };
+
+            let res = match res {
+                Ok(chunk) => Python::with_gil(|py| set_fut_result_with_gil(&fut, chunk, py)),
+                Err(e) => set_fut_exc(&fut, e),
+            };
+            if let Err(e) = res {
+                eprintln!("{e:?}");
+            }
+        });
+
+        Ok(res_fut)
+    }
+}
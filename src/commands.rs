@@ -0,0 +1,25 @@
+use pyo3::prelude::{pymethods, PyObject, PyResult};
+use redis::Cmd;
+
+use crate::{
+    conversion::RedisValuePy,
+    exceptions::ArgumentError,
+    pool::{ConnectionPool, Prefer},
+};
+
+/// Look up the token a choice argument was given as, ignoring case.
+fn token(choice: &str, tokens: &[&'static str]) -> PyResult<&'static str> {
+    tokens
+        .iter()
+        .find(|token| token.eq_ignore_ascii_case(choice))
+        .copied()
+        .ok_or_else(|| {
+            ArgumentError::new_err(format!(
+                "expected one of {}, got {choice:?}",
+                tokens.join(", ")
+            ))
+        })
+}
+
+// Methods generated by build.rs from commands.json
+include!(concat!(env!("OUT_DIR"), "/commands.rs"));
@@ -1,15 +1,62 @@
+use std::{str::from_utf8, sync::Arc};
+
+use num_bigint::BigInt;
 use pyo3::{
-    prelude::{FromPyObject, PyObject, Python, ToPyObject},
-    types::PyBytes,
+    buffer::PyBuffer,
+    intern,
+    prelude::{FromPyObject, IntoPy, PyObject, PyResult, Python, ToPyObject},
+    pybacked::PyBackedBytes,
+    sync::GILOnceCell,
+    types::{
+        PyAnyMethods, PyBool, PyBoolMethods, PyByteArray, PyBytes, PyDict, PyDictMethods, PyFloat,
+        PyFloatMethods, PyFrozenSet, PyInt, PyMemoryView, PySequence, PySet, PyString,
+        PyStringMethods, PyTypeMethods,
+    },
+    Bound, PyAny,
 };
-use redis::{RedisWrite, ToRedisArgs, Value};
+use redis::{Arg, Cmd, RedisWrite, ToRedisArgs, Value};
+
+use crate::{exceptions::ArgumentError, raw::RawBuffer, serializer::Serializer};
+
+/// Prefix of compressed values, followed by a byte identifying the algorithm.
+const COMPRESSION_MAGIC: &[u8] = b"\x00zgc";
+
+static DECIMAL: GILOnceCell<PyObject> = GILOnceCell::new();
+static DATE: GILOnceCell<PyObject> = GILOnceCell::new();
+
+/// The attribute `name` of `module`, imported once.
+fn imported<'py>(
+    cell: &'py GILOnceCell<PyObject>,
+    py: Python<'py>,
+    module: &str,
+    name: &str,
+) -> PyResult<&'py Bound<'py, PyAny>> {
+    cell.get_or_try_init(py, || Ok(py.import_bound(module)?.getattr(name)?.unbind()))
+        .map(|ty| ty.bind(py))
+}
+
+/// A contiguous buffer exported by an object like a memoryview, held until
+/// the value is dropped.
+struct Buffer(PyBuffer<u8>);
 
-#[derive(Debug, FromPyObject)]
+impl AsRef<[u8]> for Buffer {
+    fn as_ref(&self) -> &[u8] {
+        // The buffer was checked to be contiguous and stays valid until it is
+        // released on drop
+        unsafe { std::slice::from_raw_parts(self.0.buf_ptr().cast(), self.0.len_bytes()) }
+    }
+}
+
+#[derive(Clone)]
 pub enum RedisValuePy {
     Bool(bool),
     Bytes(Vec<u8>),
+    /// Memory of a Python object, written to the command without copying it
+    /// first.
+    Borrowed(Arc<dyn AsRef<[u8]> + Send + Sync>),
     String(String),
     Int(i64),
+    BigInt(BigInt),
     Float(f64),
     Array(Vec<RedisValuePy>),
 }
@@ -21,8 +68,11 @@ impl ToRedisArgs for RedisValuePy {
     {
         match self {
             RedisValuePy::Bytes(b) => b.write_redis_args(out),
+            RedisValuePy::Borrowed(b) => out.write_arg((**b).as_ref()),
             RedisValuePy::String(s) => s.write_redis_args(out),
             RedisValuePy::Int(i) => i.write_redis_args(out),
+            RedisValuePy::BigInt(i) => out.write_arg(i.to_string().as_bytes()),
+            // The shortest representation that parses back to the same float
             RedisValuePy::Float(f) => f.write_redis_args(out),
             RedisValuePy::Bool(b) => b.write_redis_args(out),
             RedisValuePy::Array(a) => a.write_redis_args(out),
@@ -30,6 +80,325 @@ impl ToRedisArgs for RedisValuePy {
     }
 }
 
+impl RedisValuePy {
+    /// The number of arguments the value is sent as.
+    pub fn arg_count(&self) -> usize {
+        match self {
+            RedisValuePy::Array(a) => a.iter().map(Self::arg_count).sum(),
+            _ => 1,
+        }
+    }
+}
+
+/// A command and the positions of the stored values among its arguments,
+/// which are compressed on the runtime right before the command is sent if
+/// the pool compresses values.
+pub struct Request {
+    pub cmd: Cmd,
+    pub stored: Vec<usize>,
+}
+
+impl Request {
+    /// A command ending with `count` arguments that are stored values.
+    pub fn trailing(cmd: Cmd, count: usize) -> Self {
+        let len = cmd.args_iter().len();
+        Self {
+            stored: (len - count..len).collect(),
+            cmd,
+        }
+    }
+
+    /// A command ending with `items`, of which the values are stored.
+    pub fn pairs(cmd: Cmd, items: &[(RedisValuePy, RedisValuePy)]) -> Self {
+        let len: usize = items
+            .iter()
+            .map(|(k, v)| k.arg_count() + v.arg_count())
+            .sum();
+        let mut position = cmd.args_iter().len() - len;
+        let mut stored = Vec::with_capacity(items.len());
+        for (k, v) in items {
+            position += k.arg_count();
+            stored.extend(position..position + v.arg_count());
+            position += v.arg_count();
+        }
+        Self { cmd, stored }
+    }
+}
+
+impl From<Cmd> for Request {
+    fn from(cmd: Cmd) -> Self {
+        Self {
+            cmd,
+            stored: Vec::new(),
+        }
+    }
+}
+
+/// Convert the items of a collection, flattening nested ones.
+fn extract_items<'py>(
+    items: impl Iterator<Item = PyResult<Bound<'py, PyAny>>>,
+) -> PyResult<RedisValuePy> {
+    items
+        .map(|item| item?.extract())
+        .collect::<PyResult<_>>()
+        .map(RedisValuePy::Array)
+}
+
+impl FromPyObject<'_> for RedisValuePy {
+    /// `None` is sent as an empty string, dicts as their keys and values
+    /// alternating, and `Decimal` and `datetime` values as their string
+    /// representation. `bytes` and contiguous memoryviews are borrowed rather
+    /// than copied, `bytearray` is copied since it can be resized.
+    fn extract_bound(ob: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let py = ob.py();
+
+        if ob.is_none() {
+            Ok(Self::Bytes(Vec::new()))
+        } else if let Ok(b) = ob.downcast::<PyBool>() {
+            Ok(Self::Bool(b.is_true()))
+        } else if let Ok(s) = ob.downcast::<PyString>() {
+            Ok(Self::String(s.to_cow()?.into_owned()))
+        } else if ob.is_instance_of::<PyBytes>() || ob.is_instance_of::<PyByteArray>() {
+            Ok(Self::Borrowed(Arc::new(ob.extract::<PyBackedBytes>()?)))
+        } else if ob.is_instance_of::<PyMemoryView>() {
+            extract_memoryview(ob)
+        } else if ob.is_instance_of::<PyInt>() {
+            match ob.extract() {
+                Ok(i) => Ok(Self::Int(i)),
+                Err(_) => Ok(Self::BigInt(ob.extract()?)),
+            }
+        } else if let Ok(f) = ob.downcast::<PyFloat>() {
+            Ok(Self::Float(f.value()))
+        } else if let Ok(d) = ob.downcast::<PyDict>() {
+            let mut items = Vec::with_capacity(d.len() * 2);
+            for (k, v) in d.iter() {
+                items.push(k.extract()?);
+                items.push(v.extract()?);
+            }
+            Ok(Self::Array(items))
+        } else if ob.is_instance_of::<PySet>() || ob.is_instance_of::<PyFrozenSet>() {
+            extract_items(ob.iter()?)
+        } else if let Ok(s) = ob.downcast::<PySequence>() {
+            extract_items(s.iter()?)
+        } else if ob.is_instance(imported(&DECIMAL, py, "decimal", "Decimal")?)? {
+            Ok(Self::String(ob.str()?.to_cow()?.into_owned()))
+        } else if ob.is_instance(imported(&DATE, py, "datetime", "date")?)? {
+            let iso = ob.call_method0(intern!(py, "isoformat"))?;
+            Ok(Self::String(
+                iso.downcast::<PyString>()?.to_cow()?.into_owned(),
+            ))
+        } else {
+            let repr = ob.repr()?;
+            let mut repr = repr.to_cow()?.into_owned();
+            if repr.len() > 50 {
+                repr.truncate(repr.floor_char_boundary(47));
+                repr.push_str("...");
+            }
+            // Named by `extract_arg`
+            Err(ArgumentError::new_err(format!(
+                "cannot send {repr} to redis, {} is not supported",
+                ob.get_type().qualname()?
+            )))
+        }
+    }
+}
+
+/// Copy the contents of a memoryview to a new `bytes` object.
+fn tobytes(ob: &Bound<'_, PyAny>) -> PyResult<RedisValuePy> {
+    let bytes = ob.call_method0(intern!(ob.py(), "tobytes"))?;
+    Ok(RedisValuePy::Borrowed(Arc::new(
+        bytes.extract::<PyBackedBytes>()?,
+    )))
+}
+
+/// Borrow the memory of a contiguous memoryview of bytes. Views of other
+/// shapes or item types are copied.
+fn extract_memoryview(ob: &Bound<'_, PyAny>) -> PyResult<RedisValuePy> {
+    match PyBuffer::<u8>::get_bound(ob) {
+        Ok(buffer) if buffer.is_c_contiguous() => {
+            Ok(RedisValuePy::Borrowed(Arc::new(Buffer(buffer))))
+        }
+        _ => tobytes(ob),
+    }
+}
+
+/// Extract the argument `name` of a method, naming it in the `ArgumentError`
+/// raised for values that can't be sent. pyo3 only names the argument in a
+/// `TypeError`.
+pub fn extract_arg<'py, T: FromPyObject<'py>>(ob: &Bound<'py, PyAny>, name: &str) -> PyResult<T> {
+    ob.extract().map_err(|e| {
+        if e.is_instance_of::<ArgumentError>(ob.py()) {
+            ArgumentError::new_err(format!("argument '{name}': {}", e.value_bound(ob.py())))
+        } else {
+            e
+        }
+    })
+}
+
+/// Define a function per argument name that extracts the argument with
+/// `extract_arg`, for `#[pyo3(from_py_with = "...")]`.
+macro_rules! named_args {
+    ($($name:ident),* $(,)?) => {$(
+        pub fn $name<'py, T: ::pyo3::FromPyObject<'py>>(
+            ob: &::pyo3::Bound<'py, ::pyo3::PyAny>,
+        ) -> ::pyo3::PyResult<T> {
+            $crate::conversion::extract_arg(ob, stringify!($name))
+        }
+    )*};
+}
+pub(crate) use named_args;
+
+/// Extractors for the arguments of the methods written by hand.
+pub mod arg {
+    super::named_args!(
+        channel, delta, dstkey, element, field, items, key, keys, max, member, members, message,
+        min, new_key, score, srckey, srckeys, value,
+    );
+}
+
+/// Pairs of a key or field and a value, given as a dict or as a sequence of
+/// tuples. The values are left to be encoded by the pool.
+pub struct Pairs<'py>(pub Vec<(RedisValuePy, Bound<'py, PyAny>)>);
+
+impl<'py> FromPyObject<'py> for Pairs<'py> {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        match ob.downcast::<PyDict>() {
+            Ok(d) => d
+                .iter()
+                .map(|(k, v)| Ok((k.extract()?, v)))
+                .collect::<PyResult<_>>()
+                .map(Self),
+            Err(_) => ob.extract().map(Self),
+        }
+    }
+}
+
+/// Compression algorithm for values larger than a threshold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Zstd,
+    Lz4,
+}
+
+impl Compression {
+    pub fn from_name(name: &str) -> PyResult<Self> {
+        match name {
+            "zstd" => Ok(Self::Zstd),
+            "lz4" => Ok(Self::Lz4),
+            _ => Err(ArgumentError::new_err(format!(
+                "unknown compression {name:?}, expected one of \"zstd\" or \"lz4\""
+            ))),
+        }
+    }
+
+    fn tag(self) -> u8 {
+        match self {
+            Self::Zstd => 1,
+            Self::Lz4 => 2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Compressor {
+    pub algorithm: Compression,
+    /// Values shorter than this many bytes are stored as-is.
+    pub threshold: usize,
+}
+
+impl Compressor {
+    /// Compress `data` and prefix it with a header, or return `None` if it is
+    /// below the threshold or does not get smaller.
+    fn compress(self, data: &[u8]) -> Option<Vec<u8>> {
+        if data.len() < self.threshold {
+            return None;
+        }
+
+        let mut out = COMPRESSION_MAGIC.to_vec();
+        out.push(self.algorithm.tag());
+        match self.algorithm {
+            Compression::Zstd => out.extend(zstd::bulk::compress(data, 0).ok()?),
+            Compression::Lz4 => out.extend(lz4_flex::compress_prepend_size(data)),
+        }
+
+        (out.len() < data.len()).then_some(out)
+    }
+
+    /// Compress the arguments of `cmd` at the positions in `stored`.
+    pub fn compress_args(self, cmd: &Cmd, stored: &[usize]) -> Cmd {
+        let mut stored = stored.iter().peekable();
+        let mut compressed = Cmd::new();
+        for (i, arg) in cmd.args_iter().enumerate() {
+            match arg {
+                Arg::Simple(arg) if stored.next_if_eq(&&i).is_some() => {
+                    match self.compress(arg) {
+                        Some(c) => compressed.arg(c),
+                        None => compressed.arg(arg),
+                    };
+                }
+                Arg::Simple(arg) => {
+                    compressed.arg(arg);
+                }
+                Arg::Cursor => {
+                    compressed.cursor_arg(0);
+                }
+            }
+        }
+        compressed
+    }
+
+    /// Decompress the stored values in a reply in place. Values without a
+    /// header are left untouched.
+    pub fn decompress_reply(v: &mut Value, shape: ResponseShape) {
+        match (shape, v) {
+            (ResponseShape::Values | ResponseShape::Buffer, Value::Data(d)) => {
+                if let Some(decompressed) = decompress(d) {
+                    *d = decompressed;
+                }
+            }
+            (ResponseShape::Values | ResponseShape::Buffer, Value::Bulk(b)) => {
+                for v in b {
+                    Self::decompress_reply(v, shape);
+                }
+            }
+            (ResponseShape::Dict, Value::Bulk(b)) => {
+                for v in b.iter_mut().skip(1).step_by(2) {
+                    Self::decompress_reply(v, ResponseShape::Values);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Values that would decompress to more than this many bytes are returned
+/// as-is. It is the largest string redis stores by default.
+const MAX_DECOMPRESSED_SIZE: usize = 512 * 1024 * 1024;
+
+fn decompress(data: &[u8]) -> Option<Vec<u8>> {
+    let rest = data.strip_prefix(COMPRESSION_MAGIC)?;
+    let (&tag, payload) = rest.split_first()?;
+
+    // Both formats store the size of the original value in front, which is
+    // checked before anything is allocated
+    if tag == Compression::Zstd.tag() {
+        let size = zstd::zstd_safe::get_frame_content_size(payload).ok()??;
+        let size = usize::try_from(size).ok()?;
+        if size > MAX_DECOMPRESSED_SIZE {
+            return None;
+        }
+        zstd::bulk::decompress(payload, size).ok()
+    } else if tag == Compression::Lz4.tag() {
+        let (size, _) = lz4_flex::block::uncompressed_size(payload).ok()?;
+        if size > MAX_DECOMPRESSED_SIZE {
+            return None;
+        }
+        lz4_flex::decompress_size_prepended(payload).ok()
+    } else {
+        None
+    }
+}
+
 pub fn re_to_object(v: &Value, py: Python) -> PyObject {
     match v {
         Value::Nil => py.None(),
@@ -44,3 +413,113 @@ pub fn re_to_object(v: &Value, py: Python) -> PyObject {
         Value::Okay => true.to_object(py),
     }
 }
+
+/// The Python type a command's reply is converted into.
+#[derive(Clone, Copy, Debug)]
+pub enum ResponseShape {
+    /// Convert the reply as-is using `re_to_object`.
+    Generic,
+    /// Like `Generic`, but every bulk string is a stored value and decoded
+    /// with the pool's serializer.
+    Values,
+    /// A flat list of alternating fields and values as a `dict`, decoding the
+    /// values with the pool's serializer.
+    Dict,
+    /// A flat list of alternating members and scores as a list of
+    /// `(member, score)` tuples.
+    Scored,
+    /// An integer reply as a `bool`.
+    Bool,
+    /// A numeric bulk string reply as a `float`, or `None` if nil.
+    Float,
+    /// Like `Values`, but bulk strings are returned as `RawBuffer`s that share
+    /// their memory with the reply and are never decoded.
+    Buffer,
+}
+
+fn value_to_f64(v: &Value) -> Option<f64> {
+    match v {
+        Value::Data(d) => from_utf8(d).ok()?.parse().ok(),
+        #[allow(clippy::cast_precision_loss)]
+        Value::Int(i) => Some(*i as f64),
+        Value::Status(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Decode a stored value with `serializer`. Values that cannot be decoded, for
+/// example because they were written without a serializer, are returned as
+/// bytes.
+fn decode_value(v: &Value, serializer: Option<Serializer>, py: Python) -> PyObject {
+    match (serializer, v) {
+        (Some(s), Value::Data(d)) => s.decode(d, py).unwrap_or_else(|_| re_to_object(v, py)),
+        (Some(_), Value::Bulk(b)) => b
+            .iter()
+            .map(|i| decode_value(i, serializer, py))
+            .collect::<Vec<PyObject>>()
+            .to_object(py),
+        _ => re_to_object(v, py),
+    }
+}
+
+/// Convert a reply into `RawBuffer`s without copying the bulk strings in it.
+fn re_to_buffer(v: Value, py: Python) -> PyObject {
+    match v {
+        Value::Data(d) => RawBuffer::new(d).into_py(py),
+        Value::Bulk(b) => b
+            .into_iter()
+            .map(|i| re_to_buffer(i, py))
+            .collect::<Vec<PyObject>>()
+            .to_object(py),
+        v => re_to_object(&v, py),
+    }
+}
+
+/// Convert a reply into the Python type described by `shape`, falling back to
+/// `re_to_object` if the reply does not have the expected layout.
+pub fn re_to_object_shaped(
+    v: Value,
+    shape: ResponseShape,
+    serializer: Option<Serializer>,
+    py: Python,
+) -> PyObject {
+    if matches!(shape, ResponseShape::Buffer) {
+        return re_to_buffer(v, py);
+    }
+    let v = &v;
+    match (shape, v) {
+        (ResponseShape::Values, _) => decode_value(v, serializer, py),
+        (ResponseShape::Dict, Value::Bulk(b)) if b.len() % 2 == 0 => {
+            let dict = PyDict::new_bound(py);
+            for pair in b.chunks_exact(2) {
+                if dict
+                    .set_item(
+                        re_to_object(&pair[0], py),
+                        decode_value(&pair[1], serializer, py),
+                    )
+                    .is_err()
+                {
+                    return re_to_object(v, py);
+                }
+            }
+            dict.to_object(py)
+        }
+        (ResponseShape::Scored, Value::Bulk(b)) if b.len() % 2 == 0 => {
+            let mut items = Vec::with_capacity(b.len() / 2);
+            for pair in b.chunks_exact(2) {
+                match value_to_f64(&pair[1]) {
+                    Some(score) => items.push((re_to_object(&pair[0], py), score).to_object(py)),
+                    None => return re_to_object(v, py),
+                }
+            }
+            items.to_object(py)
+        }
+        (ResponseShape::Bool, Value::Int(i)) => (*i > 0).to_object(py),
+        (ResponseShape::Float, Value::Nil) => py.None(),
+        (ResponseShape::Float, _) => match value_to_f64(v) {
+            Some(f) => f.to_object(py),
+            None => re_to_object(v, py),
+        },
+        _ => re_to_object(v, py),
+    }
+}
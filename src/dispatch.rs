@@ -0,0 +1,231 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use pyo3::{
+    intern,
+    prelude::{pyclass, pymethods, PyObject, PyResult, Python},
+    types::{PyAnyMethods, PyDict, PyDictMethods},
+    Bound, IntoPy, Py, PyAny, PyErr,
+};
+use tokio::{
+    sync::Notify,
+    time::{timeout_at, Instant},
+};
+
+use crate::{
+    asyncio::EVENT_LOOP,
+    buffer::EventQueue,
+    exceptions::PubSubConnectionLost,
+    pubsub::{overflowed, Event, Message, RawMessage},
+};
+
+/// Calls handlers for batches of messages on the event loop and keeps track of
+/// how many batches and handler tasks have not finished yet.
+#[pyclass(module = "zangy", frozen)]
+struct Dispatcher {
+    handlers: HashMap<Vec<u8>, PyObject>,
+    in_flight: AtomicUsize,
+    finished: Notify,
+}
+
+impl Dispatcher {
+    fn handler(&self, msg: &RawMessage) -> Option<&PyObject> {
+        msg.pattern
+            .as_ref()
+            .and_then(|p| self.handlers.get(p))
+            .or_else(|| self.handlers.get(&msg.channel))
+    }
+
+    fn finish(&self) {
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+        self.finished.notify_one();
+    }
+
+    fn task_done(&self, py: Python, task: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.finish();
+
+        if !task.call_method0(intern!(py, "cancelled"))?.is_truthy()? {
+            let exc = task.call_method0(intern!(py, "exception"))?;
+            if !exc.is_none() {
+                report(
+                    py,
+                    "pubsub handler raised an exception",
+                    &PyErr::from_value_bound(exc),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Wait until fewer than `max` batches and tasks are in flight.
+    async fn wait_below(&self, max: usize) {
+        while self.in_flight.load(Ordering::Relaxed) >= max {
+            self.finished.notified().await;
+        }
+    }
+}
+
+/// Finishes a batch when dropped, so that it is counted as done even if
+/// scheduling one of its handlers failed.
+struct BatchGuard<'a>(&'a Dispatcher);
+
+impl Drop for BatchGuard<'_> {
+    fn drop(&mut self) {
+        self.0.finish();
+    }
+}
+
+/// The done callback of a handler task.
+#[pyclass(module = "zangy", frozen)]
+struct TaskDone(Py<Dispatcher>);
+
+#[pymethods]
+impl TaskDone {
+    fn __call__(&self, py: Python, task: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.0.get().task_done(py, task)
+    }
+}
+
+fn report(py: Python, message: &str, e: &PyErr) {
+    let context = PyDict::new_bound(py);
+    let _res = context.set_item(intern!(py, "message"), message);
+    let _res = context.set_item(intern!(py, "exception"), e.value_bound(py));
+    let _res = EVENT_LOOP.call_method1(py, intern!(py, "call_exception_handler"), (context,));
+}
+
+#[pymethods]
+impl Dispatcher {
+    fn __call__(slf: &Bound<'_, Self>, batch: Vec<(PyObject, PyObject)>) -> PyResult<()> {
+        let py = slf.py();
+        let this = slf.get();
+        let _guard = BatchGuard(this);
+        let asyncio = py.import_bound(intern!(py, "asyncio"))?;
+
+        for (handler, msg) in batch {
+            match handler.call1(py, (msg,)) {
+                Ok(res) => {
+                    if asyncio
+                        .call_method1(intern!(py, "iscoroutine"), (&res,))?
+                        .is_truthy()?
+                    {
+                        let task =
+                            EVENT_LOOP.call_method1(py, intern!(py, "create_task"), (res,))?;
+                        let done = TaskDone(slf.clone().unbind());
+                        this.in_flight.fetch_add(1, Ordering::Relaxed);
+                        if let Err(e) =
+                            task.call_method1(py, intern!(py, "add_done_callback"), (done,))
+                        {
+                            this.finish();
+                            return Err(e);
+                        }
+                    }
+                }
+                Err(e) => report(py, "pubsub handler raised an exception", &e),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Options {
+    pub batch_size: usize,
+    pub max_latency: Duration,
+    pub max_in_flight: usize,
+}
+
+/// Read messages until the context is exited and hand them to `handlers` in
+/// batches. A batch is dispatched once it is full or its first message has
+/// waited `max_latency`. No more messages are read while `max_in_flight`
+/// batches and coroutine handlers are unfinished.
+pub async fn run(
+    events: Arc<EventQueue>,
+    handlers: HashMap<Vec<u8>, PyObject>,
+    options: Options,
+) -> PyResult<()> {
+    let dispatcher = Python::with_gil(|py| {
+        Py::new(
+            py,
+            Dispatcher {
+                handlers,
+                in_flight: AtomicUsize::new(0),
+                finished: Notify::new(),
+            },
+        )
+    })?;
+    let mut batch = Vec::with_capacity(options.batch_size);
+
+    loop {
+        dispatcher.get().wait_below(options.max_in_flight).await;
+
+        let mut result = None;
+        let Some(first) = events.recv().await else {
+            return Ok(());
+        };
+        let deadline = Instant::now() + options.max_latency;
+        let mut event = Some(first);
+
+        while let Some(ev) = event.take() {
+            match ev {
+                Event::Message(msg) => batch.push(msg),
+                // Messages may have been missed, but handlers can't be told
+                Event::Reconnected => {}
+                Event::Lost(e) => {
+                    result = Some(Err(PubSubConnectionLost::new_err(format!(
+                        "pubsub connection lost and reconnecting failed: {e}"
+                    ))));
+                    break;
+                }
+                Event::Overflow => {
+                    result = Some(Err(overflowed()));
+                    break;
+                }
+            }
+
+            if batch.len() >= options.batch_size {
+                break;
+            }
+            match timeout_at(deadline, events.recv()).await {
+                Ok(Some(ev)) => event = Some(ev),
+                Ok(None) => result = Some(Ok(())),
+                Err(_) => {}
+            }
+        }
+
+        if !batch.is_empty() {
+            Python::with_gil(|py| {
+                let this = dispatcher.get();
+                let calls: Vec<(PyObject, PyObject)> = batch
+                    .drain(..)
+                    .filter_map(|msg| {
+                        let handler = this.handler(&msg)?.clone_ref(py);
+                        Some((handler, Message::from_raw(&msg, py).into_py(py)))
+                    })
+                    .collect();
+
+                this.in_flight.fetch_add(1, Ordering::Relaxed);
+                if let Err(e) = EVENT_LOOP.call_method1(
+                    py,
+                    intern!(py, "call_soon_threadsafe"),
+                    (dispatcher.clone_ref(py), calls),
+                ) {
+                    this.finish();
+                    return Err(e);
+                }
+
+                Ok(())
+            })?;
+        }
+
+        if let Some(result) = result {
+            return result;
+        }
+    }
+}
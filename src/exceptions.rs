@@ -1,7 +1,86 @@
-use pyo3::{create_exception, exceptions::PyException};
+use pyo3::{
+    create_exception,
+    exceptions::PyException,
+    intern,
+    prelude::{PyErr, Python},
+    types::{PyAnyMethods, PyType},
+    Bound,
+};
+use redis::ErrorKind;
 
-create_exception!(zangy, ConnectionError, PyException);
 create_exception!(zangy, ArgumentError, PyException);
 create_exception!(zangy, RedisError, PyException);
 create_exception!(zangy, PoolEmpty, PyException);
+create_exception!(zangy, PoolClosed, PyException);
 create_exception!(zangy, PubSubClosed, PyException);
+create_exception!(zangy, LockError, PyException);
+create_exception!(zangy, LockNotOwnedError, LockError);
+
+create_exception!(zangy, ConnectionError, RedisError);
+create_exception!(zangy, TimeoutError, ConnectionError);
+create_exception!(zangy, PubSubConnectionLost, ConnectionError);
+create_exception!(zangy, AuthenticationError, RedisError);
+create_exception!(zangy, ClientError, RedisError);
+
+create_exception!(zangy, ResponseError, RedisError);
+create_exception!(zangy, WrongTypeError, ResponseError);
+create_exception!(zangy, NoScriptError, ResponseError);
+create_exception!(zangy, ReadOnlyError, ResponseError);
+create_exception!(zangy, BusyLoadingError, ResponseError);
+create_exception!(zangy, BusyError, ResponseError);
+create_exception!(zangy, ExecAbortError, ResponseError);
+create_exception!(zangy, OutOfMemoryError, ResponseError);
+create_exception!(zangy, ClusterError, ResponseError);
+create_exception!(zangy, ClusterRedirectError, ClusterError);
+
+fn error_type<'py>(py: Python<'py>, e: &redis::RedisError) -> Bound<'py, PyType> {
+    match (e.kind(), e.code()) {
+        (ErrorKind::IoError, _) if e.is_timeout() => py.get_type_bound::<TimeoutError>(),
+        (ErrorKind::IoError, _) => py.get_type_bound::<ConnectionError>(),
+        (ErrorKind::AuthenticationFailed, _)
+        | (ErrorKind::ExtensionError, Some("NOAUTH" | "WRONGPASS" | "NOPERM")) => {
+            py.get_type_bound::<AuthenticationError>()
+        }
+        (ErrorKind::ExtensionError, Some("WRONGTYPE")) => py.get_type_bound::<WrongTypeError>(),
+        (ErrorKind::ExtensionError, Some("OOM")) => py.get_type_bound::<OutOfMemoryError>(),
+        (ErrorKind::ExtensionError, Some("BUSY")) => py.get_type_bound::<BusyError>(),
+        (ErrorKind::NoScriptError, _) => py.get_type_bound::<NoScriptError>(),
+        (ErrorKind::ReadOnly, _) => py.get_type_bound::<ReadOnlyError>(),
+        (ErrorKind::BusyLoadingError, _) => py.get_type_bound::<BusyLoadingError>(),
+        (ErrorKind::ExecAbortError, _) => py.get_type_bound::<ExecAbortError>(),
+        (ErrorKind::Moved | ErrorKind::Ask, _) => py.get_type_bound::<ClusterRedirectError>(),
+        (
+            ErrorKind::TryAgain
+            | ErrorKind::ClusterDown
+            | ErrorKind::CrossSlot
+            | ErrorKind::MasterDown
+            | ErrorKind::ClusterConnectionNotFound,
+            _,
+        ) => py.get_type_bound::<ClusterError>(),
+        (ErrorKind::ResponseError | ErrorKind::NotBusy | ErrorKind::ExtensionError, _) => {
+            py.get_type_bound::<ResponseError>()
+        }
+        (
+            ErrorKind::TypeError
+            | ErrorKind::ParseError
+            | ErrorKind::ClientError
+            | ErrorKind::InvalidClientConfig,
+            _,
+        ) => py.get_type_bound::<ClientError>(),
+        _ => py.get_type_bound::<RedisError>(),
+    }
+}
+
+/// Convert a redis-rs error into the matching exception class with the error
+/// code and detail as `.code` and `.detail`.
+pub fn redis_error(e: &redis::RedisError) -> PyErr {
+    Python::with_gil(|py| {
+        let err = PyErr::from_type_bound(error_type(py, e), e.to_string());
+        let value = err.value_bound(py);
+
+        let _res = value.setattr(intern!(py, "code"), e.code());
+        let _res = value.setattr(intern!(py, "detail"), e.detail());
+
+        err
+    })
+}
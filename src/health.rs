@@ -0,0 +1,189 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+use redis::{aio::MultiplexedConnection, Client, ConnectionInfo, RedisResult};
+use tokio::time::{timeout, Instant};
+
+use crate::{lifecycle::Lifecycle, metrics::Metrics, pubsub_pool::PubSubPool, resp};
+
+const RTT_UNKNOWN: u64 = u64::MAX;
+
+/// A pooled connection together with the result of its last health check and
+/// the client used to replace it.
+pub struct Slot {
+    conn: RwLock<MultiplexedConnection>,
+    client: Client,
+    /// The number of commands waiting for a reply on the connection.
+    in_flight: Arc<AtomicUsize>,
+    healthy: AtomicBool,
+    /// Round trip time of the last successful PING in microseconds.
+    rtt: AtomicU64,
+}
+
+impl Slot {
+    pub fn new(conn: MultiplexedConnection, client: Client) -> Self {
+        Self {
+            conn: RwLock::new(conn),
+            client,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            healthy: AtomicBool::new(true),
+            rtt: AtomicU64::new(RTT_UNKNOWN),
+        }
+    }
+
+    pub fn connection(&self) -> MultiplexedConnection {
+        self.conn.read().unwrap().clone()
+    }
+
+    /// The address of the server the connection is to.
+    pub fn info(&self) -> &ConnectionInfo {
+        self.client.get_connection_info()
+    }
+
+    /// The connection and a guard counting a command as in flight on it until
+    /// dropped.
+    pub fn acquire(&self) -> (MultiplexedConnection, Busy) {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        (self.connection(), Busy(self.in_flight.clone()))
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    pub fn rtt(&self) -> Option<Duration> {
+        match self.rtt.load(Ordering::Relaxed) {
+            RTT_UNKNOWN => None,
+            rtt => Some(Duration::from_micros(rtt)),
+        }
+    }
+
+    /// PING the connection and replace it if that fails. Returns whether it
+    /// was replaced.
+    async fn check(&self, limit: Duration) -> bool {
+        let mut conn = self.connection();
+        let start = Instant::now();
+        let res: Result<RedisResult<()>, _> =
+            timeout(limit, redis::cmd("PING").query_async(&mut conn)).await;
+
+        if let Ok(Ok(())) = res {
+            let rtt = u64::try_from(start.elapsed().as_micros()).unwrap_or(RTT_UNKNOWN - 1);
+            self.rtt.store(rtt, Ordering::Relaxed);
+            self.healthy.store(true, Ordering::Relaxed);
+            return false;
+        }
+
+        self.healthy.store(false, Ordering::Relaxed);
+        if let Ok(Ok(conn)) = timeout(limit, self.client.get_multiplexed_tokio_connection()).await {
+            *self.conn.write().unwrap() = conn;
+            self.rtt.store(RTT_UNKNOWN, Ordering::Relaxed);
+            self.healthy.store(true, Ordering::Relaxed);
+            return true;
+        }
+
+        false
+    }
+}
+
+pub struct Busy(Arc<AtomicUsize>);
+
+impl Drop for Busy {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// The connections to the primary followed by those to replicas, each with
+/// its role.
+pub fn roles<'a>(
+    primary: &'a [Slot],
+    replicas: &'a [Slot],
+) -> impl Iterator<Item = (&'static str, &'a Slot)> {
+    let primary = primary.iter().map(|slot| ("primary", slot));
+    let replicas = replicas.iter().map(|slot| ("replica", slot));
+    primary.chain(replicas)
+}
+
+/// PING every idle pubsub connection, replacing the ones that don't answer.
+/// Connections are checked one at a time, and `PubSubPool::try_take` waits
+/// for a check to finish rather than reporting the pool as empty.
+async fn check_pubsub(pool: &PubSubPool, client: &Client, metrics: &Metrics, limit: Duration) {
+    // Returned connections are put back at the end, so the oldest ones are
+    // the ones that have not been checked yet
+    for _ in 0..pool.idle() {
+        let Some(mut stream) = pool.take_for_check() else {
+            return;
+        };
+
+        let start = Instant::now();
+        if let Ok(Ok(())) = timeout(limit, resp::ping(&mut stream)).await {
+            pool.checked(Some(stream), Some(start.elapsed()));
+        } else if let Ok(Ok(stream)) =
+            timeout(limit, resp::connect(client.get_connection_info())).await
+        {
+            metrics.reconnected();
+            pool.checked(Some(stream), None);
+        } else {
+            pool.checked(None, None);
+        }
+    }
+}
+
+/// Open `count` connections to the server of `client`.
+pub async fn open(client: &Client, count: usize) -> RedisResult<Vec<Slot>> {
+    let mut slots = Vec::with_capacity(count);
+    for _ in 0..count {
+        let conn = client.get_multiplexed_tokio_connection().await?;
+        slots.push(Slot::new(conn, client.clone()));
+    }
+
+    Ok(slots)
+}
+
+/// Open `count` connections to each replica in `addresses`.
+pub async fn open_replicas(addresses: Vec<String>, count: usize) -> RedisResult<Vec<Slot>> {
+    let mut slots = Vec::with_capacity(addresses.len() * count);
+    for address in addresses {
+        slots.extend(open(&Client::open(address)?, count).await?);
+    }
+
+    Ok(slots)
+}
+
+/// Check all connections of a pool every `interval` until it is closed. The
+/// connections are released as soon as the pool is closed.
+pub async fn run(
+    slots: Arc<Vec<Slot>>,
+    replicas: Arc<Vec<Slot>>,
+    pubsub_pool: Arc<PubSubPool>,
+    client: Client,
+    lifecycle: Arc<Lifecycle>,
+    metrics: Arc<Metrics>,
+    interval: Duration,
+) {
+    loop {
+        tokio::select! {
+            () = tokio::time::sleep(interval) => {}
+            () = lifecycle.closed() => return,
+        }
+
+        check_pubsub(&pubsub_pool, &client, &metrics, interval).await;
+        for slot in slots.iter().chain(replicas.iter()) {
+            if lifecycle.is_closed() {
+                return;
+            }
+            if slot.check(interval).await {
+                metrics.reconnected();
+            }
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use redis::{Arg, Cmd, Value};
+
+/// Where the keys are in the arguments of a command, counted like in the
+/// reply to `COMMAND INFO` with the command name at position 0.
+#[derive(Clone, Copy)]
+enum Spec {
+    /// From `first` to `last` in steps of `step`, where a negative `last`
+    /// counts from the end.
+    Range {
+        first: usize,
+        last: isize,
+        step: usize,
+    },
+    /// The number of keys is at position `numkeys` and the keys follow it.
+    /// If `dest` is set, position 1 is a destination key.
+    NumKeys { numkeys: usize, dest: bool },
+    /// The pattern after `MATCH` of `SCAN`.
+    Scan,
+    /// The stream names of `XREAD` and `XREADGROUP`, the first half of the
+    /// arguments after `STREAMS`.
+    Streams,
+    /// The channel of `PUBLISH` and `SPUBLISH`, only prefixed if channels are.
+    Channel,
+}
+
+const ONE: Spec = Spec::Range {
+    first: 1,
+    last: 1,
+    step: 1,
+};
+const TWO: Spec = Spec::Range {
+    first: 1,
+    last: 2,
+    step: 1,
+};
+const ALL: Spec = Spec::Range {
+    first: 1,
+    last: -1,
+    step: 1,
+};
+const PAIRS: Spec = Spec::Range {
+    first: 1,
+    last: -1,
+    step: 2,
+};
+/// All but the last argument, which is a timeout.
+const ALL_BUT_TIMEOUT: Spec = Spec::Range {
+    first: 1,
+    last: -2,
+    step: 1,
+};
+const ALL_AFTER_OPERATION: Spec = Spec::Range {
+    first: 2,
+    last: -1,
+    step: 1,
+};
+
+fn spec(name: &[u8]) -> Option<Spec> {
+    let spec = match name.to_ascii_uppercase().as_slice() {
+        b"APPEND" | b"DECR" | b"DECRBY" | b"EXPIRE" | b"EXPIREAT" | b"GET" | b"GETBIT"
+        | b"GETDEL" | b"GETEX" | b"GETRANGE" | b"GETSET" | b"HDEL" | b"HEXISTS" | b"HGET"
+        | b"HGETALL" | b"HINCRBY" | b"HINCRBYFLOAT" | b"HKEYS" | b"HLEN" | b"HMGET" | b"HMSET"
+        | b"HSCAN" | b"HSET" | b"HSETNX" | b"HSTRLEN" | b"HVALS" | b"INCR" | b"INCRBY"
+        | b"INCRBYFLOAT" | b"LINDEX" | b"LINSERT" | b"LLEN" | b"LPOP" | b"LPOS" | b"LPUSH"
+        | b"LPUSHX" | b"LRANGE" | b"LREM" | b"LSET" | b"LTRIM" | b"PERSIST" | b"PEXPIRE"
+        | b"PEXPIREAT" | b"PFADD" | b"PSETEX" | b"PTTL" | b"RPOP" | b"RPUSH" | b"RPUSHX"
+        | b"SADD" | b"SCARD" | b"SET" | b"SETBIT" | b"SETEX" | b"SETNX" | b"SETRANGE"
+        | b"SISMEMBER" | b"SMEMBERS" | b"SMISMEMBER" | b"SPOP" | b"SRANDMEMBER" | b"SREM"
+        | b"SSCAN" | b"STRLEN" | b"BITCOUNT" | b"BITPOS" | b"TTL" | b"TYPE" | b"ZADD"
+        | b"ZCARD" | b"ZCOUNT" | b"ZINCRBY" | b"ZLEXCOUNT" | b"ZMSCORE" | b"ZPOPMAX"
+        | b"ZPOPMIN" | b"ZRANDMEMBER" | b"ZRANGE" | b"ZRANGEBYLEX" | b"ZRANGEBYSCORE"
+        | b"ZRANK" | b"ZREM" | b"ZREMRANGEBYLEX" | b"ZREMRANGEBYRANK" | b"ZREMRANGEBYSCORE"
+        | b"ZREVRANGE" | b"ZREVRANGEBYLEX" | b"ZREVRANGEBYSCORE" | b"ZREVRANK" | b"ZSCAN"
+        | b"ZSCORE" | b"DUMP" | b"RESTORE"
+        // The pattern, whose matches are stripped of the prefix
+        | b"KEYS" => ONE,
+        b"RENAME" | b"RENAMENX" | b"RPOPLPUSH" | b"SMOVE" | b"LMOVE" | b"BRPOPLPUSH"
+        | b"BLMOVE" | b"COPY" => TWO,
+        b"DEL" | b"EXISTS" | b"MGET" | b"PFCOUNT" | b"PFMERGE" | b"SDIFF" | b"SDIFFSTORE"
+        | b"SINTER" | b"SINTERSTORE" | b"SUNION" | b"SUNIONSTORE" | b"TOUCH" | b"UNLINK"
+        | b"WATCH" => ALL,
+        b"MSET" | b"MSETNX" => PAIRS,
+        b"BLPOP" | b"BRPOP" | b"BZPOPMAX" | b"BZPOPMIN" => ALL_BUT_TIMEOUT,
+        b"BITOP" => ALL_AFTER_OPERATION,
+        b"SCAN" => Spec::Scan,
+        b"ZUNIONSTORE" | b"ZINTERSTORE" | b"ZDIFFSTORE" => Spec::NumKeys {
+            numkeys: 2,
+            dest: true,
+        },
+        b"ZUNION" | b"ZINTER" | b"ZDIFF" | b"ZINTERCARD" | b"SINTERCARD" | b"LMPOP"
+        | b"ZMPOP" => Spec::NumKeys {
+            numkeys: 1,
+            dest: false,
+        },
+        b"EVAL" | b"EVALSHA" | b"EVAL_RO" | b"EVALSHA_RO" | b"FCALL" | b"FCALL_RO" | b"BLMPOP"
+        | b"BZMPOP" => Spec::NumKeys {
+            numkeys: 2,
+            dest: false,
+        },
+        b"XREAD" | b"XREADGROUP" => Spec::Streams,
+        b"PUBLISH" | b"SPUBLISH" => Spec::Channel,
+        _ => return None,
+    };
+
+    Some(spec)
+}
+
+// `generated_spec`, the key positions of the commands in `commands.json`
+include!(concat!(env!("OUT_DIR"), "/key_specs.rs"));
+
+/// The position of the `STREAMS` token of `XREAD` and `XREADGROUP`, skipping
+/// the group and consumer names, which could be spelled the same.
+fn streams_token(args: &[&[u8]]) -> Option<usize> {
+    let mut idx = 1;
+    while let Some(arg) = args.get(idx) {
+        if arg.eq_ignore_ascii_case(b"STREAMS") {
+            return Some(idx);
+        }
+        idx += if arg.eq_ignore_ascii_case(b"GROUP") { 3 } else { 1 };
+    }
+
+    None
+}
+
+/// Whether the argument at `idx` is a key.
+fn is_key(spec: Spec, idx: usize, args: &[&[u8]]) -> bool {
+    match spec {
+        Spec::Range { first, last, step } => {
+            let last = if last < 0 {
+                args.len().checked_sub(last.unsigned_abs())
+            } else {
+                Some(last.unsigned_abs())
+            };
+
+            last.is_some_and(|last| {
+                idx >= first && idx <= last && (idx - first).is_multiple_of(step)
+            })
+        }
+        Spec::NumKeys { numkeys, dest } => {
+            // A missing or invalid count is left for the server to reject
+            let count = args
+                .get(numkeys)
+                .and_then(|n| std::str::from_utf8(n).ok())
+                .and_then(|n| n.parse::<usize>().ok())
+                .unwrap_or(0);
+
+            (dest && idx == 1) || (idx > numkeys && idx <= numkeys + count)
+        }
+        Spec::Scan => idx > 0 && args[idx - 1].eq_ignore_ascii_case(b"MATCH"),
+        Spec::Streams => streams_token(args).is_some_and(|token| {
+            let count = (args.len() - token - 1) / 2;
+            idx > token && idx <= token + count
+        }),
+        Spec::Channel => idx == 1,
+    }
+}
+
+fn args(cmd: &Cmd) -> Vec<&[u8]> {
+    cmd.args_iter()
+        .filter_map(|arg| match arg {
+            Arg::Simple(arg) => Some(arg),
+            Arg::Cursor => None,
+        })
+        .collect()
+}
+
+/// Prepend `prefix` to every key in `cmd`, and to the channel it publishes to
+/// if `channels` is set. Commands not in the table are returned unchanged.
+pub fn prefix(cmd: Cmd, prefix: &[u8], channels: bool) -> Cmd {
+    let args = args(&cmd);
+    let spec = args
+        .first()
+        .and_then(|name| spec(name).or_else(|| generated_spec(name, args.get(1).copied())));
+    let spec = match spec {
+        Some(Spec::Channel) if !channels => None,
+        spec => spec,
+    };
+    let Some(spec) = spec else {
+        return cmd;
+    };
+
+    let mut prefixed = Cmd::new();
+    for (idx, arg) in args.iter().enumerate() {
+        if is_key(spec, idx, &args) {
+            prefixed.arg([prefix, arg].concat());
+        } else {
+            prefixed.arg(*arg);
+        }
+    }
+
+    // Without a pattern, SCAN would return keys outside the namespace
+    if matches!(spec, Spec::Scan) && !args.iter().any(|arg| arg.eq_ignore_ascii_case(b"MATCH")) {
+        prefixed.arg("MATCH").arg([prefix, b"*"].concat());
+    }
+
+    prefixed
+}
+
+fn strip_data(value: &mut Value, prefix: &[u8]) {
+    if let Value::Data(data) = value {
+        if let Some(stripped) = data.strip_prefix(prefix) {
+            *data = stripped.to_vec();
+        }
+    }
+}
+
+/// Remove `prefix` from the keys in the reply to `cmd`, for commands that
+/// return key names.
+pub fn strip(cmd: &Cmd, reply: &mut Value, prefix: &[u8]) {
+    let name = match cmd.args_iter().next() {
+        Some(Arg::Simple(name)) => name.to_ascii_uppercase(),
+        _ => return,
+    };
+
+    match (name.as_slice(), reply) {
+        (b"KEYS", Value::Bulk(keys)) => {
+            for key in keys {
+                strip_data(key, prefix);
+            }
+        }
+        (b"SCAN", Value::Bulk(reply)) => {
+            if let Some(Value::Bulk(keys)) = reply.get_mut(1) {
+                for key in keys {
+                    strip_data(key, prefix);
+                }
+            }
+        }
+        (
+            b"BLPOP" | b"BRPOP" | b"BZPOPMIN" | b"BZPOPMAX" | b"LMPOP" | b"BLMPOP" | b"ZMPOP"
+            | b"BZMPOP",
+            Value::Bulk(reply),
+        ) => {
+            if let Some(key) = reply.first_mut() {
+                strip_data(key, prefix);
+            }
+        }
+        (b"XREAD" | b"XREADGROUP", Value::Bulk(streams)) => {
+            for stream in streams {
+                if let Value::Bulk(stream) = stream {
+                    if let Some(key) = stream.first_mut() {
+                        strip_data(key, prefix);
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,198 @@
+use pyo3::{
+    prelude::{pyclass, pymethods, PyObject, PyResult, Python},
+    types::{PyAnyMethods, PyIterator},
+    Bound, IntoPy,
+};
+use redis::Value;
+
+use crate::pubsub::RawMessage;
+
+const KEYSPACE_PREFIX: &[u8] = b"__keyspace@";
+const KEYEVENT_PREFIX: &[u8] = b"__keyevent@";
+
+/// A keyspace notification.
+#[pyclass(module = "zangy", frozen, get_all)]
+pub struct KeyspaceEvent {
+    /// The name of the event, for example `"set"`, `"del"` or `"expired"`.
+    event: String,
+    key: String,
+    db: u32,
+}
+
+#[pymethods]
+impl KeyspaceEvent {
+    // Allows unpacking an event into (event, key, db)
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        let triple: PyObject = (self.event.clone(), self.key.clone(), self.db).into_py(py);
+        triple.into_bound(py).iter()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "KeyspaceEvent(event={:?}, key={:?}, db={})",
+            self.event, self.key, self.db
+        )
+    }
+}
+
+/// Decides which channel to listen on for keyspace notifications and turns
+/// the messages received on it into events.
+pub struct KeyspaceListener {
+    /// Only set if the keys can't be filtered by the channel pattern.
+    key_pattern: Option<Vec<u8>>,
+    /// Removed from the keys of events, which are ignored without it.
+    key_prefix: Vec<u8>,
+}
+
+impl KeyspaceListener {
+    /// Returns the listener and the channel pattern to subscribe to. Keyspace
+    /// channels are used if `events` enables them, keyevent channels
+    /// otherwise, in which case keys are matched against `pattern` locally.
+    /// Only keys starting with `key_prefix` are matched and it is removed
+    /// from them.
+    pub fn new(
+        pattern: &str,
+        events: Option<&str>,
+        db: Option<u32>,
+        key_prefix: &[u8],
+    ) -> (Self, Vec<u8>) {
+        let db = db.map_or_else(|| "*".to_string(), |db| db.to_string());
+        let pattern = [key_prefix, pattern.as_bytes()].concat();
+
+        match events {
+            Some(events) if !events.contains('K') && events.contains('E') => (
+                Self {
+                    key_pattern: Some(pattern),
+                    key_prefix: key_prefix.to_vec(),
+                },
+                format!("__keyevent@{db}__:*").into_bytes(),
+            ),
+            _ => (
+                Self {
+                    key_pattern: None,
+                    key_prefix: key_prefix.to_vec(),
+                },
+                [format!("__keyspace@{db}__:").as_bytes(), &pattern].concat(),
+            ),
+        }
+    }
+
+    /// Parse a message, returning `None` for anything that is not a matching
+    /// keyspace notification.
+    pub fn parse(&self, msg: &RawMessage) -> Option<KeyspaceEvent> {
+        let Value::Data(ref payload) = msg.payload else {
+            return None;
+        };
+
+        let (is_keyspace, rest) = if let Some(rest) = msg.channel.strip_prefix(KEYSPACE_PREFIX) {
+            (true, rest)
+        } else {
+            (false, msg.channel.strip_prefix(KEYEVENT_PREFIX)?)
+        };
+
+        // The remainder is `<db>__:<key or event>`
+        let sep = rest.iter().position(|&b| b == b'_')?;
+        let db = std::str::from_utf8(&rest[..sep]).ok()?.parse().ok()?;
+        let name = rest[sep..].strip_prefix(b"__:")?;
+
+        let (event, key) = if is_keyspace {
+            (payload.as_slice(), name)
+        } else {
+            (name, payload.as_slice())
+        };
+
+        if let Some(ref pattern) = self.key_pattern {
+            if !glob_match(pattern, key) {
+                return None;
+            }
+        }
+        let key = key.strip_prefix(self.key_prefix.as_slice())?;
+
+        Some(KeyspaceEvent {
+            event: String::from_utf8_lossy(event).into_owned(),
+            key: String::from_utf8_lossy(key).into_owned(),
+            db,
+        })
+    }
+}
+
+/// Match `s` against a glob-style pattern the way redis does for `KEYS` and
+/// `PSUBSCRIBE`, supporting `*`, `?`, `[...]` and `\` escapes. On a mismatch
+/// it backtracks to the last `*` only, so it runs in `O(pattern * s)`.
+fn glob_match(pattern: &[u8], s: &[u8]) -> bool {
+    let (mut p, mut i) = (0, 0);
+    // The position after the last `*` and the position in `s` it is
+    // currently matched up to
+    let mut star = None;
+
+    while i < s.len() {
+        let step = match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p + 1, i));
+                p += 1;
+                continue;
+            }
+            Some(b'?') => Some(1),
+            Some(b'[') => match_class(&pattern[p + 1..], s[i]).map(|len| len + 1),
+            Some(b'\\') if p + 1 < pattern.len() => (pattern[p + 1] == s[i]).then_some(2),
+            Some(&c) => (c == s[i]).then_some(1),
+            None => None,
+        };
+
+        match (step, star) {
+            (Some(step), _) => {
+                p += step;
+                i += 1;
+            }
+            // Let the last `*` take one more character
+            (None, Some((after_star, matched))) => {
+                p = after_star;
+                i = matched + 1;
+                star = Some((after_star, i));
+            }
+            (None, None) => return false,
+        }
+    }
+
+    pattern[p.min(pattern.len())..].iter().all(|&c| c == b'*')
+}
+
+/// Match `c` against the character class at the start of `class`, which
+/// follows a `[`. Returns the length of the class including the closing `]`
+/// if it matches.
+fn match_class(class: &[u8], c: u8) -> Option<usize> {
+    let (negate, mut rest) = match class.split_first() {
+        Some((b'^', rest)) => (true, rest),
+        _ => (false, class),
+    };
+
+    let mut matched = false;
+    loop {
+        match rest {
+            [] => break,
+            [b']', tail @ ..] => {
+                rest = tail;
+                break;
+            }
+            [b'\\', escaped, tail @ ..] => {
+                matched |= *escaped == c;
+                rest = tail;
+            }
+            [start, b'-', end, tail @ ..] if *end != b']' => {
+                let (lo, hi) = if start <= end {
+                    (*start, *end)
+                } else {
+                    (*end, *start)
+                };
+                matched |= (lo..=hi).contains(&c);
+                rest = tail;
+            }
+            [single, tail @ ..] => {
+                matched |= *single == c;
+                rest = tail;
+            }
+        }
+    }
+
+    (matched != negate).then_some(class.len() - rest.len())
+}
@@ -6,82 +6,198 @@
     clippy::doc_markdown,
     internal_features
 )]
-use std::sync::{atomic::AtomicUsize, Arc, Mutex};
+use std::{
+    sync::{atomic::AtomicUsize, Arc},
+    time::Duration,
+};
 
 use pyo3::{
     prelude::{pyfunction, pymodule, IntoPy, PyModule, PyObject, PyResult, Python},
     types::PyModuleMethods,
-    wrap_pyfunction, Bound,
+    wrap_pyfunction, Bound, PyAny,
 };
-use redis::Client;
+use redis::{Client, RedisResult};
 
 mod asyncio;
+mod buffer;
+mod bulk;
+mod chunks;
+mod commands;
 mod conversion;
+mod dispatch;
 mod exceptions;
+mod health;
+mod keys;
+mod keyspace;
+mod lifecycle;
+mod lock;
+mod metrics;
+mod middleware;
 mod pool;
+mod pubsub;
+mod pubsub_pool;
+mod raw;
+mod resp;
 mod runtime;
+mod serializer;
+mod trace;
 
-/// Connect to a redis server at `address` and use up to `pool_size`
-/// connections.
-#[pyfunction]
-#[pyo3(text_signature = "(address, pool_size, pubsub_size)")]
-fn create_pool(address: String, pool_size: u16, pubsub_size: u16) -> PyResult<PyObject> {
-    let (fut, res_fut) = asyncio::create_future()?;
-
-    runtime::RUNTIME.spawn(async move {
-        let client = Client::open(address);
+/// The connections opened when a pool is created.
+struct Connections {
+    client: Client,
+    primary: Vec<health::Slot>,
+    replicas: Vec<health::Slot>,
+    pubsub: Vec<resp::Stream>,
+}
 
-        match client {
-            Ok(client) => {
-                let mut connections = Vec::with_capacity(pool_size as usize);
-                for _ in 0..pool_size {
-                    let connection = client.get_multiplexed_tokio_connection().await;
+async fn connect(
+    address: String,
+    replicas: Vec<String>,
+    pool_size: u16,
+    pubsub_size: u16,
+) -> RedisResult<Connections> {
+    let client = Client::open(address)?;
+    let primary = health::open(&client, pool_size as usize).await?;
+    let replicas = health::open_replicas(replicas, pool_size as usize).await?;
 
-                    match connection {
-                        Ok(conn) => connections.push(conn),
-                        Err(e) => {
-                            let _res = asyncio::set_fut_exc(
-                                &fut,
-                                exceptions::ConnectionError::new_err(e.to_string()),
-                            );
-                            return;
-                        }
-                    }
-                }
-                let mut pubsub_connections = Vec::with_capacity(pubsub_size as usize);
-                for _ in 0..pubsub_size {
-                    let pubsub = client.get_async_pubsub().await;
+    let mut pubsub = Vec::with_capacity(pubsub_size as usize);
+    for _ in 0..pubsub_size {
+        pubsub.push(resp::connect(client.get_connection_info()).await?);
+    }
 
-                    match pubsub {
-                        Ok(conn) => pubsub_connections.push(conn),
-                        Err(e) => {
-                            let _res = asyncio::set_fut_exc(
-                                &fut,
-                                exceptions::ConnectionError::new_err(e.to_string()),
-                            );
-                            return;
-                        }
-                    }
-                }
+    Ok(Connections {
+        client,
+        primary,
+        replicas,
+        pubsub,
+    })
+}
 
-                let pool = pool::ConnectionPool {
-                    current: AtomicUsize::new(0),
-                    pool: connections,
-                    pubsub_pool: Arc::new(Mutex::new(pubsub_connections)),
-                    pool_size: pool_size as usize,
-                };
+/// Connect to a redis server at `address` and use up to `pool_size`
+/// connections. If `serializer` is one of `"json"`, `"msgpack"` or `"pickle"`,
+/// values are encoded with it when written and decoded when read. If
+/// `compression` is `"zstd"` or `"lz4"`, values of at least
+/// `compression_threshold` bytes are compressed. Up to `max_pubsub_size`
+/// pubsub connections are opened on demand by `acquire_pubsub`. If
+/// `health_check_interval` is set, all connections are PINGed every that many
+/// seconds and replaced if they don't answer. For every address in `replicas`,
+/// `pool_size` connections are opened that read-only commands are sent to.
+/// If `on_span` is set, it is called on the event loop with a span for every
+/// command, in the `contextvars` context the command was issued from. The
+/// span includes the statement with its arguments masked if
+/// `trace_statements` is set. `middleware` is a list of hooks that can
+/// rewrite commands and their replies, see `KeyNamespace` and `SlowLog`.
+/// If `key_prefix` is set, it is prepended to all keys, and to pubsub
+/// channels if `prefix_channels` is set.
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+#[pyo3(signature = (
+    address,
+    pool_size,
+    pubsub_size,
+    serializer = None,
+    compression = None,
+    compression_threshold = 1024,
+    max_pubsub_size = None,
+    health_check_interval = None,
+    replicas = Vec::new(),
+    on_span = None,
+    trace_statements = false,
+    middleware = Vec::new(),
+    key_prefix = None,
+    prefix_channels = false,
+))]
+#[pyo3(
+    text_signature = "(address, pool_size, pubsub_size, serializer = None, compression = None, compression_threshold = 1024, max_pubsub_size = None, health_check_interval = None, replicas = [], on_span = None, trace_statements = False, middleware = [], key_prefix = None, prefix_channels = False)"
+)]
+fn create_pool(
+    address: String,
+    pool_size: u16,
+    pubsub_size: u16,
+    serializer: Option<&str>,
+    compression: Option<&str>,
+    compression_threshold: usize,
+    max_pubsub_size: Option<u16>,
+    health_check_interval: Option<f64>,
+    replicas: Vec<String>,
+    on_span: Option<PyObject>,
+    trace_statements: bool,
+    middleware: Vec<Bound<'_, PyAny>>,
+    key_prefix: Option<&str>,
+    prefix_channels: bool,
+) -> PyResult<PyObject> {
+    let serializer = serializer
+        .map(serializer::Serializer::from_name)
+        .transpose()?;
+    let compressor = compression
+        .map(conversion::Compression::from_name)
+        .transpose()?
+        .map(|algorithm| conversion::Compressor {
+            algorithm,
+            threshold: compression_threshold,
+        });
+    let health_check_interval = health_check_interval
+        .map(Duration::try_from_secs_f64)
+        .transpose()
+        .map_err(|e| {
+            exceptions::ArgumentError::new_err(format!("invalid health_check_interval: {e}"))
+        })?;
+    let tracer = on_span.map(|callback| Arc::new(trace::Tracer::new(callback, trace_statements)));
+    let mut middleware = middleware::Chain::from_py(middleware);
+    if let Some(prefix) = key_prefix {
+        middleware.push(Box::new(middleware::KeyNamespace::new(
+            prefix,
+            prefix_channels,
+        )));
+    }
+    let middleware = Arc::new(middleware);
+    let key_prefix = key_prefix.unwrap_or_default().as_bytes().to_vec();
+    let (fut, res_fut) = asyncio::create_future()?;
 
-                let _res = Python::with_gil(|py| {
-                    asyncio::set_fut_result_with_gil(&fut, pool.into_py(py), py)
-                });
-            }
+    runtime::RUNTIME.spawn(async move {
+        let connections = match connect(address, replicas, pool_size, pubsub_size).await {
+            Ok(connections) => connections,
             Err(e) => {
-                let _res = asyncio::set_fut_exc(
-                    &fut,
-                    exceptions::ConnectionError::new_err(format!("{e}")),
-                );
+                let _res = asyncio::set_fut_exc(&fut, exceptions::redis_error(&e));
+                return;
             }
+        };
+
+        let pool = pool::ConnectionPool {
+            current: AtomicUsize::new(0),
+            pool: Arc::new(connections.primary),
+            replicas: Arc::new(connections.replicas),
+            replica_current: AtomicUsize::new(0),
+            pubsub_pool: Arc::new(pubsub_pool::PubSubPool::new(
+                connections.pubsub,
+                max_pubsub_size.unwrap_or(pubsub_size).max(pubsub_size) as usize,
+            )),
+            pool_size: pool_size as usize,
+            serializer,
+            compressor,
+            client: connections.client,
+            lifecycle: Arc::new(lifecycle::Lifecycle::default()),
+            metrics: Arc::new(metrics::Metrics::default()),
+            tracer,
+            middleware,
+            key_prefix,
+            prefix_channels,
+        };
+
+        if let Some(interval) = health_check_interval {
+            runtime::RUNTIME.spawn(health::run(
+                pool.pool.clone(),
+                pool.replicas.clone(),
+                pool.pubsub_pool.clone(),
+                pool.client.clone(),
+                pool.lifecycle.clone(),
+                pool.metrics.clone(),
+                interval,
+            ));
         }
+
+        let _res =
+            Python::with_gil(|py| asyncio::set_fut_result_with_gil(&fut, pool.into_py(py), py));
     });
 
     Ok(res_fut)
@@ -91,20 +207,87 @@ fn create_pool(address: String, pool_size: u16, pubsub_size: u16) -> PyResult<Py
 fn zangy(py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(create_pool, m)?)?;
     m.add_class::<pool::ConnectionPool>()?;
-    m.add(
-        "ConnectionError",
-        py.get_type_bound::<exceptions::ConnectionError>(),
-    )?;
+    m.add_class::<pubsub::Message>()?;
+    m.add_class::<pubsub::PubSubContext>()?;
+    m.add_class::<keyspace::KeyspaceEvent>()?;
+    m.add_class::<middleware::KeyNamespace>()?;
+    m.add_class::<middleware::SlowLog>()?;
+    m.add_class::<raw::RawBuffer>()?;
+    m.add_class::<chunks::Chunks>()?;
+    m.add_class::<lock::Lock>()?;
     m.add(
         "ArgumentError",
         py.get_type_bound::<exceptions::ArgumentError>(),
     )?;
     m.add("RedisError", py.get_type_bound::<exceptions::RedisError>())?;
     m.add("PoolEmpty", py.get_type_bound::<exceptions::PoolEmpty>())?;
+    m.add("PoolClosed", py.get_type_bound::<exceptions::PoolClosed>())?;
     m.add(
         "PubSubClosed",
         py.get_type_bound::<exceptions::PubSubClosed>(),
     )?;
+    m.add("LockError", py.get_type_bound::<exceptions::LockError>())?;
+    m.add(
+        "LockNotOwnedError",
+        py.get_type_bound::<exceptions::LockNotOwnedError>(),
+    )?;
+    m.add(
+        "ConnectionError",
+        py.get_type_bound::<exceptions::ConnectionError>(),
+    )?;
+    m.add(
+        "TimeoutError",
+        py.get_type_bound::<exceptions::TimeoutError>(),
+    )?;
+    m.add(
+        "PubSubConnectionLost",
+        py.get_type_bound::<exceptions::PubSubConnectionLost>(),
+    )?;
+    m.add(
+        "AuthenticationError",
+        py.get_type_bound::<exceptions::AuthenticationError>(),
+    )?;
+    m.add(
+        "ClientError",
+        py.get_type_bound::<exceptions::ClientError>(),
+    )?;
+    m.add(
+        "ResponseError",
+        py.get_type_bound::<exceptions::ResponseError>(),
+    )?;
+    m.add(
+        "WrongTypeError",
+        py.get_type_bound::<exceptions::WrongTypeError>(),
+    )?;
+    m.add(
+        "NoScriptError",
+        py.get_type_bound::<exceptions::NoScriptError>(),
+    )?;
+    m.add(
+        "ReadOnlyError",
+        py.get_type_bound::<exceptions::ReadOnlyError>(),
+    )?;
+    m.add(
+        "BusyLoadingError",
+        py.get_type_bound::<exceptions::BusyLoadingError>(),
+    )?;
+    m.add("BusyError", py.get_type_bound::<exceptions::BusyError>())?;
+    m.add(
+        "ExecAbortError",
+        py.get_type_bound::<exceptions::ExecAbortError>(),
+    )?;
+    m.add(
+        "OutOfMemoryError",
+        py.get_type_bound::<exceptions::OutOfMemoryError>(),
+    )?;
+    m.add(
+        "ClusterError",
+        py.get_type_bound::<exceptions::ClusterError>(),
+    )?;
+    m.add(
+        "ClusterRedirectError",
+        py.get_type_bound::<exceptions::ClusterRedirectError>(),
+    )?;
 
     Ok(())
 }
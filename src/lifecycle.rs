@@ -0,0 +1,78 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+
+use pyo3::prelude::PyResult;
+use tokio::sync::Notify;
+
+use crate::exceptions::PoolClosed;
+
+/// Tracks whether a pool has been closed and how many of its commands are
+/// still running.
+#[derive(Default)]
+pub struct Lifecycle {
+    closed: AtomicBool,
+    in_flight: AtomicUsize,
+    idle: Notify,
+    closing: Notify,
+}
+
+impl Lifecycle {
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        self.closing.notify_waiters();
+    }
+
+    /// Wait until the pool is closed.
+    pub async fn closed(&self) {
+        loop {
+            let closing = self.closing.notified();
+            if self.is_closed() {
+                return;
+            }
+            closing.await;
+        }
+    }
+
+    pub fn ensure_open(&self) -> PyResult<()> {
+        if self.is_closed() {
+            Err(PoolClosed::new_err("pool has been closed"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Register a command, failing if the pool has been closed. The command
+    /// counts as running until the guard is dropped.
+    pub fn start(self: &Arc<Self>) -> PyResult<InFlight> {
+        self.ensure_open()?;
+        self.in_flight.fetch_add(1, Ordering::AcqRel);
+        Ok(InFlight(self.clone()))
+    }
+
+    /// Wait until no commands are running.
+    pub async fn wait_idle(&self) {
+        loop {
+            let idle = self.idle.notified();
+            if self.in_flight.load(Ordering::Acquire) == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+}
+
+pub struct InFlight(Arc<Lifecycle>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if self.0.in_flight.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
@@ -0,0 +1,351 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use pyo3::{
+    intern,
+    prelude::{pyclass, pymethods, Py, PyErr, PyObject, PyResult, Python},
+    types::PyAnyMethods,
+    IntoPy,
+};
+use redis::{Cmd, Value};
+use tokio::task::JoinHandle;
+
+use crate::{
+    asyncio::{create_future, set_fut_exc, set_fut_result_with_gil},
+    exceptions::{redis_error, ArgumentError, LockError, LockNotOwnedError, PoolClosed},
+    pool::{ConnectionPool, Prefer},
+    runtime::RUNTIME,
+};
+
+/// Deletes the lock if it still holds our token.
+const RELEASE: &str = r#"if redis.call("get", KEYS[1]) == ARGV[1] then
+    return redis.call("del", KEYS[1])
+end
+return 0"#;
+
+/// Resets the expiry of the lock if it still holds our token.
+const EXTEND: &str = r#"if redis.call("get", KEYS[1]) == ARGV[1] then
+    return redis.call("pexpire", KEYS[1], ARGV[2])
+end
+return 0"#;
+
+/// Convert `seconds` to a duration, failing if it is negative or too large.
+fn duration(seconds: f64, name: &str) -> PyResult<Duration> {
+    Duration::try_from_secs_f64(seconds)
+        .map_err(|e| ArgumentError::new_err(format!("invalid {name}: {e}")))
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+async fn query(pool: &Py<ConnectionPool>, cmd: Cmd) -> PyResult<Value> {
+    let reply = Python::with_gil(|py| pool.borrow(py).send_cmd(cmd, Prefer::Primary))?;
+    reply.await.1.map_err(|e| redis_error(&e))
+}
+
+fn extend_cmd(name: &str, token: &str, timeout: Duration) -> Cmd {
+    let mut cmd = redis::cmd("EVAL");
+    cmd.arg(EXTEND)
+        .arg(1)
+        .arg(name)
+        .arg(token)
+        .arg(millis(timeout));
+    cmd
+}
+
+/// Resolve a new future with the outcome of `task`.
+fn spawn<T>(task: impl Future<Output = PyResult<T>> + Send + 'static) -> PyResult<PyObject>
+where
+    T: IntoPy<PyObject> + Send + 'static,
+{
+    let (fut, res_fut) = create_future()?;
+
+    RUNTIME.spawn(async move {
+        let res = match task.await {
+            Ok(res) => Python::with_gil(|py| set_fut_result_with_gil(&fut, res.into_py(py), py)),
+            Err(e) => set_fut_exc(&fut, e),
+        };
+        if let Err(e) = res {
+            eprintln!("{e:?}");
+        }
+    });
+
+    Ok(res_fut)
+}
+
+/// Extend the lock every third of its timeout until it is no longer ours.
+async fn renew(inner: Arc<Inner>, token: String) {
+    loop {
+        tokio::time::sleep(inner.timeout / 3).await;
+        match query(&inner.pool, extend_cmd(&inner.name, &token, inner.timeout)).await {
+            Ok(Value::Int(0)) => {
+                // Expired, so it can be acquired again
+                inner.lost(&token);
+                return;
+            }
+            Err(e) if Python::with_gil(|py| e.is_instance_of::<PoolClosed>(py)) => return,
+            // Connection errors are retried in the next round
+            _ => {}
+        }
+    }
+}
+
+/// The token of the lock while it is held by us.
+#[derive(Default)]
+struct Held {
+    token: Option<String>,
+    renewer: Option<JoinHandle<()>>,
+    /// Whether an `acquire` is running, so that a concurrent one fails.
+    acquiring: bool,
+}
+
+impl Held {
+    /// Forget the token and stop renewing the lock.
+    fn take(&mut self) -> Option<String> {
+        if let Some(renewer) = self.renewer.take() {
+            renewer.abort();
+        }
+        self.token.take()
+    }
+}
+
+struct Inner {
+    pool: Py<ConnectionPool>,
+    name: String,
+    timeout: Duration,
+    blocking_timeout: Option<Duration>,
+    sleep: Duration,
+    auto_renew: bool,
+    held: Mutex<Held>,
+}
+
+/// Marks the lock as being acquired until dropped, which happens when
+/// `Inner::acquire` returns or its future is dropped.
+struct Acquiring(Arc<Inner>);
+
+impl Drop for Acquiring {
+    fn drop(&mut self) {
+        self.0.held.lock().unwrap().acquiring = false;
+    }
+}
+
+impl Inner {
+    async fn acquire(
+        self: Arc<Self>,
+        token: String,
+        _acquiring: Acquiring,
+        blocking: bool,
+    ) -> PyResult<bool> {
+        let deadline = self
+            .blocking_timeout
+            .map(|timeout| Instant::now() + timeout);
+
+        loop {
+            let mut cmd = redis::cmd("SET");
+            cmd.arg(&self.name)
+                .arg(&token)
+                .arg("NX")
+                .arg("PX")
+                .arg(millis(self.timeout));
+            if let Value::Okay = query(&self.pool, cmd).await? {
+                break;
+            }
+
+            if !blocking || deadline.is_some_and(|deadline| Instant::now() + self.sleep > deadline)
+            {
+                return Ok(false);
+            }
+            tokio::time::sleep(self.sleep).await;
+        }
+
+        let renewer = self
+            .auto_renew
+            .then(|| RUNTIME.spawn(renew(self.clone(), token.clone())));
+        let mut held = self.held.lock().unwrap();
+        held.take();
+        held.token = Some(token);
+        held.renewer = renewer;
+
+        Ok(true)
+    }
+
+    /// Forget `token` if it is still ours after the lock turned out to have
+    /// expired.
+    fn lost(&self, token: &str) {
+        let mut held = self.held.lock().unwrap();
+        if held.token.as_deref() == Some(token) {
+            held.take();
+        }
+    }
+
+    fn not_owned(&self) -> PyErr {
+        LockNotOwnedError::new_err(format!("lock {:?} is no longer owned", self.name))
+    }
+}
+
+/// A lock on a key that other clients respect, returned by `pool.lock`. It is
+/// acquired with `SET NX PX` and a random token that has to match to release
+/// or extend it, so a lock that expired and was acquired by someone else is
+/// left alone. If `auto_renew` is set, the lock is extended in the background
+/// while it is held.
+#[pyclass(module = "zangy")]
+pub struct Lock {
+    inner: Arc<Inner>,
+}
+
+impl Lock {
+    pub fn new(
+        pool: Py<ConnectionPool>,
+        name: String,
+        timeout: f64,
+        blocking_timeout: Option<f64>,
+        sleep: f64,
+        auto_renew: bool,
+    ) -> PyResult<Self> {
+        let timeout = duration(timeout, "timeout")?;
+        if millis(timeout) == 0 {
+            return Err(ArgumentError::new_err(
+                "timeout must be at least a millisecond",
+            ));
+        }
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                pool,
+                name,
+                timeout,
+                blocking_timeout: blocking_timeout
+                    .map(|timeout| duration(timeout, "blocking_timeout"))
+                    .transpose()?,
+                sleep: duration(sleep, "sleep")?,
+                auto_renew,
+                held: Mutex::default(),
+            }),
+        })
+    }
+
+    /// A random token for acquiring the lock, failing if it is already held
+    /// or being acquired. It counts as being acquired until the returned
+    /// guard is dropped.
+    fn new_token(&self, py: Python) -> PyResult<(String, Acquiring)> {
+        {
+            let mut held = self.inner.held.lock().unwrap();
+            if held.token.is_some() || held.acquiring {
+                return Err(LockError::new_err(format!(
+                    "lock {:?} is already held",
+                    self.inner.name
+                )));
+            }
+            held.acquiring = true;
+        }
+        let acquiring = Acquiring(self.inner.clone());
+
+        let token = py
+            .import_bound(intern!(py, "secrets"))?
+            .call_method1(intern!(py, "token_hex"), (16,))?
+            .extract()?;
+        Ok((token, acquiring))
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        self.inner.held.lock().unwrap().take();
+    }
+}
+
+#[pymethods]
+impl Lock {
+    /// Try to acquire the lock, retrying every `sleep` seconds until
+    /// `blocking_timeout` has passed if `blocking` is set. Resolves to whether
+    /// the lock was acquired.
+    #[pyo3(signature = (blocking = true))]
+    #[pyo3(text_signature = "($self, blocking = True)")]
+    fn acquire(&self, py: Python, blocking: bool) -> PyResult<PyObject> {
+        let (token, acquiring) = self.new_token(py)?;
+        spawn(self.inner.clone().acquire(token, acquiring, blocking))
+    }
+
+    /// Release the lock. Raises `LockNotOwnedError` if it expired in the
+    /// meantime.
+    #[pyo3(text_signature = "($self)")]
+    fn release(&self) -> PyResult<PyObject> {
+        let token =
+            self.inner.held.lock().unwrap().take().ok_or_else(|| {
+                LockError::new_err(format!("lock {:?} is not held", self.inner.name))
+            })?;
+        let inner = self.inner.clone();
+
+        spawn(async move {
+            let mut cmd = redis::cmd("EVAL");
+            cmd.arg(RELEASE).arg(1).arg(&inner.name).arg(&token);
+            match query(&inner.pool, cmd).await? {
+                Value::Int(0) => Err(inner.not_owned()),
+                _ => Ok(()),
+            }
+        })
+    }
+
+    /// Reset the expiry of the lock to `timeout` seconds, or to the timeout
+    /// it was created with. Raises `LockNotOwnedError` if it expired in the
+    /// meantime.
+    #[pyo3(signature = (timeout = None))]
+    #[pyo3(text_signature = "($self, timeout = None)")]
+    fn extend(&self, timeout: Option<f64>) -> PyResult<PyObject> {
+        let timeout = timeout.map_or(Ok(self.inner.timeout), |t| duration(t, "timeout"))?;
+        let token = self
+            .inner
+            .held
+            .lock()
+            .unwrap()
+            .token
+            .clone()
+            .ok_or_else(|| LockError::new_err(format!("lock {:?} is not held", self.inner.name)))?;
+        let inner = self.inner.clone();
+
+        spawn(async move {
+            let cmd = extend_cmd(&inner.name, &token, timeout);
+            match query(&inner.pool, cmd).await? {
+                Value::Int(0) => {
+                    inner.lost(&token);
+                    Err(inner.not_owned())
+                }
+                _ => Ok(()),
+            }
+        })
+    }
+
+    /// Acquires the lock, raising `LockError` if that did not succeed within
+    /// `blocking_timeout`.
+    fn __aenter__(slf: Py<Self>, py: Python) -> PyResult<PyObject> {
+        let (inner, (token, acquiring)) = {
+            let lock = slf.borrow(py);
+            (lock.inner.clone(), lock.new_token(py)?)
+        };
+
+        spawn(async move {
+            if inner.clone().acquire(token, acquiring, true).await? {
+                Ok(slf)
+            } else {
+                Err(LockError::new_err(format!(
+                    "could not acquire lock {:?}",
+                    inner.name
+                )))
+            }
+        })
+    }
+
+    /// Releases the lock.
+    fn __aexit__(
+        &self,
+        _ty: PyObject,
+        _value: PyObject,
+        _traceback: PyObject,
+    ) -> PyResult<PyObject> {
+        self.release()
+    }
+}
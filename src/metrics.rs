@@ -0,0 +1,337 @@
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use hdrhistogram::Histogram;
+use pyo3::{
+    intern,
+    prelude::{PyResult, Python},
+    types::{PyDict, PyDictMethods, PyList, PyListMethods},
+    Bound,
+};
+use redis::{Arg, Cmd};
+
+use crate::health::{roles, Slot};
+
+/// The quantiles reported for command latencies.
+const QUANTILES: [(&str, f64); 4] = [("p50", 0.5), ("p90", 0.9), ("p99", 0.99), ("p999", 0.999)];
+
+/// Latencies are recorded in microseconds, from 1µs up to a minute.
+const MAX_LATENCY: u64 = 60_000_000;
+
+/// The commands that get their own statistics, sorted. All others are
+/// counted as `OTHER` so that `execute` can't add labels without bound.
+const COMMANDS: &[&str] = &[
+    "ACL", "APPEND", "ASKING", "AUTH", "BGREWRITEAOF", "BGSAVE", "BITCOUNT", "BITFIELD",
+    "BITFIELD_RO", "BITOP", "BITPOS", "BLMOVE", "BLMPOP", "BLPOP", "BRPOP", "BRPOPLPUSH", "BZMPOP",
+    "BZPOPMAX", "BZPOPMIN", "CLIENT", "CLUSTER", "COMMAND", "CONFIG", "COPY", "DBSIZE", "DEBUG",
+    "DECR", "DECRBY", "DEL", "DISCARD", "DUMP", "ECHO", "EVAL", "EVALSHA", "EVALSHA_RO", "EVAL_RO",
+    "EXEC", "EXISTS", "EXPIRE", "EXPIREAT", "EXPIRETIME", "FAILOVER", "FCALL", "FCALL_RO",
+    "FLUSHALL", "FLUSHDB", "FUNCTION", "GEOADD", "GEODIST", "GEOHASH", "GEOPOS", "GEORADIUS",
+    "GEORADIUSBYMEMBER", "GEORADIUSBYMEMBER_RO", "GEORADIUS_RO", "GEOSEARCH", "GEOSEARCHSTORE",
+    "GET", "GETBIT", "GETDEL", "GETEX", "GETRANGE", "GETSET", "HDEL", "HELLO", "HEXISTS", "HEXPIRE",
+    "HGET", "HGETALL", "HINCRBY", "HINCRBYFLOAT", "HKEYS", "HLEN", "HMGET", "HMSET", "HPERSIST",
+    "HRANDFIELD", "HSCAN", "HSET", "HSETNX", "HSTRLEN", "HTTL", "HVALS", "INCR", "INCRBY",
+    "INCRBYFLOAT", "INFO", "JSON.DEL", "JSON.GET", "JSON.SET", "KEYS", "LASTSAVE", "LATENCY", "LCS",
+    "LINDEX", "LINSERT", "LLEN", "LMOVE", "LMPOP", "LOLWUT", "LPOP", "LPOS", "LPUSH", "LPUSHX",
+    "LRANGE", "LREM", "LSET", "LTRIM", "MEMORY", "MGET", "MIGRATE", "MODULE", "MONITOR", "MOVE",
+    "MSET", "MSETNX", "MULTI", "OBJECT", "PERSIST", "PEXPIRE", "PEXPIREAT", "PEXPIRETIME", "PFADD",
+    "PFCOUNT", "PFDEBUG", "PFMERGE", "PFSELFTEST", "PING", "PSETEX", "PSUBSCRIBE", "PSYNC", "PTTL",
+    "PUBLISH", "PUBSUB", "PUNSUBSCRIBE", "QUIT", "RANDOMKEY", "READONLY", "READWRITE", "RENAME",
+    "RENAMENX", "REPLCONF", "REPLICAOF", "RESET", "RESTORE", "ROLE", "RPOP", "RPOPLPUSH", "RPUSH",
+    "RPUSHX", "SADD", "SAVE", "SCAN", "SCARD", "SCRIPT", "SDIFF", "SDIFFSTORE", "SELECT", "SET",
+    "SETBIT", "SETEX", "SETNX", "SETRANGE", "SHUTDOWN", "SINTER", "SINTERCARD", "SINTERSTORE",
+    "SISMEMBER", "SLAVEOF", "SLOWLOG", "SMEMBERS", "SMISMEMBER", "SMOVE", "SORT", "SORT_RO", "SPOP",
+    "SPUBLISH", "SRANDMEMBER", "SREM", "SSCAN", "SSUBSCRIBE", "STRLEN", "SUBSCRIBE", "SUBSTR",
+    "SUNION", "SUNIONSTORE", "SUNSUBSCRIBE", "SWAPDB", "SYNC", "TIME", "TOUCH", "TTL", "TYPE",
+    "UNLINK", "UNSUBSCRIBE", "UNWATCH", "WAIT", "WAITAOF", "WATCH", "XACK", "XADD", "XAUTOCLAIM",
+    "XCLAIM", "XDEL", "XGROUP", "XINFO", "XLEN", "XPENDING", "XRANGE", "XREAD", "XREADGROUP",
+    "XREVRANGE", "XSETID", "XTRIM", "ZADD", "ZCARD", "ZCOUNT", "ZDIFF", "ZDIFFSTORE", "ZINCRBY",
+    "ZINTER", "ZINTERCARD", "ZINTERSTORE", "ZLEXCOUNT", "ZMPOP", "ZMSCORE", "ZPOPMAX", "ZPOPMIN",
+    "ZRANDMEMBER", "ZRANGE", "ZRANGEBYLEX", "ZRANGEBYSCORE", "ZRANGESTORE", "ZRANK", "ZREM",
+    "ZREMRANGEBYLEX", "ZREMRANGEBYRANK", "ZREMRANGEBYSCORE", "ZREVRANGE", "ZREVRANGEBYLEX",
+    "ZREVRANGEBYSCORE", "ZREVRANK", "ZSCAN", "ZSCORE", "ZUNION", "ZUNIONSTORE",
+];
+
+/// The name unknown commands are counted under.
+const OTHER: &str = "OTHER";
+
+/// The index of the statistics of the command called `name`, where unknown
+/// commands share the last one.
+fn command_index(name: &[u8]) -> usize {
+    COMMANDS
+        .binary_search_by(|known| {
+            let known = known.bytes();
+            let name = name.iter().map(u8::to_ascii_uppercase);
+            known.cmp(name)
+        })
+        .unwrap_or(COMMANDS.len())
+}
+
+fn command_name(idx: usize) -> &'static str {
+    COMMANDS.get(idx).copied().unwrap_or(OTHER)
+}
+
+#[derive(Clone)]
+struct CommandStats {
+    count: u64,
+    errors: u64,
+    /// Sum of all latencies in microseconds.
+    sum: u64,
+    latency: Histogram<u64>,
+}
+
+impl CommandStats {
+    fn new() -> Self {
+        Self {
+            count: 0,
+            errors: 0,
+            sum: 0,
+            latency: Histogram::new_with_bounds(1, MAX_LATENCY, 3).unwrap(),
+        }
+    }
+
+    fn quantile(&self, quantile: f64) -> f64 {
+        micros_to_secs(self.latency.value_at_quantile(quantile))
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn micros_to_secs(micros: u64) -> f64 {
+    micros as f64 / 1_000_000.0
+}
+
+/// Counters and latency histograms of a pool.
+pub struct Metrics {
+    /// The statistics of each command in `COMMANDS` followed by those of all
+    /// other commands, created when the command is first recorded.
+    commands: Box<[Mutex<Option<CommandStats>>]>,
+    reconnects: AtomicU64,
+    pubsub_checkouts: AtomicU64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            commands: (0..=COMMANDS.len()).map(|_| Mutex::default()).collect(),
+            reconnects: AtomicU64::default(),
+            pubsub_checkouts: AtomicU64::default(),
+        }
+    }
+}
+
+impl Metrics {
+    /// Record a command that took `elapsed` and failed unless `ok`.
+    pub fn record(&self, cmd: &Cmd, elapsed: Duration, ok: bool) {
+        let idx = match cmd.args_iter().next() {
+            Some(Arg::Simple(name)) => command_index(name),
+            _ => COMMANDS.len(),
+        };
+        let micros = u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX);
+
+        let mut stats = self.commands[idx].lock().unwrap();
+        let stats = stats.get_or_insert_with(CommandStats::new);
+        stats.count += 1;
+        stats.sum = stats.sum.saturating_add(micros);
+        if !ok {
+            stats.errors += 1;
+        }
+        stats
+            .latency
+            .saturating_record(micros.clamp(1, MAX_LATENCY));
+    }
+
+    /// The names and statistics of the commands that have been recorded, in
+    /// order of their names with `OTHER` last.
+    fn command_stats(&self) -> Vec<(&'static str, CommandStats)> {
+        self.commands
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, stats)| Some((command_name(idx), stats.lock().unwrap().clone()?)))
+            .collect()
+    }
+
+    pub fn reconnected(&self) {
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn checked_out(&self) {
+        self.pubsub_checkouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn to_dict<'py>(
+        &self,
+        py: Python<'py>,
+        primary: &[Slot],
+        replicas: &[Slot],
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let commands = PyDict::new_bound(py);
+        for (name, stats) in self.command_stats() {
+            let entry = PyDict::new_bound(py);
+            entry.set_item(intern!(py, "count"), stats.count)?;
+            entry.set_item(intern!(py, "errors"), stats.errors)?;
+            for (key, quantile) in QUANTILES {
+                entry.set_item(key, stats.quantile(quantile))?;
+            }
+            entry.set_item(intern!(py, "max"), micros_to_secs(stats.latency.max()))?;
+            commands.set_item(name, entry)?;
+        }
+
+        let connections = PyList::empty_bound(py);
+        for (role, slot) in roles(primary, replicas) {
+            let entry = PyDict::new_bound(py);
+            entry.set_item(intern!(py, "role"), role)?;
+            entry.set_item(intern!(py, "in_flight"), slot.in_flight())?;
+            connections.append(entry)?;
+        }
+
+        let stats = PyDict::new_bound(py);
+        stats.set_item(intern!(py, "commands"), commands)?;
+        stats.set_item(intern!(py, "connections"), connections)?;
+        stats.set_item(
+            intern!(py, "reconnects"),
+            self.reconnects.load(Ordering::Relaxed),
+        )?;
+        stats.set_item(
+            intern!(py, "pubsub_checkouts"),
+            self.pubsub_checkouts.load(Ordering::Relaxed),
+        )?;
+
+        Ok(stats)
+    }
+
+    /// Render the metrics in the Prometheus text exposition format with all
+    /// metric names prefixed by `namespace`.
+    pub fn render_prometheus(
+        &self,
+        namespace: &str,
+        primary: &[Slot],
+        replicas: &[Slot],
+    ) -> String {
+        let mut out = String::new();
+        render_commands(&mut out, namespace, &self.command_stats());
+
+        header(
+            &mut out,
+            namespace,
+            "connection_in_flight",
+            "gauge",
+            "Commands waiting for a reply, by connection.",
+        );
+        for (idx, (role, slot)) in roles(primary, replicas).enumerate() {
+            let _res = writeln!(
+                out,
+                "{namespace}_connection_in_flight{{role=\"{role}\",connection=\"{idx}\"}} {}",
+                slot.in_flight()
+            );
+        }
+
+        header(
+            &mut out,
+            namespace,
+            "reconnects_total",
+            "counter",
+            "Connections that were replaced after being lost.",
+        );
+        let _res = writeln!(
+            out,
+            "{namespace}_reconnects_total {}",
+            self.reconnects.load(Ordering::Relaxed)
+        );
+
+        header(
+            &mut out,
+            namespace,
+            "pubsub_checkouts_total",
+            "counter",
+            "Pubsub connections taken from the pool.",
+        );
+        let _res = writeln!(
+            out,
+            "{namespace}_pubsub_checkouts_total {}",
+            self.pubsub_checkouts.load(Ordering::Relaxed)
+        );
+
+        out
+    }
+}
+
+fn render_commands(out: &mut String, namespace: &str, commands: &[(&str, CommandStats)]) {
+    header(
+        out,
+        namespace,
+        "commands_total",
+        "counter",
+        "Commands sent, by command.",
+    );
+    for (name, stats) in commands {
+        let _res = writeln!(
+            out,
+            "{namespace}_commands_total{{command=\"{}\"}} {}",
+            escape(name),
+            stats.count
+        );
+    }
+
+    header(
+        out,
+        namespace,
+        "command_errors_total",
+        "counter",
+        "Commands that failed, by command.",
+    );
+    for (name, stats) in commands {
+        let _res = writeln!(
+            out,
+            "{namespace}_command_errors_total{{command=\"{}\"}} {}",
+            escape(name),
+            stats.errors
+        );
+    }
+
+    header(
+        out,
+        namespace,
+        "command_duration_seconds",
+        "summary",
+        "Time until the reply to a command was received.",
+    );
+    for (name, stats) in commands {
+        let label = escape(name);
+        for (_, quantile) in QUANTILES {
+            let _res = writeln!(
+                    out,
+                    "{namespace}_command_duration_seconds{{command=\"{label}\",quantile=\"{quantile}\"}} {}",
+                    stats.quantile(quantile)
+                );
+        }
+        let _res = writeln!(
+            out,
+            "{namespace}_command_duration_seconds_sum{{command=\"{label}\"}} {}",
+            micros_to_secs(stats.sum)
+        );
+        let _res = writeln!(
+            out,
+            "{namespace}_command_duration_seconds_count{{command=\"{label}\"}} {}",
+            stats.count
+        );
+    }
+}
+
+fn header(out: &mut String, namespace: &str, name: &str, kind: &str, help: &str) {
+    let _res = writeln!(out, "# HELP {namespace}_{name} {help}");
+    let _res = writeln!(out, "# TYPE {namespace}_{name} {kind}");
+}
+
+/// Escape a label value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,248 @@
+use std::time::Duration;
+
+use pyo3::{
+    intern,
+    prelude::{pyclass, pymethods, PyObject, PyResult, Python},
+    types::{PyAnyMethods, PyBytes, PyList},
+    Bound, PyAny,
+};
+use redis::{Arg, Cmd, RedisResult, Value};
+
+use crate::{conversion::RedisValuePy, exceptions::ArgumentError, keys};
+
+/// A hook around every command sent by a pool. Hooks run in the order they
+/// were given before a command and in reverse order after it.
+pub trait Middleware: Send + Sync {
+    /// Inspect or rewrite a command before it is sent. Hooks that need the GIL
+    /// take it themselves.
+    fn before(&self, cmd: Cmd) -> PyResult<Cmd> {
+        Ok(cmd)
+    }
+
+    /// Inspect or rewrite the reply to a command. Runs without the GIL.
+    fn after(
+        &self,
+        _cmd: &Cmd,
+        reply: RedisResult<Value>,
+        _elapsed: Duration,
+    ) -> RedisResult<Value> {
+        reply
+    }
+
+    /// Inspect or rewrite the reply after it was converted to a Python object.
+    fn after_py(&self, _py: Python, _cmd: &Cmd, reply: PyObject) -> PyResult<PyObject> {
+        Ok(reply)
+    }
+}
+
+/// Prefixes the keys of all commands with a namespace and removes it from
+/// key names in replies. The channels published to are prefixed as well if
+/// `channels` is set.
+#[pyclass(module = "zangy", frozen)]
+#[derive(Clone)]
+pub struct KeyNamespace {
+    prefix: Vec<u8>,
+    channels: bool,
+}
+
+#[pymethods]
+impl KeyNamespace {
+    #[new]
+    #[pyo3(signature = (prefix, channels = false))]
+    pub fn new(prefix: &str, channels: bool) -> Self {
+        Self {
+            prefix: prefix.as_bytes().to_vec(),
+            channels,
+        }
+    }
+}
+
+impl Middleware for KeyNamespace {
+    fn before(&self, cmd: Cmd) -> PyResult<Cmd> {
+        Ok(keys::prefix(cmd, &self.prefix, self.channels))
+    }
+
+    fn after(
+        &self,
+        cmd: &Cmd,
+        reply: RedisResult<Value>,
+        _elapsed: Duration,
+    ) -> RedisResult<Value> {
+        reply.map(|mut reply| {
+            keys::strip(cmd, &mut reply, &self.prefix);
+            reply
+        })
+    }
+}
+
+/// Logs commands that took longer than `threshold` seconds to the `zangy`
+/// logger. The GIL is only taken for slow commands.
+#[pyclass(module = "zangy", frozen)]
+#[derive(Clone)]
+pub struct SlowLog {
+    threshold: Duration,
+}
+
+#[pymethods]
+impl SlowLog {
+    #[new]
+    #[pyo3(signature = (threshold = 0.1))]
+    fn new(threshold: f64) -> PyResult<Self> {
+        let threshold = Duration::try_from_secs_f64(threshold)
+            .map_err(|e| ArgumentError::new_err(format!("invalid threshold: {e}")))?;
+        Ok(Self { threshold })
+    }
+}
+
+impl Middleware for SlowLog {
+    fn after(&self, cmd: &Cmd, reply: RedisResult<Value>, elapsed: Duration) -> RedisResult<Value> {
+        if elapsed >= self.threshold {
+            let name = match cmd.args_iter().next() {
+                Some(Arg::Simple(name)) => String::from_utf8_lossy(name).to_ascii_uppercase(),
+                _ => String::from("UNKNOWN"),
+            };
+
+            Python::with_gil(|py| {
+                let res = py
+                    .import_bound(intern!(py, "logging"))
+                    .and_then(|logging| logging.call_method1(intern!(py, "getLogger"), ("zangy",)))
+                    .and_then(|logger| {
+                        logger.call_method1(
+                            intern!(py, "warning"),
+                            ("slow command %s took %.3fs", name, elapsed.as_secs_f64()),
+                        )
+                    });
+                if let Err(e) = res {
+                    eprintln!("{e:?}");
+                }
+            });
+        }
+
+        reply
+    }
+}
+
+/// A Python object with optional `before(args)` and `after(args, reply)`
+/// methods. `before` may return a new list of arguments, `after` returns the
+/// reply to use.
+struct PyMiddleware(PyObject);
+
+fn args_to_list<'py>(py: Python<'py>, cmd: &Cmd) -> Bound<'py, PyList> {
+    let args: Vec<_> = cmd
+        .args_iter()
+        .filter_map(|arg| match arg {
+            Arg::Simple(arg) => Some(PyBytes::new_bound(py, arg)),
+            Arg::Cursor => None,
+        })
+        .collect();
+    PyList::new_bound(py, args)
+}
+
+impl Middleware for PyMiddleware {
+    fn before(&self, cmd: Cmd) -> PyResult<Cmd> {
+        Python::with_gil(|py| {
+            let hook = self.0.bind(py);
+            if !hook.hasattr(intern!(py, "before"))? {
+                return Ok(cmd);
+            }
+
+            let args = hook.call_method1(intern!(py, "before"), (args_to_list(py, &cmd),))?;
+            if args.is_none() {
+                return Ok(cmd);
+            }
+
+            let args: Vec<RedisValuePy> = args.extract()?;
+            if args.is_empty() {
+                return Err(ArgumentError::new_err("middleware returned no arguments"));
+            }
+            let mut rewritten = Cmd::new();
+            rewritten.arg(args);
+            Ok(rewritten)
+        })
+    }
+
+    fn after_py(&self, py: Python, cmd: &Cmd, reply: PyObject) -> PyResult<PyObject> {
+        let hook = self.0.bind(py);
+        if !hook.hasattr(intern!(py, "after"))? {
+            return Ok(reply);
+        }
+
+        Ok(hook
+            .call_method1(intern!(py, "after"), (args_to_list(py, cmd), reply))?
+            .unbind())
+    }
+}
+
+/// The middleware of a pool. `python` is set if any of it is a Python object,
+/// otherwise commands pass through the chain without taking the GIL.
+#[derive(Default)]
+pub struct Chain {
+    middleware: Vec<Box<dyn Middleware>>,
+    python: bool,
+}
+
+impl Chain {
+    /// Build a chain from the built-in middleware classes and Python objects.
+    pub fn from_py(middleware: Vec<Bound<'_, PyAny>>) -> Self {
+        let mut python = false;
+        let middleware = middleware
+            .into_iter()
+            .map(|m| -> Box<dyn Middleware> {
+                if let Ok(namespace) = m.downcast::<KeyNamespace>() {
+                    Box::new(namespace.get().clone())
+                } else if let Ok(slow_log) = m.downcast::<SlowLog>() {
+                    Box::new(slow_log.get().clone())
+                } else {
+                    python = true;
+                    Box::new(PyMiddleware(m.unbind()))
+                }
+            })
+            .collect();
+
+        Self { middleware, python }
+    }
+
+    /// Add a middleware that runs after all others before a command, and
+    /// first after it.
+    pub fn push(&mut self, middleware: Box<dyn Middleware>) {
+        self.middleware.push(middleware);
+    }
+
+    /// Whether the `after_py` hooks have to run.
+    pub fn has_python(&self) -> bool {
+        self.python
+    }
+
+    pub fn before(&self, cmd: Cmd) -> PyResult<Cmd> {
+        let run = || self.middleware.iter().try_fold(cmd, |cmd, m| m.before(cmd));
+        if self.python {
+            // Take the GIL once for all Python middleware
+            Python::with_gil(|_py| run())
+        } else {
+            run()
+        }
+    }
+
+    pub fn after(
+        &self,
+        cmd: &Cmd,
+        reply: RedisResult<Value>,
+        elapsed: Duration,
+    ) -> RedisResult<Value> {
+        self.middleware
+            .iter()
+            .rev()
+            .fold(reply, |reply, m| m.after(cmd, reply, elapsed))
+    }
+
+    pub fn after_py(&self, py: Python, cmd: &Cmd, reply: PyObject) -> PyResult<PyObject> {
+        if !self.python {
+            return Ok(reply);
+        }
+
+        self.middleware
+            .iter()
+            .rev()
+            .try_fold(reply, |reply, m| m.after_py(py, cmd, reply))
+    }
+}
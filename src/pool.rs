@@ -1,70 +1,306 @@
 use std::{
+    future::Future,
     intrinsics::unlikely,
     num::NonZeroUsize,
     sync::{
         atomic::{AtomicUsize, Ordering},
-        Arc, Mutex,
+        Arc,
     },
+    time::{Duration, Instant},
 };
 
-use futures_util::StreamExt;
 use pyo3::{
-    prelude::{pyclass, pymethods, PyObject, PyResult, Python},
-    IntoPy, Py, PyAny, PyRef,
-};
-use redis::{
-    aio::{MultiplexedConnection, PubSub},
-    Cmd, Value,
+    intern,
+    prelude::{pyclass, pymethods, FromPyObject, PyObject, PyResult, Python},
+    types::{
+        PyAnyMethods, PyDict, PyDictMethods, PyList, PyListMethods, PyString, PyStringMethods,
+        PyTuple,
+    },
+    Bound, IntoPy, Py, PyAny,
 };
-use tokio::sync::Mutex as TokioMutex;
+use redis::{Client, Cmd, RedisResult, Value};
 
 use crate::{
     asyncio::{create_future, set_fut_exc, set_fut_result_none, set_fut_result_with_gil},
-    conversion::{re_to_object, RedisValuePy},
-    exceptions::{ArgumentError, PoolEmpty, PubSubClosed, RedisError},
+    buffer::Overflow,
+    bulk,
+    chunks::{Chunks, Source},
+    conversion::{
+        arg, extract_arg, re_to_object_shaped, Compressor, Pairs, RedisValuePy, Request,
+        ResponseShape,
+    },
+    exceptions::{redis_error, ArgumentError, PoolClosed, PoolEmpty},
+    health::{roles, Slot},
+    keyspace::KeyspaceListener,
+    lifecycle::Lifecycle,
+    lock::Lock,
+    metrics::Metrics,
+    middleware::Chain,
+    pubsub::{self, PubSubContext},
+    pubsub_pool::PubSubPool,
+    resp::Stream,
     runtime::RUNTIME,
+    serializer::Serializer,
+    trace::{Span, Tracer},
 };
 
+/// Where read-only commands are sent if the pool has replicas.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Prefer {
+    Replica,
+    Primary,
+}
+
+impl FromPyObject<'_> for Prefer {
+    fn extract_bound(ob: &Bound<'_, PyAny>) -> PyResult<Self> {
+        match &*ob.downcast::<PyString>()?.to_cow()? {
+            "replica" => Ok(Self::Replica),
+            "primary" => Ok(Self::Primary),
+            other => Err(ArgumentError::new_err(format!(
+                "unknown prefer value {other:?}, expected \"replica\" or \"primary\""
+            ))),
+        }
+    }
+}
+
 #[pyclass(module = "zangy")]
 pub struct ConnectionPool {
     pub current: AtomicUsize,
-    pub pool: Vec<MultiplexedConnection>,
-    pub pubsub_pool: Arc<Mutex<Vec<PubSub>>>,
+    pub pool: Arc<Vec<Slot>>,
+    /// Connections to replicas that read-only commands are sent to.
+    pub replicas: Arc<Vec<Slot>>,
+    pub replica_current: AtomicUsize,
+    pub pubsub_pool: Arc<PubSubPool>,
     #[pyo3(get)]
     pub pool_size: usize,
+    pub serializer: Option<Serializer>,
+    pub compressor: Option<Compressor>,
+    pub client: Client,
+    pub lifecycle: Arc<Lifecycle>,
+    pub metrics: Arc<Metrics>,
+    pub tracer: Option<Arc<Tracer>>,
+    pub middleware: Arc<Chain>,
+    /// Prepended to all keys by a `KeyNamespace` in `middleware`.
+    pub key_prefix: Vec<u8>,
+    pub prefix_channels: bool,
 }
 
 impl ConnectionPool {
-    fn next_idx(&self) -> usize {
+    fn next_idx(&self, len: usize) -> usize {
         self.current
             .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |x| {
-                if x + 1 == self.pool_size {
+                if x + 1 >= len {
                     Some(0)
                 } else {
                     Some(x + 1)
                 }
             })
             .unwrap()
+            % len
     }
 
-    fn query_cmd(&self, cmd: Cmd) -> PyResult<PyObject> {
-        let (fut, res_fut) = create_future()?;
+    /// The next healthy connection to the primary in round robin order, or the
+    /// next one if none are healthy. Fails once the pool was closed, which
+    /// drops the connections.
+    fn connection(&self) -> PyResult<&Slot> {
+        let len = self.pool.len();
+        if len == 0 {
+            return Err(PoolClosed::new_err("pool has been closed"));
+        }
+
+        let idx = self.next_idx(len);
+        Ok((0..len)
+            .map(|i| &self.pool[(idx + i) % len])
+            .find(|slot| slot.is_healthy())
+            .unwrap_or(&self.pool[idx]))
+    }
+
+    /// The next healthy replica connection in round robin order. Falls back
+    /// to the primary if there are no healthy replicas.
+    fn read_connection(&self, prefer: Prefer) -> PyResult<&Slot> {
+        if prefer == Prefer::Primary || self.replicas.is_empty() {
+            return self.connection();
+        }
+
+        let len = self.replicas.len();
+        let idx = self.replica_current.fetch_add(1, Ordering::Relaxed);
+        match (0..len)
+            .map(|i| &self.replicas[(idx + i) % len])
+            .find(|slot| slot.is_healthy())
+        {
+            Some(slot) => Ok(slot),
+            None => self.connection(),
+        }
+    }
+
+    /// Turn the argument `name` into a single value, encoding it with the
+    /// serializer if configured. Compression happens when the command is sent.
+    pub fn encode_value(&self, value: &Bound<'_, PyAny>, name: &str) -> PyResult<RedisValuePy> {
+        match self.serializer {
+            Some(serializer) => Ok(RedisValuePy::Bytes(serializer.encode(value)?)),
+            None => extract_arg(value, name),
+        }
+    }
+
+    /// Like `encode_value`, but a list or tuple is treated as multiple values
+    /// that are encoded one by one.
+    fn encode_values(&self, values: &Bound<'_, PyAny>, name: &str) -> PyResult<RedisValuePy> {
+        if self.serializer.is_some()
+            && (values.is_instance_of::<PyList>() || values.is_instance_of::<PyTuple>())
+        {
+            let encoded = values
+                .iter()?
+                .map(|v| self.encode_value(&v?, name))
+                .collect::<PyResult<_>>()?;
+            Ok(RedisValuePy::Array(encoded))
+        } else {
+            self.encode_value(values, name)
+        }
+    }
+
+    fn encode_items(&self, items: Pairs<'_>) -> PyResult<Vec<(RedisValuePy, RedisValuePy)>> {
+        items
+            .0
+            .into_iter()
+            .map(|(k, v)| Ok((k, self.encode_value(&v, "items")?)))
+            .collect()
+    }
+
+    /// The prefix of pubsub channels, empty unless `prefix_channels` is set.
+    fn channel_prefix(&self) -> Vec<u8> {
+        if self.prefix_channels {
+            self.key_prefix.clone()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Take an idle pubsub connection. Connections that are being health
+    /// checked are waited for, so they are not mistaken for an empty pool. A
+    /// check never needs the GIL and gives up after the check interval.
+    fn take_pubsub(&self) -> PyResult<Stream> {
+        loop {
+            if let Some(conn) = self.pubsub_pool.try_take() {
+                return Ok(conn);
+            }
+            if !self.pubsub_pool.wait_checks() {
+                return Err(PoolEmpty::new_err("PubSub pool is empty"));
+            }
+        }
+    }
+
+    fn pubsub_context(&self, conn: Stream, options: &pubsub::Options) -> PubSubContext {
+        PubSubContext::new(
+            conn,
+            self.pubsub_pool.clone(),
+            self.lifecycle.clone(),
+            self.metrics.clone(),
+            self.client.get_connection_info().clone(),
+            options,
+        )
+    }
+
+    /// Start a span for `cmd` if tracing is enabled.
+    fn trace(&self, cmd: &Cmd, slot: &Slot) -> PyResult<Option<Span>> {
+        self.tracer
+            .as_ref()
+            .map(|tracer| tracer.start(cmd, slot.info()))
+            .transpose()
+    }
+
+    pub fn query_cmd(&self, cmd: Cmd) -> PyResult<PyObject> {
+        self.query_cmd_as(cmd, ResponseShape::Generic)
+    }
+
+    fn query_cmd_as(&self, cmd: impl Into<Request>, shape: ResponseShape) -> PyResult<PyObject> {
+        self.read_cmd_as(cmd, shape, Prefer::Primary)
+    }
+
+    /// Like `query_cmd`, but for read-only commands which may be sent to a
+    /// replica.
+    pub fn read_cmd(&self, cmd: Cmd, prefer: Prefer) -> PyResult<PyObject> {
+        self.read_cmd_as(cmd, ResponseShape::Generic, prefer)
+    }
+
+    /// Send `cmd` to a connection picked by `prefer`. The returned future
+    /// resolves to the command as rewritten by the middleware and its reply
+    /// after the `after` hooks have run.
+    pub fn send_cmd(
+        &self,
+        cmd: impl Into<Request>,
+        prefer: Prefer,
+    ) -> PyResult<impl Future<Output = (Cmd, RedisResult<Value>)> + Send + 'static> {
+        let in_flight = self.lifecycle.start()?;
+        let Request { cmd, stored } = cmd.into();
+        let len = cmd.args_iter().len();
+        let cmd = self.middleware.before(cmd)?;
+        // Middleware that added or removed arguments moved the stored values,
+        // they are sent uncompressed then
+        let compressor = self
+            .compressor
+            .filter(|_| !stored.is_empty() && cmd.args_iter().len() == len);
+
+        let slot = self.read_connection(prefer)?;
+        let span = self.trace(&cmd, slot)?;
+        let (mut conn, busy) = slot.acquire();
+        let metrics = self.metrics.clone();
+        let middleware = self.middleware.clone();
+
+        Ok(async move {
+            let _in_flight = in_flight;
+            let cmd = match compressor {
+                Some(compressor) => compressor.compress_args(&cmd, &stored),
+                None => cmd,
+            };
+
+            let start = Instant::now();
+            let res = cmd.query_async(&mut conn).await;
+            let elapsed = start.elapsed();
+            drop(busy);
+            metrics.record(&cmd, elapsed, res.is_ok());
+            if let Some(span) = span {
+                span.finish(res.as_ref().err());
+            }
 
-        let idx = self.next_idx();
-        let mut conn = self.pool[idx].clone();
+            let res = middleware.after(&cmd, res, elapsed);
+            (cmd, res)
+        })
+    }
+
+    fn read_cmd_as(
+        &self,
+        cmd: impl Into<Request>,
+        shape: ResponseShape,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
+        let reply = self.send_cmd(cmd, prefer)?;
+        let (fut, res_fut) = create_future()?;
+        let serializer = self.serializer;
+        let compressor = self.compressor;
+        let middleware = self.middleware.clone();
 
         RUNTIME.spawn(async move {
-            match cmd.query_async(&mut conn).await {
-                Ok(v) => {
+            let (cmd, res) = reply.await;
+
+            match res {
+                Ok(mut v) => {
+                    if compressor.is_some() {
+                        Compressor::decompress_reply(&mut v, shape);
+                    }
+
                     Python::with_gil(|py| {
-                        if let Err(e) = set_fut_result_with_gil(&fut, re_to_object(&v, py), py) {
-                            eprintln!("{e:?}");
+                        let res = re_to_object_shaped(v, shape, serializer, py);
+                        let res = match middleware.after_py(py, &cmd, res) {
+                            Ok(res) => set_fut_result_with_gil(&fut, res, py),
+                            Err(e) => set_fut_exc(&fut, e),
                         };
+                        if let Err(e) = res {
+                            eprintln!("{e:?}");
+                        }
                     });
                 }
                 Err(e) => {
-                    let desc = e.to_string();
-                    if let Err(e2) = set_fut_exc(&fut, RedisError::new_err(desc)) {
+                    if let Err(e2) = set_fut_exc(&fut, redis_error(&e)) {
                         eprintln!("{e2:?}");
                     }
                 }
@@ -74,24 +310,31 @@ impl ConnectionPool {
         Ok(res_fut)
     }
 
-    fn exec_cmd(&self, cmd: Cmd) -> PyResult<PyObject> {
+    fn exec_cmd(&self, cmd: impl Into<Request>) -> PyResult<PyObject> {
+        let reply = self.send_cmd(cmd, Prefer::Primary)?;
         let (fut, res_fut) = create_future()?;
-
-        let idx = self.next_idx();
-        let mut conn = self.pool[idx].clone();
+        let middleware = self.middleware.clone();
 
         RUNTIME.spawn(async move {
-            if let Err(e) = cmd
-                .query_async::<MultiplexedConnection, ()>(&mut conn)
-                .await
-            {
-                let desc = e.to_string();
-                if let Err(e2) = set_fut_exc(&fut, RedisError::new_err(desc)) {
+            let (cmd, res) = reply.await;
+
+            if let Err(e) = res {
+                if let Err(e2) = set_fut_exc(&fut, redis_error(&e)) {
                     eprintln!("{e2:?}");
                 }
-            } else {
+            } else if !middleware.has_python() {
                 let _res = set_fut_result_none(&fut);
-            };
+            } else {
+                Python::with_gil(|py| {
+                    let res = match middleware.after_py(py, &cmd, py.None()) {
+                        Ok(res) => set_fut_result_with_gil(&fut, res, py),
+                        Err(e) => set_fut_exc(&fut, e),
+                    };
+                    if let Err(e) = res {
+                        eprintln!("{e:?}");
+                    }
+                });
+            }
         });
 
         Ok(res_fut)
@@ -101,16 +344,109 @@ impl ConnectionPool {
 #[allow(clippy::needless_pass_by_value)]
 #[pymethods]
 impl ConnectionPool {
+    fn __aenter__(slf: Py<Self>, py: Python) -> PyResult<PyObject> {
+        let (fut, res_fut) = create_future()?;
+        set_fut_result_with_gil(&fut, slf.into_py(py), py)?;
+        Ok(res_fut)
+    }
+
+    fn __aexit__(
+        &mut self,
+        _ty: PyObject,
+        _value: PyObject,
+        _traceback: PyObject,
+    ) -> PyResult<PyObject> {
+        self.close();
+        self.wait_closed()
+    }
+
+    /// Stop accepting commands and close all idle connections. Connections
+    /// are closed once the commands running on them have finished and pubsub
+    /// connections once their context manager exits.
+    #[pyo3(text_signature = "($self)")]
+    fn close(&mut self) {
+        self.lifecycle.close();
+        self.pool = Arc::new(Vec::new());
+        self.replicas = Arc::new(Vec::new());
+        self.pubsub_pool.close();
+    }
+
+    /// Wait until all commands running when the pool was closed have
+    /// finished.
+    #[pyo3(text_signature = "($self)")]
+    fn wait_closed(&self) -> PyResult<PyObject> {
+        let (fut, res_fut) = create_future()?;
+        let lifecycle = self.lifecycle.clone();
+
+        RUNTIME.spawn(async move {
+            lifecycle.wait_idle().await;
+
+            if let Err(e) = set_fut_result_none(&fut) {
+                eprintln!("{e:?}");
+            }
+        });
+
+        Ok(res_fut)
+    }
+
     /// Returns the index of the next connection to be used in the pool.
     #[pyo3(text_signature = "($self)")]
     fn current(&self) -> usize {
         self.current.load(Ordering::Relaxed)
     }
 
+    /// Returns the result of the last health check of each connection as a
+    /// list of dicts with the keys `role` (`"primary"`, `"replica"` or
+    /// `"pubsub"`), `healthy` and `rtt`, the round trip time of the last PING
+    /// in seconds or `None` if it is not known yet. Pubsub connections are
+    /// only checked while idle, so their `rtt` is `None` while in use.
+    #[pyo3(text_signature = "($self)")]
+    fn health<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let list = PyList::empty_bound(py);
+        for (role, slot) in roles(&self.pool, &self.replicas) {
+            let status = PyDict::new_bound(py);
+            status.set_item(intern!(py, "role"), role)?;
+            status.set_item(intern!(py, "healthy"), slot.is_healthy())?;
+            status.set_item(intern!(py, "rtt"), slot.rtt().map(|rtt| rtt.as_secs_f64()))?;
+            list.append(status)?;
+        }
+        // Pubsub connections that don't answer are replaced or dropped, so the
+        // remaining ones are healthy as far as is known
+        for rtt in self.pubsub_pool.rtts() {
+            let status = PyDict::new_bound(py);
+            status.set_item(intern!(py, "role"), "pubsub")?;
+            status.set_item(intern!(py, "healthy"), true)?;
+            status.set_item(intern!(py, "rtt"), rtt.map(|rtt| rtt.as_secs_f64()))?;
+            list.append(status)?;
+        }
+
+        Ok(list)
+    }
+
+    /// Returns statistics about the pool as a dict with the keys `commands`,
+    /// mapping command names to their `count`, `errors` and latency
+    /// percentiles `p50`, `p90`, `p99`, `p999` and `max` in seconds,
+    /// `connections`, a list with the `role` and `in_flight` commands of each
+    /// connection, `reconnects` and `pubsub_checkouts`.
+    #[pyo3(text_signature = "($self)")]
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.metrics.to_dict(py, &self.pool, &self.replicas)
+    }
+
+    /// Returns the statistics of `stats` in the Prometheus text exposition
+    /// format, with metric names prefixed by `namespace`.
+    #[pyo3(signature = (namespace = "zangy"))]
+    #[pyo3(text_signature = "($self, namespace = \"zangy\")")]
+    fn prometheus(&self, namespace: &str) -> String {
+        self.metrics
+            .render_prometheus(namespace, &self.pool, &self.replicas)
+    }
+
     /// Execute a raw redis command.
     #[pyo3(signature = (*args))]
     #[pyo3(text_signature = "($self, *args)")]
-    fn execute(&self, args: Vec<RedisValuePy>) -> PyResult<PyObject> {
+    fn execute(&self, args: &Bound<'_, PyTuple>) -> PyResult<PyObject> {
+        let args: Vec<RedisValuePy> = extract_arg(args, "args")?;
         if unlikely(args.is_empty()) {
             return Err(ArgumentError::new_err("no arguments provided to execute"));
         }
@@ -123,88 +459,201 @@ impl ConnectionPool {
 
     /// Set the string value of a key.
     #[pyo3(text_signature = "($self, key, value)")]
-    fn set(&self, key: RedisValuePy, value: RedisValuePy) -> PyResult<PyObject> {
+    fn set(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        value: Bound<'_, PyAny>,
+    ) -> PyResult<PyObject> {
+        let value = self.encode_value(&value, "value")?;
+        let stored = value.arg_count();
         let redis_cmd = Cmd::set(key, value);
-        self.exec_cmd(redis_cmd)
+        self.exec_cmd(Request::trailing(redis_cmd, stored))
     }
 
-    /// Get the value of a key. If key is a list this becomes an `MGET`.
-    #[pyo3(text_signature = "($self, key)")]
-    fn get(&self, key: RedisValuePy) -> PyResult<PyObject> {
+    /// Get the value of a key. If key is a list this becomes an `MGET`. With
+    /// `raw_buffer`, the value is returned as a `RawBuffer` instead of being
+    /// copied into `bytes` and decoded.
+    #[pyo3(signature = (key, *, prefer = Prefer::Replica, raw_buffer = false))]
+    #[pyo3(text_signature = "($self, key, *, prefer = \"replica\", raw_buffer = False)")]
+    fn get(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        prefer: Prefer,
+        raw_buffer: bool,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::get(key);
-        self.query_cmd(redis_cmd)
+        let shape = if raw_buffer {
+            ResponseShape::Buffer
+        } else {
+            ResponseShape::Values
+        };
+        self.read_cmd_as(redis_cmd, shape, prefer)
     }
 
     /// Gets all keys matching pattern.
-    #[pyo3(text_signature = "($self, key)")]
-    fn keys(&self, key: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, *, prefer = \"replica\")")]
+    fn keys(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::keys(key);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Set the value and expiration of a key.
     #[pyo3(text_signature = "($self, key, value, seconds)")]
-    fn set_ex(&self, key: RedisValuePy, value: RedisValuePy, seconds: u64) -> PyResult<PyObject> {
+    fn set_ex(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        value: Bound<'_, PyAny>,
+        seconds: u64,
+    ) -> PyResult<PyObject> {
+        let value = self.encode_value(&value, "value")?;
+        let stored = value.arg_count();
         let redis_cmd = Cmd::set_ex(key, value, seconds);
-        self.exec_cmd(redis_cmd)
+        self.exec_cmd(Request::trailing(redis_cmd, stored))
     }
 
     /// Sets multiple keys to their values.
     #[pyo3(text_signature = "($self, items)")]
-    fn mset(&self, items: Vec<(RedisValuePy, RedisValuePy)>) -> PyResult<PyObject> {
+    fn mset(&self, #[pyo3(from_py_with = "arg::items")] items: Pairs<'_>) -> PyResult<PyObject> {
+        let items = self.encode_items(items)?;
         let redis_cmd = Cmd::mset(&items);
-        self.exec_cmd(redis_cmd)
+        self.exec_cmd(Request::pairs(redis_cmd, &items))
     }
 
     /// Set the value and expiration in milliseconds of a key.
     #[pyo3(text_signature = "($self, key, value, milliseconds)")]
     fn pset_ex(
         &self,
-        key: RedisValuePy,
-        value: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        value: Bound<'_, PyAny>,
         milliseconds: u64,
     ) -> PyResult<PyObject> {
+        let value = self.encode_value(&value, "value")?;
+        let stored = value.arg_count();
         let redis_cmd = Cmd::pset_ex(key, value, milliseconds);
-        self.exec_cmd(redis_cmd)
+        self.exec_cmd(Request::trailing(redis_cmd, stored))
     }
 
     /// Set the value of a key, only if the key does not exist.
     #[pyo3(text_signature = "($self, key, value)")]
-    fn set_nx(&self, key: RedisValuePy, value: RedisValuePy) -> PyResult<PyObject> {
+    fn set_nx(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        value: Bound<'_, PyAny>,
+    ) -> PyResult<PyObject> {
+        let value = self.encode_value(&value, "value")?;
+        let stored = value.arg_count();
         let redis_cmd = Cmd::set_nx(key, value);
-        self.exec_cmd(redis_cmd)
+        self.exec_cmd(Request::trailing(redis_cmd, stored))
     }
 
     /// Sets multiple keys to their values failing if at least one already
     /// exists.
     #[pyo3(text_signature = "($self, items)")]
-    fn mset_nx(&self, items: Vec<(RedisValuePy, RedisValuePy)>) -> PyResult<PyObject> {
+    fn mset_nx(&self, #[pyo3(from_py_with = "arg::items")] items: Pairs<'_>) -> PyResult<PyObject> {
+        let items = self.encode_items(items)?;
         let redis_cmd = Cmd::mset_nx(&items);
-        self.exec_cmd(redis_cmd)
+        self.exec_cmd(Request::pairs(redis_cmd, &items))
+    }
+
+    /// Set the keys of the `(key, value)` pairs in `iterable`, or of a dict, to
+    /// their values, expiring after `ex` seconds if given. Resolves to a dict
+    /// with the number of keys set as `count` and the `errors`. See `bulk`.
+    #[pyo3(signature = (iterable, ex = None, chunk_size = 10_000))]
+    #[pyo3(text_signature = "($self, iterable, ex = None, chunk_size = 10000)")]
+    fn bulk_set(
+        slf: &Bound<'_, Self>,
+        iterable: &Bound<'_, PyAny>,
+        ex: Option<u64>,
+        chunk_size: usize,
+    ) -> PyResult<PyObject> {
+        let items = match iterable.downcast::<PyDict>() {
+            Ok(dict) => dict.items().as_any().iter()?,
+            Err(_) => iterable.iter()?,
+        };
+
+        bulk::spawn(slf, &items, chunk_size, move |pool, item| {
+            let (key, value): (RedisValuePy, Bound<'_, PyAny>) = extract_arg(&item, "iterable")?;
+            let value = pool.encode_value(&value, "iterable")?;
+            let stored = value.arg_count();
+            let redis_cmd = match ex {
+                Some(seconds) => Cmd::set_ex(key, value, seconds),
+                None => Cmd::set(key, value),
+            };
+            Ok(Request::trailing(redis_cmd, stored))
+        })
+    }
+
+    /// Send `command` once for every item of `iterable`, with the item as its
+    /// arguments. Items are taken `chunk_size` at a time and every chunk is
+    /// pipelined over all connections before the next one is taken, so no
+    /// future is created per command. Resolves to a dict with the number of
+    /// commands sent as `count` and the `errors`, which holds at most one
+    /// error per pipeline. Like with `execute`, the arguments are not
+    /// serialized. Middleware `before` hooks run for every command, but since
+    /// the replies are dropped, `after` hooks, `SlowLog` and `on_span` are
+    /// not called.
+    #[pyo3(signature = (command, iterable, chunk_size = 10_000))]
+    #[pyo3(text_signature = "($self, command, iterable, chunk_size = 10000)")]
+    fn bulk(
+        slf: &Bound<'_, Self>,
+        command: String,
+        iterable: &Bound<'_, PyAny>,
+        chunk_size: usize,
+    ) -> PyResult<PyObject> {
+        bulk::spawn(slf, &iterable.iter()?, chunk_size, move |_, item| {
+            let mut redis_cmd = redis::cmd(&command);
+            redis_cmd.arg(extract_arg::<RedisValuePy>(&item, "iterable")?);
+            Ok(redis_cmd.into())
+        })
     }
 
     /// Set the string value of a key and return its old value.
     #[pyo3(text_signature = "($self, key, value)")]
-    fn getset(&self, key: RedisValuePy, value: RedisValuePy) -> PyResult<PyObject> {
+    fn getset(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        value: Bound<'_, PyAny>,
+    ) -> PyResult<PyObject> {
+        let value = self.encode_value(&value, "value")?;
+        let stored = value.arg_count();
         let redis_cmd = Cmd::getset(key, value);
-        self.query_cmd(redis_cmd)
+        self.query_cmd_as(Request::trailing(redis_cmd, stored), ResponseShape::Values)
     }
 
     /// Get a range of bytes/substring from the value of a key. Negative values
-    /// provide an offset from the end of the value.
-    #[pyo3(text_signature = "($self, key, from, to)")]
-    fn getrange(&self, key: RedisValuePy, from: isize, to: isize) -> PyResult<PyObject> {
+    /// provide an offset from the end of the value. With `raw_buffer`, it is
+    /// returned as a `RawBuffer`.
+    #[pyo3(signature = (key, from, to, *, prefer = Prefer::Replica, raw_buffer = false))]
+    #[pyo3(text_signature = "($self, key, from, to, *, prefer = \"replica\", raw_buffer = False)")]
+    fn getrange(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        from: isize,
+        to: isize,
+        prefer: Prefer,
+        raw_buffer: bool,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::getrange(key, from, to);
-        self.query_cmd(redis_cmd)
+        let shape = if raw_buffer {
+            ResponseShape::Buffer
+        } else {
+            ResponseShape::Generic
+        };
+        self.read_cmd_as(redis_cmd, shape, prefer)
     }
 
     /// Overwrite the part of the value stored in key at the specified offset.
     #[pyo3(text_signature = "($self, key, offset, value)")]
     fn setrange(
         &self,
-        key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
         offset: isize,
-        value: RedisValuePy,
+        #[pyo3(from_py_with = "arg::value")] value: RedisValuePy,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::setrange(key, offset, value);
         self.exec_cmd(redis_cmd)
@@ -212,84 +661,127 @@ impl ConnectionPool {
 
     /// Delete one or more keys.
     #[pyo3(text_signature = "($self, key)")]
-    fn del(&self, key: RedisValuePy) -> PyResult<PyObject> {
+    fn del(&self, #[pyo3(from_py_with = "arg::key")] key: RedisValuePy) -> PyResult<PyObject> {
         let redis_cmd = Cmd::del(key);
         self.exec_cmd(redis_cmd)
     }
 
     /// Determine if a key exists.
-    #[pyo3(text_signature = "($self, key)")]
-    fn exists(&self, key: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, *, prefer = \"replica\")")]
+    fn exists(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::exists(key);
-        self.query_cmd(redis_cmd)
+        self.read_cmd_as(redis_cmd, ResponseShape::Bool, prefer)
     }
 
     /// Set a key's time to live in seconds.
     #[pyo3(text_signature = "($self, key, seconds)")]
-    fn expire(&self, key: RedisValuePy, seconds: i64) -> PyResult<PyObject> {
+    fn expire(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        seconds: i64,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::expire(key, seconds);
         self.exec_cmd(redis_cmd)
     }
 
     /// Set the expiration for a key as a UNIX timestamp.
     #[pyo3(text_signature = "($self, key, ts)")]
-    fn expire_at(&self, key: RedisValuePy, ts: i64) -> PyResult<PyObject> {
+    fn expire_at(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        ts: i64,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::expire_at(key, ts);
         self.exec_cmd(redis_cmd)
     }
 
     /// Set a key's time to live in milliseconds.
     #[pyo3(text_signature = "($self, key, ms)")]
-    fn pexpire(&self, key: RedisValuePy, ms: i64) -> PyResult<PyObject> {
+    fn pexpire(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        ms: i64,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::pexpire(key, ms);
         self.exec_cmd(redis_cmd)
     }
 
     /// Set the expiration for a key as a UNIX timestamp in milliseconds.
     #[pyo3(text_signature = "($self, key, ts)")]
-    fn pexpire_at(&self, key: RedisValuePy, ts: i64) -> PyResult<PyObject> {
+    fn pexpire_at(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        ts: i64,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::pexpire_at(key, ts);
         self.exec_cmd(redis_cmd)
     }
 
     /// Remove the expiration from a key.
     #[pyo3(text_signature = "($self, key)")]
-    fn persist(&self, key: RedisValuePy) -> PyResult<PyObject> {
+    fn persist(&self, #[pyo3(from_py_with = "arg::key")] key: RedisValuePy) -> PyResult<PyObject> {
         let redis_cmd = Cmd::persist(key);
         self.exec_cmd(redis_cmd)
     }
 
     /// Get the expiration time of a key.
-    #[pyo3(text_signature = "($self, key)")]
-    fn ttl(&self, key: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, *, prefer = \"replica\")")]
+    fn ttl(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::ttl(key);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Get the expiration time of a key in milliseconds.
-    #[pyo3(text_signature = "($self, key)")]
-    fn pttl(&self, key: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, *, prefer = \"replica\")")]
+    fn pttl(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::pttl(key);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Rename a key.
     #[pyo3(text_signature = "($self, key, new_key)")]
-    fn rename(&self, key: RedisValuePy, new_key: RedisValuePy) -> PyResult<PyObject> {
+    fn rename(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::new_key")] new_key: RedisValuePy,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::rename(key, new_key);
         self.exec_cmd(redis_cmd)
     }
 
     /// Rename a key, only if the new key does not exist.
     #[pyo3(text_signature = "($self, key, new_key)")]
-    fn rename_nx(&self, key: RedisValuePy, new_key: RedisValuePy) -> PyResult<PyObject> {
+    fn rename_nx(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::new_key")] new_key: RedisValuePy,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::rename_nx(key, new_key);
         self.exec_cmd(redis_cmd)
     }
 
     /// Append a value to a key.
     #[pyo3(text_signature = "($self, key, value)")]
-    fn append(&self, key: RedisValuePy, value: RedisValuePy) -> PyResult<PyObject> {
+    fn append(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::value")] value: RedisValuePy,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::append(key, value);
         self.exec_cmd(redis_cmd)
     }
@@ -297,43 +789,74 @@ impl ConnectionPool {
     /// Increment the numeric value of a key by the given amount. This issues a
     /// `INCRBY` or `INCRBYFLOAT` depending on the type.
     #[pyo3(text_signature = "($self, key, delta)")]
-    fn incr(&self, key: RedisValuePy, delta: RedisValuePy) -> PyResult<PyObject> {
+    fn incr(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::delta")] delta: RedisValuePy,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::incr(key, delta);
         self.exec_cmd(redis_cmd)
     }
 
     /// Sets or clears the bit at offset in the string value stored at key.
     #[pyo3(text_signature = "($self, key, offset, value)")]
-    fn setbit(&self, key: RedisValuePy, offset: usize, value: bool) -> PyResult<PyObject> {
+    fn setbit(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        offset: usize,
+        value: bool,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::setbit(key, offset, value);
         self.exec_cmd(redis_cmd)
     }
 
     /// Returns the bit value at offset in the string value stored at key.
-    #[pyo3(text_signature = "($self, key, offset)")]
-    fn getbit(&self, key: RedisValuePy, offset: usize) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, offset, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, offset, *, prefer = \"replica\")")]
+    fn getbit(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        offset: usize,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::getbit(key, offset);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Count set bits in a string.
-    #[pyo3(text_signature = "($self, key)")]
-    fn bitcount(&self, key: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, *, prefer = \"replica\")")]
+    fn bitcount(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::bitcount(key);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Count set bits in a string in a range.
-    #[pyo3(text_signature = "($self, key, start, end)")]
-    fn bitcount_range(&self, key: RedisValuePy, start: usize, end: usize) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, start, end, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, start, end, *, prefer = \"replica\")")]
+    fn bitcount_range(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        start: usize,
+        end: usize,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::bitcount_range(key, start, end);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Perform a bitwise AND between multiple keys (containing string values)
     /// and store the result in the destination key.
     #[pyo3(text_signature = "($self, dstkey, srckeys)")]
-    fn bit_and(&self, dstkey: RedisValuePy, srckeys: RedisValuePy) -> PyResult<PyObject> {
+    fn bit_and(
+        &self,
+        #[pyo3(from_py_with = "arg::dstkey")] dstkey: RedisValuePy,
+        #[pyo3(from_py_with = "arg::srckeys")] srckeys: RedisValuePy,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::bit_and(dstkey, srckeys);
         self.exec_cmd(redis_cmd)
     }
@@ -341,7 +864,11 @@ impl ConnectionPool {
     /// Perform a bitwise OR between multiple keys (containing string values)
     /// and store the result in the destination key.
     #[pyo3(text_signature = "($self, dstkey, srckeys)")]
-    fn bit_or(&self, dstkey: RedisValuePy, srckeys: RedisValuePy) -> PyResult<PyObject> {
+    fn bit_or(
+        &self,
+        #[pyo3(from_py_with = "arg::dstkey")] dstkey: RedisValuePy,
+        #[pyo3(from_py_with = "arg::srckeys")] srckeys: RedisValuePy,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::bit_or(dstkey, srckeys);
         self.exec_cmd(redis_cmd)
     }
@@ -349,7 +876,11 @@ impl ConnectionPool {
     /// Perform a bitwise XOR between multiple keys (containing string values)
     /// and store the result in the destination key.
     #[pyo3(text_signature = "($self, dstkey, srckeys)")]
-    fn bit_xor(&self, dstkey: RedisValuePy, srckeys: RedisValuePy) -> PyResult<PyObject> {
+    fn bit_xor(
+        &self,
+        #[pyo3(from_py_with = "arg::dstkey")] dstkey: RedisValuePy,
+        #[pyo3(from_py_with = "arg::srckeys")] srckeys: RedisValuePy,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::bit_xor(dstkey, srckeys);
         self.exec_cmd(redis_cmd)
     }
@@ -357,28 +888,54 @@ impl ConnectionPool {
     /// Perform a bitwise NOT of the key (containing string values) and store
     /// the result in the destination key.
     #[pyo3(text_signature = "($self, dstkey, srckeys)")]
-    fn bit_not(&self, dstkey: RedisValuePy, srckeys: RedisValuePy) -> PyResult<PyObject> {
+    fn bit_not(
+        &self,
+        #[pyo3(from_py_with = "arg::dstkey")] dstkey: RedisValuePy,
+        #[pyo3(from_py_with = "arg::srckeys")] srckeys: RedisValuePy,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::bit_not(dstkey, srckeys);
         self.exec_cmd(redis_cmd)
     }
 
     /// Get the length of the value stored in a key.
-    #[pyo3(text_signature = "($self, key)")]
-    fn strlen(&self, key: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, *, prefer = \"replica\")")]
+    fn strlen(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::strlen(key);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
-    /// Gets a single (or multiple) fields from a hash.
-    #[pyo3(text_signature = "($self, key, field)")]
-    fn hget(&self, key: RedisValuePy, field: RedisValuePy) -> PyResult<PyObject> {
+    /// Gets a single (or multiple) fields from a hash. With `raw_buffer`, the
+    /// values are returned as `RawBuffer`s.
+    #[pyo3(signature = (key, field, *, prefer = Prefer::Replica, raw_buffer = false))]
+    #[pyo3(text_signature = "($self, key, field, *, prefer = \"replica\", raw_buffer = False)")]
+    fn hget(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::field")] field: RedisValuePy,
+        prefer: Prefer,
+        raw_buffer: bool,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::hget(key, field);
-        self.query_cmd(redis_cmd)
+        let shape = if raw_buffer {
+            ResponseShape::Buffer
+        } else {
+            ResponseShape::Values
+        };
+        self.read_cmd_as(redis_cmd, shape, prefer)
     }
 
     /// Deletes a single (or multiple) fields from a hash.
     #[pyo3(text_signature = "($self, key, field)")]
-    fn hdel(&self, key: RedisValuePy, field: RedisValuePy) -> PyResult<PyObject> {
+    fn hdel(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::field")] field: RedisValuePy,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::hdel(key, field);
         self.exec_cmd(redis_cmd)
     }
@@ -387,88 +944,137 @@ impl ConnectionPool {
     #[pyo3(text_signature = "($self, key, field, value)")]
     fn hset(
         &self,
-        key: RedisValuePy,
-        field: RedisValuePy,
-        value: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::field")] field: RedisValuePy,
+        value: Bound<'_, PyAny>,
     ) -> PyResult<PyObject> {
+        let value = self.encode_value(&value, "value")?;
+        let stored = value.arg_count();
         let redis_cmd = Cmd::hset(key, field, value);
-        self.exec_cmd(redis_cmd)
+        self.exec_cmd(Request::trailing(redis_cmd, stored))
     }
 
     /// Sets a single field in a hash if it does not exist.
     #[pyo3(text_signature = "($self, key, field, value)")]
     fn hset_nx(
         &self,
-        key: RedisValuePy,
-        field: RedisValuePy,
-        value: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::field")] field: RedisValuePy,
+        value: Bound<'_, PyAny>,
     ) -> PyResult<PyObject> {
+        let value = self.encode_value(&value, "value")?;
+        let stored = value.arg_count();
         let redis_cmd = Cmd::hset_nx(key, field, value);
-        self.exec_cmd(redis_cmd)
+        self.exec_cmd(Request::trailing(redis_cmd, stored))
     }
 
     /// Sets a multiple fields in a hash.
     #[pyo3(text_signature = "($self, key, items)")]
     fn hset_multiple(
         &self,
-        key: RedisValuePy,
-        items: Vec<(RedisValuePy, RedisValuePy)>,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::items")] items: Pairs<'_>,
     ) -> PyResult<PyObject> {
+        let items = self.encode_items(items)?;
         let redis_cmd = Cmd::hset_multiple(key, &items);
-        self.exec_cmd(redis_cmd)
+        self.exec_cmd(Request::pairs(redis_cmd, &items))
     }
 
     /// Increments a value.
     #[pyo3(text_signature = "($self, key, field, delta)")]
     fn hincr(
         &self,
-        key: RedisValuePy,
-        field: RedisValuePy,
-        delta: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::field")] field: RedisValuePy,
+        #[pyo3(from_py_with = "arg::delta")] delta: RedisValuePy,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::hincr(key, field, delta);
         self.exec_cmd(redis_cmd)
     }
 
     /// Checks if a field in a hash exists.
-    #[pyo3(text_signature = "($self, key, field)")]
-    fn hexists(&self, key: RedisValuePy, field: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, field, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, field, *, prefer = \"replica\")")]
+    fn hexists(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::field")] field: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::hexists(key, field);
-        self.query_cmd(redis_cmd)
+        self.read_cmd_as(redis_cmd, ResponseShape::Bool, prefer)
     }
 
     /// Gets all the keys in a hash.
-    #[pyo3(text_signature = "($self, key)")]
-    fn hkeys(&self, key: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, *, prefer = \"replica\")")]
+    fn hkeys(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::hkeys(key);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Gets all the values in a hash.
-    #[pyo3(text_signature = "($self, key)")]
-    fn hvals(&self, key: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, *, prefer = \"replica\")")]
+    fn hvals(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::hvals(key);
-        self.query_cmd(redis_cmd)
+        self.read_cmd_as(redis_cmd, ResponseShape::Values, prefer)
     }
 
-    /// Gets all the fields and values in a hash.
-    #[pyo3(text_signature = "($self, key)")]
-    fn hgetall(&self, key: RedisValuePy) -> PyResult<PyObject> {
+    /// Gets all the fields and values in a hash as a dict.
+    #[pyo3(signature = (key, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, *, prefer = \"replica\")")]
+    fn hgetall(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::hgetall(key);
-        self.query_cmd(redis_cmd)
+        self.read_cmd_as(redis_cmd, ResponseShape::Dict, prefer)
+    }
+
+    /// Iterate over the fields and values of a hash with `HSCAN`, as dicts of
+    /// about `chunk_size` fields. A field may be returned more than once.
+    /// See `Chunks`.
+    #[pyo3(signature = (key, *, chunk_size = 1000, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, *, chunk_size = 1000, prefer = \"replica\")")]
+    fn hgetall_iter(
+        slf: Py<Self>,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        chunk_size: usize,
+        prefer: Prefer,
+    ) -> PyResult<Chunks> {
+        Chunks::new(slf, key, Source::Hash, chunk_size, prefer)
     }
 
     /// Gets the length of a hash.
-    #[pyo3(text_signature = "($self, key)")]
-    fn hlen(&self, key: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, *, prefer = \"replica\")")]
+    fn hlen(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::hlen(key);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Remove and get the first element in a list, or block until one is
     /// available.
     #[pyo3(text_signature = "($self, key, timeout)")]
-    fn blpop(&self, key: RedisValuePy, timeout: f64) -> PyResult<PyObject> {
+    fn blpop(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        timeout: f64,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::blpop(key, timeout);
         self.query_cmd(redis_cmd)
     }
@@ -476,7 +1082,11 @@ impl ConnectionPool {
     /// Remove and get the last element in a list, or block until one is
     /// available.
     #[pyo3(text_signature = "($self, key, timeout)")]
-    fn brpop(&self, key: RedisValuePy, timeout: f64) -> PyResult<PyObject> {
+    fn brpop(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        timeout: f64,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::brpop(key, timeout);
         self.query_cmd(redis_cmd)
     }
@@ -486,196 +1096,335 @@ impl ConnectionPool {
     #[pyo3(text_signature = "($self, srckey, dstkey, timeout)")]
     fn brpoplpush(
         &self,
-        srckey: RedisValuePy,
-        dstkey: RedisValuePy,
+        #[pyo3(from_py_with = "arg::srckey")] srckey: RedisValuePy,
+        #[pyo3(from_py_with = "arg::dstkey")] dstkey: RedisValuePy,
         timeout: f64,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::brpoplpush(srckey, dstkey, timeout);
-        self.query_cmd(redis_cmd)
+        self.query_cmd_as(redis_cmd, ResponseShape::Values)
     }
 
     /// Get an element from a list by its index.
-    #[pyo3(text_signature = "($self, key, index)")]
-    fn lindex(&self, key: RedisValuePy, index: isize) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, index, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, index, *, prefer = \"replica\")")]
+    fn lindex(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        index: isize,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::lindex(key, index);
-        self.query_cmd(redis_cmd)
+        self.read_cmd_as(redis_cmd, ResponseShape::Values, prefer)
     }
 
     /// Insert an element before another element in a list.
     #[pyo3(text_signature = "($self, key, pivot, value)")]
     fn linsert_before(
         &self,
-        key: RedisValuePy,
-        pivot: RedisValuePy,
-        value: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        pivot: Bound<'_, PyAny>,
+        value: Bound<'_, PyAny>,
     ) -> PyResult<PyObject> {
+        let pivot = self.encode_value(&pivot, "pivot")?;
+        let value = self.encode_value(&value, "value")?;
+        let stored = pivot.arg_count() + value.arg_count();
         let redis_cmd = Cmd::linsert_before(key, pivot, value);
-        self.exec_cmd(redis_cmd)
+        self.exec_cmd(Request::trailing(redis_cmd, stored))
     }
 
     /// Insert an element after another element in a list.
     #[pyo3(text_signature = "($self, key, pivot, value)")]
     fn linsert_after(
         &self,
-        key: RedisValuePy,
-        pivot: RedisValuePy,
-        value: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        pivot: Bound<'_, PyAny>,
+        value: Bound<'_, PyAny>,
     ) -> PyResult<PyObject> {
+        let pivot = self.encode_value(&pivot, "pivot")?;
+        let value = self.encode_value(&value, "value")?;
+        let stored = pivot.arg_count() + value.arg_count();
         let redis_cmd = Cmd::linsert_after(key, pivot, value);
-        self.exec_cmd(redis_cmd)
+        self.exec_cmd(Request::trailing(redis_cmd, stored))
     }
 
     /// Returns the length of the list stored at key.
-    #[pyo3(text_signature = "($self, key)")]
-    fn llen(&self, key: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, *, prefer = \"replica\")")]
+    fn llen(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::llen(key);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Removes and returns the first element of the list stored at key.
     #[pyo3(signature = (key, count = None), text_signature = "($self, key, count = None)")]
-    fn lpop(&self, key: RedisValuePy, count: Option<usize>) -> PyResult<PyObject> {
+    fn lpop(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        count: Option<usize>,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::lpop(key, count.and_then(NonZeroUsize::new));
-        self.query_cmd(redis_cmd)
+        self.query_cmd_as(redis_cmd, ResponseShape::Values)
     }
 
     /// Insert all the specified values at the head of the list stored at key.
     #[pyo3(text_signature = "($self, key, value)")]
-    fn lpush(&self, key: RedisValuePy, value: RedisValuePy) -> PyResult<PyObject> {
+    fn lpush(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        value: Bound<'_, PyAny>,
+    ) -> PyResult<PyObject> {
+        let value = self.encode_values(&value, "value")?;
+        let stored = value.arg_count();
         let redis_cmd = Cmd::lpush(key, value);
-        self.exec_cmd(redis_cmd)
+        self.exec_cmd(Request::trailing(redis_cmd, stored))
     }
 
     /// Inserts a value at the head of the list stored at key, only if key
     /// already exists and holds a list.
     #[pyo3(text_signature = "($self, key, value)")]
-    fn lpush_exists(&self, key: RedisValuePy, value: RedisValuePy) -> PyResult<PyObject> {
+    fn lpush_exists(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        value: Bound<'_, PyAny>,
+    ) -> PyResult<PyObject> {
+        let value = self.encode_values(&value, "value")?;
+        let stored = value.arg_count();
         let redis_cmd = Cmd::lpush_exists(key, value);
-        self.exec_cmd(redis_cmd)
+        self.exec_cmd(Request::trailing(redis_cmd, stored))
     }
 
-    /// Returns the specified elements of the list stored at key.
-    #[pyo3(text_signature = "($self, key, start, stop)")]
-    fn lrange(&self, key: RedisValuePy, start: isize, stop: isize) -> PyResult<PyObject> {
+    /// Returns the specified elements of the list stored at key. With
+    /// `raw_buffer`, they are returned as `RawBuffer`s.
+    #[pyo3(signature = (key, start, stop, *, prefer = Prefer::Replica, raw_buffer = false))]
+    #[pyo3(
+        text_signature = "($self, key, start, stop, *, prefer = \"replica\", raw_buffer = False)"
+    )]
+    fn lrange(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        start: isize,
+        stop: isize,
+        prefer: Prefer,
+        raw_buffer: bool,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::lrange(key, start, stop);
-        self.query_cmd(redis_cmd)
+        let shape = if raw_buffer {
+            ResponseShape::Buffer
+        } else {
+            ResponseShape::Values
+        };
+        self.read_cmd_as(redis_cmd, shape, prefer)
+    }
+
+    /// Iterate over the elements of a list in chunks of `chunk_size`
+    /// elements, see `Chunks`.
+    #[pyo3(signature = (key, *, chunk_size = 1000, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, *, chunk_size = 1000, prefer = \"replica\")")]
+    fn lrange_iter(
+        slf: Py<Self>,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        chunk_size: usize,
+        prefer: Prefer,
+    ) -> PyResult<Chunks> {
+        Chunks::new(slf, key, Source::List, chunk_size, prefer)
     }
 
     /// Removes the first count occurrences of elements equal to value from the
     /// list stored at key.
     #[pyo3(text_signature = "($self, key, count, value)")]
-    fn lrem(&self, key: RedisValuePy, count: isize, value: RedisValuePy) -> PyResult<PyObject> {
+    fn lrem(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        count: isize,
+        value: Bound<'_, PyAny>,
+    ) -> PyResult<PyObject> {
+        let value = self.encode_value(&value, "value")?;
+        let stored = value.arg_count();
         let redis_cmd = Cmd::lrem(key, count, value);
-        self.exec_cmd(redis_cmd)
+        self.exec_cmd(Request::trailing(redis_cmd, stored))
     }
 
     /// Trim an existing list so that it will contain only the specified range
     /// of elements specified.
     #[pyo3(text_signature = "($self, key, start, stop)")]
-    fn ltrim(&self, key: RedisValuePy, start: isize, stop: isize) -> PyResult<PyObject> {
+    fn ltrim(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        start: isize,
+        stop: isize,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::ltrim(key, start, stop);
         self.exec_cmd(redis_cmd)
     }
 
     /// Sets the list element at index to value.
     #[pyo3(text_signature = "($self, key, index, value)")]
-    fn lset(&self, key: RedisValuePy, index: isize, value: RedisValuePy) -> PyResult<PyObject> {
+    fn lset(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        index: isize,
+        value: Bound<'_, PyAny>,
+    ) -> PyResult<PyObject> {
+        let value = self.encode_value(&value, "value")?;
+        let stored = value.arg_count();
         let redis_cmd = Cmd::lset(key, index, value);
-        self.exec_cmd(redis_cmd)
+        self.exec_cmd(Request::trailing(redis_cmd, stored))
     }
 
     /// Removes and returns the last element of the list stored at key.
-    #[pyo3(signature = (key, count = None), text_signature = "($self, key)")]
-    fn rpop(&self, key: RedisValuePy, count: Option<usize>) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, count = None), text_signature = "($self, key, count = None)")]
+    fn rpop(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        count: Option<usize>,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::rpop(key, count.and_then(NonZeroUsize::new));
-        self.query_cmd(redis_cmd)
+        self.query_cmd_as(redis_cmd, ResponseShape::Values)
     }
 
     /// Pop a value from a list, push it to another list and return it.
     #[pyo3(text_signature = "($self, key, dstkey)")]
-    fn rpoplpush(&self, key: RedisValuePy, dstkey: RedisValuePy) -> PyResult<PyObject> {
+    fn rpoplpush(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::dstkey")] dstkey: RedisValuePy,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::rpoplpush(key, dstkey);
-        self.query_cmd(redis_cmd)
+        self.query_cmd_as(redis_cmd, ResponseShape::Values)
     }
 
     /// Insert all the specified values at the tail of the list stored at key.
     #[pyo3(text_signature = "($self, key, value)")]
-    fn rpush(&self, key: RedisValuePy, value: RedisValuePy) -> PyResult<PyObject> {
+    fn rpush(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        value: Bound<'_, PyAny>,
+    ) -> PyResult<PyObject> {
+        let value = self.encode_values(&value, "value")?;
+        let stored = value.arg_count();
         let redis_cmd = Cmd::rpush(key, value);
-        self.exec_cmd(redis_cmd)
+        self.exec_cmd(Request::trailing(redis_cmd, stored))
     }
 
     /// Inserts value at the tail of the list stored at key, only if key already
     /// exists and holds a list.
     #[pyo3(text_signature = "($self, key, value)")]
-    fn rpush_exists(&self, key: RedisValuePy, value: RedisValuePy) -> PyResult<PyObject> {
+    fn rpush_exists(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        value: Bound<'_, PyAny>,
+    ) -> PyResult<PyObject> {
+        let value = self.encode_values(&value, "value")?;
+        let stored = value.arg_count();
         let redis_cmd = Cmd::rpush_exists(key, value);
-        self.exec_cmd(redis_cmd)
+        self.exec_cmd(Request::trailing(redis_cmd, stored))
     }
 
     /// Add one or more members to a set.
     #[pyo3(text_signature = "($self, key, member)")]
-    fn sadd(&self, key: RedisValuePy, member: RedisValuePy) -> PyResult<PyObject> {
+    fn sadd(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::member")] member: RedisValuePy,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::sadd(key, member);
         self.exec_cmd(redis_cmd)
     }
 
     /// Get the number of members in a set.
-    #[pyo3(text_signature = "($self, key)")]
-    fn scard(&self, key: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, *, prefer = \"replica\")")]
+    fn scard(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::scard(key);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Subtract multiple sets.
-    #[pyo3(text_signature = "($self, keys)")]
-    fn sdiff(&self, keys: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (keys, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, keys, *, prefer = \"replica\")")]
+    fn sdiff(
+        &self,
+        #[pyo3(from_py_with = "arg::keys")] keys: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::sdiff(keys);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Subtract multiple sets and store the resulting set in a key.
     #[pyo3(text_signature = "($self, dstkey, keys)")]
-    fn sdiffstore(&self, dstkey: RedisValuePy, keys: RedisValuePy) -> PyResult<PyObject> {
+    fn sdiffstore(
+        &self,
+        #[pyo3(from_py_with = "arg::dstkey")] dstkey: RedisValuePy,
+        #[pyo3(from_py_with = "arg::keys")] keys: RedisValuePy,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::sdiffstore(dstkey, keys);
         self.exec_cmd(redis_cmd)
     }
 
     /// Intersect multiple sets.
-    #[pyo3(text_signature = "($self, keys)")]
-    fn sinter(&self, keys: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (keys, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, keys, *, prefer = \"replica\")")]
+    fn sinter(
+        &self,
+        #[pyo3(from_py_with = "arg::keys")] keys: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::sinter(keys);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Intersect multiple sets and store the resulting set in a key.
     #[pyo3(text_signature = "($self, dstkey, keys)")]
-    fn sinterstore(&self, dstkey: RedisValuePy, keys: RedisValuePy) -> PyResult<PyObject> {
+    fn sinterstore(
+        &self,
+        #[pyo3(from_py_with = "arg::dstkey")] dstkey: RedisValuePy,
+        #[pyo3(from_py_with = "arg::keys")] keys: RedisValuePy,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::sinterstore(dstkey, keys);
         self.exec_cmd(redis_cmd)
     }
 
     /// Determine if a given value is a member of a set.
-    #[pyo3(text_signature = "($self, key, member)")]
-    fn sismember(&self, key: RedisValuePy, member: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, member, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, member, *, prefer = \"replica\")")]
+    fn sismember(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::member")] member: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::sismember(key, member);
-        self.query_cmd(redis_cmd)
+        self.read_cmd_as(redis_cmd, ResponseShape::Bool, prefer)
     }
 
     /// Get all the members in a set.
-    #[pyo3(text_signature = "($self, key)")]
-    fn smembers(&self, key: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, *, prefer = \"replica\")")]
+    fn smembers(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::smembers(key);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Move a member from one set to another.
     #[pyo3(text_signature = "($self, srckey, dstkey, member)")]
     fn smove(
         &self,
-        srckey: RedisValuePy,
-        dstkey: RedisValuePy,
-        member: RedisValuePy,
+        #[pyo3(from_py_with = "arg::srckey")] srckey: RedisValuePy,
+        #[pyo3(from_py_with = "arg::dstkey")] dstkey: RedisValuePy,
+        #[pyo3(from_py_with = "arg::member")] member: RedisValuePy,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::smove(srckey, dstkey, member);
         self.exec_cmd(redis_cmd)
@@ -683,42 +1432,66 @@ impl ConnectionPool {
 
     /// Remove and return a random member from a set.
     #[pyo3(text_signature = "($self, key)")]
-    fn spop(&self, key: RedisValuePy) -> PyResult<PyObject> {
+    fn spop(&self, #[pyo3(from_py_with = "arg::key")] key: RedisValuePy) -> PyResult<PyObject> {
         let redis_cmd = Cmd::spop(key);
         self.query_cmd(redis_cmd)
     }
 
     /// Get one random member from a set.
-    #[pyo3(text_signature = "($self, key)")]
-    fn srandmember(&self, key: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, *, prefer = \"replica\")")]
+    fn srandmember(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::srandmember(key);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Get multiple random members from a set.
-    #[pyo3(text_signature = "($self, key, count)")]
-    fn srandmember_multiple(&self, key: RedisValuePy, count: usize) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, count, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, count, *, prefer = \"replica\")")]
+    fn srandmember_multiple(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        count: usize,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::srandmember_multiple(key, count);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Remove one or more members from a set.
     #[pyo3(text_signature = "($self, key, member)")]
-    fn srem(&self, key: RedisValuePy, member: RedisValuePy) -> PyResult<PyObject> {
+    fn srem(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::member")] member: RedisValuePy,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::srem(key, member);
         self.exec_cmd(redis_cmd)
     }
 
     /// Add multiple sets.
-    #[pyo3(text_signature = "($self, keys)")]
-    fn sunion(&self, keys: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (keys, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, keys, *, prefer = \"replica\")")]
+    fn sunion(
+        &self,
+        #[pyo3(from_py_with = "arg::keys")] keys: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::sunion(keys);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Add multiple sets and store the resulting set in a key.
     #[pyo3(text_signature = "($self, dstkey, keys)")]
-    fn sunionstore(&self, dstkey: RedisValuePy, keys: RedisValuePy) -> PyResult<PyObject> {
+    fn sunionstore(
+        &self,
+        #[pyo3(from_py_with = "arg::dstkey")] dstkey: RedisValuePy,
+        #[pyo3(from_py_with = "arg::keys")] keys: RedisValuePy,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::sunionstore(dstkey, keys);
         self.exec_cmd(redis_cmd)
     }
@@ -728,9 +1501,9 @@ impl ConnectionPool {
     #[pyo3(text_signature = "($self, key, member, score)")]
     fn zadd(
         &self,
-        key: RedisValuePy,
-        member: RedisValuePy,
-        score: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::member")] member: RedisValuePy,
+        #[pyo3(from_py_with = "arg::score")] score: RedisValuePy,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zadd(key, member, score);
         self.exec_cmd(redis_cmd)
@@ -741,7 +1514,7 @@ impl ConnectionPool {
     #[pyo3(text_signature = "($self, key, items)")]
     fn zadd_multiple(
         &self,
-        key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
         items: Vec<(RedisValuePy, RedisValuePy)>,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zadd_multiple(key, &items);
@@ -749,41 +1522,53 @@ impl ConnectionPool {
     }
 
     /// Get the number of members in a sorted set.
-    #[pyo3(text_signature = "($self, key)")]
-    fn zcard(&self, key: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, *, prefer = \"replica\")")]
+    fn zcard(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zcard(key);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Count the members in a sorted set with scores within the given values.
-    #[pyo3(text_signature = "($self, key, min, max)")]
+    #[pyo3(signature = (key, min, max, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, min, max, *, prefer = \"replica\")")]
     fn zcount(
         &self,
-        key: RedisValuePy,
-        min: RedisValuePy,
-        max: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::min")] min: RedisValuePy,
+        #[pyo3(from_py_with = "arg::max")] max: RedisValuePy,
+        prefer: Prefer,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zcount(key, min, max);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
-    /// Increments the member in a sorted set at key by delta. If the member
-    /// does not exist, it is added with delta as its score.
+    /// Increments the member in a sorted set at key by delta and returns the
+    /// new score. If the member does not exist, it is added with delta as its
+    /// score.
     #[pyo3(text_signature = "($self, key, member, delta)")]
     fn zincr(
         &self,
-        key: RedisValuePy,
-        member: RedisValuePy,
-        delta: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::member")] member: RedisValuePy,
+        #[pyo3(from_py_with = "arg::delta")] delta: RedisValuePy,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zincr(key, member, delta);
-        self.exec_cmd(redis_cmd)
+        self.query_cmd_as(redis_cmd, ResponseShape::Float)
     }
 
     /// Intersect multiple sorted sets and store the resulting sorted set in a
     /// new key using SUM as aggregation function.
     #[pyo3(text_signature = "($self, dstkey, keys)")]
-    fn zinterstore(&self, dstkey: RedisValuePy, keys: Vec<RedisValuePy>) -> PyResult<PyObject> {
+    fn zinterstore(
+        &self,
+        #[pyo3(from_py_with = "arg::dstkey")] dstkey: RedisValuePy,
+        #[pyo3(from_py_with = "arg::keys")] keys: Vec<RedisValuePy>,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zinterstore(dstkey, &keys);
         self.exec_cmd(redis_cmd)
     }
@@ -791,7 +1576,11 @@ impl ConnectionPool {
     /// Intersect multiple sorted sets and store the resulting sorted set in a
     /// new key using MIN as aggregation function.
     #[pyo3(text_signature = "($self, dstkey, keys)")]
-    fn zinterstore_min(&self, dstkey: RedisValuePy, keys: Vec<RedisValuePy>) -> PyResult<PyObject> {
+    fn zinterstore_min(
+        &self,
+        #[pyo3(from_py_with = "arg::dstkey")] dstkey: RedisValuePy,
+        #[pyo3(from_py_with = "arg::keys")] keys: Vec<RedisValuePy>,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zinterstore_min(dstkey, &keys);
         self.exec_cmd(redis_cmd)
     }
@@ -799,176 +1588,243 @@ impl ConnectionPool {
     /// Intersect multiple sorted sets and store the resulting sorted set in a
     /// new key using MAX as aggregation function.
     #[pyo3(text_signature = "($self, dstkey, keys)")]
-    fn zinterstore_max(&self, dstkey: RedisValuePy, keys: Vec<RedisValuePy>) -> PyResult<PyObject> {
+    fn zinterstore_max(
+        &self,
+        #[pyo3(from_py_with = "arg::dstkey")] dstkey: RedisValuePy,
+        #[pyo3(from_py_with = "arg::keys")] keys: Vec<RedisValuePy>,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zinterstore_max(dstkey, &keys);
         self.exec_cmd(redis_cmd)
     }
 
     /// Count the number of members in a sorted set between a given
     /// lexicographical range.
-    #[pyo3(text_signature = "($self, key, min, max)")]
+    #[pyo3(signature = (key, min, max, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, min, max, *, prefer = \"replica\")")]
     fn zlexcount(
         &self,
-        key: RedisValuePy,
-        min: RedisValuePy,
-        max: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::min")] min: RedisValuePy,
+        #[pyo3(from_py_with = "arg::max")] max: RedisValuePy,
+        prefer: Prefer,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zlexcount(key, min, max);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Removes and returns up to count members with the highest scores in a
     /// sorted set.
     #[pyo3(text_signature = "($self, key, count)")]
-    fn zpopmax(&self, key: RedisValuePy, count: isize) -> PyResult<PyObject> {
+    fn zpopmax(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        count: isize,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zpopmax(key, count);
-        self.query_cmd(redis_cmd)
+        self.query_cmd_as(redis_cmd, ResponseShape::Scored)
     }
 
     /// Removes and returns up to count members with the lowest scores in a
     /// sorted set.
     #[pyo3(text_signature = "($self, key, count)")]
-    fn zpopmin(&self, key: RedisValuePy, count: isize) -> PyResult<PyObject> {
+    fn zpopmin(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        count: isize,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zpopmin(key, count);
-        self.query_cmd(redis_cmd)
+        self.query_cmd_as(redis_cmd, ResponseShape::Scored)
     }
 
     /// Return a range of members in a sorted set, by index.
-    #[pyo3(text_signature = "($self, key, start, stop)")]
-    fn zrange(&self, key: RedisValuePy, start: isize, stop: isize) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, start, stop, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, start, stop, *, prefer = \"replica\")")]
+    fn zrange(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        start: isize,
+        stop: isize,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zrange(key, start, stop);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Return a range of members in a sorted set, by index with scores.
-    #[pyo3(text_signature = "($self, key, start, stop)")]
+    #[pyo3(signature = (key, start, stop, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, start, stop, *, prefer = \"replica\")")]
     fn zrange_withscores(
         &self,
-        key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
         start: isize,
         stop: isize,
+        prefer: Prefer,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zrange_withscores(key, start, stop);
-        self.query_cmd(redis_cmd)
+        self.read_cmd_as(redis_cmd, ResponseShape::Scored, prefer)
+    }
+
+    /// Iterate over the members of a sorted set by index in chunks of
+    /// `chunk_size` members, as `(member, score)` tuples if `withscores` is
+    /// set. See `Chunks`.
+    #[pyo3(signature = (key, *, chunk_size = 1000, withscores = false, prefer = Prefer::Replica))]
+    #[pyo3(
+        text_signature = "($self, key, *, chunk_size = 1000, withscores = False, prefer = \"replica\")"
+    )]
+    fn zrange_iter(
+        slf: Py<Self>,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        chunk_size: usize,
+        withscores: bool,
+        prefer: Prefer,
+    ) -> PyResult<Chunks> {
+        let source = Source::SortedSet { withscores };
+        Chunks::new(slf, key, source, chunk_size, prefer)
     }
 
     /// Return a range of members in a sorted set, by lexicographical range.
-    #[pyo3(text_signature = "($self, key, min, max)")]
+    #[pyo3(signature = (key, min, max, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, min, max, *, prefer = \"replica\")")]
     fn zrangebylex(
         &self,
-        key: RedisValuePy,
-        min: RedisValuePy,
-        max: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::min")] min: RedisValuePy,
+        #[pyo3(from_py_with = "arg::max")] max: RedisValuePy,
+        prefer: Prefer,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zrangebylex(key, min, max);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Return a range of members in a sorted set, by lexicographical range with
     /// offset and limit.
-    #[pyo3(text_signature = "($self, key, min, max, offset, count)")]
+    #[pyo3(signature = (key, min, max, offset, count, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, min, max, offset, count, *, prefer = \"replica\")")]
     fn zrangebylex_limit(
         &self,
-        key: RedisValuePy,
-        min: RedisValuePy,
-        max: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::min")] min: RedisValuePy,
+        #[pyo3(from_py_with = "arg::max")] max: RedisValuePy,
         offset: isize,
         count: isize,
+        prefer: Prefer,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zrangebylex_limit(key, min, max, offset, count);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Return a range of members in a sorted set, by lexicographical range.
-    #[pyo3(text_signature = "($self, key, max, min)")]
+    #[pyo3(signature = (key, max, min, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, max, min, *, prefer = \"replica\")")]
     fn zrevrangebylex(
         &self,
-        key: RedisValuePy,
-        max: RedisValuePy,
-        min: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::max")] max: RedisValuePy,
+        #[pyo3(from_py_with = "arg::min")] min: RedisValuePy,
+        prefer: Prefer,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zrevrangebylex(key, max, min);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Return a range of members in a sorted set, by lexicographical range with
     /// offset and limit.
-    #[pyo3(text_signature = "($self, key, max, min, offset, count)")]
+    #[pyo3(signature = (key, max, min, offset, count, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, max, min, offset, count, *, prefer = \"replica\")")]
     fn zrevrangebylex_limit(
         &self,
-        key: RedisValuePy,
-        max: RedisValuePy,
-        min: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::max")] max: RedisValuePy,
+        #[pyo3(from_py_with = "arg::min")] min: RedisValuePy,
         offset: isize,
         count: isize,
+        prefer: Prefer,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zrevrangebylex_limit(key, max, min, offset, count);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Return a range of members in a sorted set, by score.
-    #[pyo3(text_signature = "($self, key, min, max)")]
+    #[pyo3(signature = (key, min, max, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, min, max, *, prefer = \"replica\")")]
     fn zrangebyscore(
         &self,
-        key: RedisValuePy,
-        min: RedisValuePy,
-        max: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::min")] min: RedisValuePy,
+        #[pyo3(from_py_with = "arg::max")] max: RedisValuePy,
+        prefer: Prefer,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zrangebyscore(key, min, max);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Return a range of members in a sorted set, by score with scores.
-    #[pyo3(text_signature = "($self, key, min, max)")]
+    #[pyo3(signature = (key, min, max, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, min, max, *, prefer = \"replica\")")]
     fn zrangebyscore_withscores(
         &self,
-        key: RedisValuePy,
-        min: RedisValuePy,
-        max: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::min")] min: RedisValuePy,
+        #[pyo3(from_py_with = "arg::max")] max: RedisValuePy,
+        prefer: Prefer,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zrangebyscore_withscores(key, min, max);
-        self.query_cmd(redis_cmd)
+        self.read_cmd_as(redis_cmd, ResponseShape::Scored, prefer)
     }
 
     /// Return a range of members in a sorted set, by score with limit.
-    #[pyo3(text_signature = "($self, key, min, max, offset, count)")]
+    #[pyo3(signature = (key, min, max, offset, count, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, min, max, offset, count, *, prefer = \"replica\")")]
     fn zrangebyscore_limit(
         &self,
-        key: RedisValuePy,
-        min: RedisValuePy,
-        max: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::min")] min: RedisValuePy,
+        #[pyo3(from_py_with = "arg::max")] max: RedisValuePy,
         offset: isize,
         count: isize,
+        prefer: Prefer,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zrangebyscore_limit(key, min, max, offset, count);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Return a range of members in a sorted set, by score with limit with
     /// scores.
-    #[pyo3(text_signature = "($self, key, min, max, offset, count)")]
+    #[pyo3(signature = (key, min, max, offset, count, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, min, max, offset, count, *, prefer = \"replica\")")]
     fn zrangebyscore_limit_withscores(
         &self,
-        key: RedisValuePy,
-        min: RedisValuePy,
-        max: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::min")] min: RedisValuePy,
+        #[pyo3(from_py_with = "arg::max")] max: RedisValuePy,
         offset: isize,
         count: isize,
+        prefer: Prefer,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zrangebyscore_limit_withscores(key, min, max, offset, count);
-        self.query_cmd(redis_cmd)
+        self.read_cmd_as(redis_cmd, ResponseShape::Scored, prefer)
     }
 
     /// Determine the index of a member in a sorted set.
-    #[pyo3(text_signature = "($self, key, member)")]
-    fn zrank(&self, key: RedisValuePy, member: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, member, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, member, *, prefer = \"replica\")")]
+    fn zrank(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::member")] member: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zrank(key, member);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Remove one or more members from a sorted set.
     #[pyo3(text_signature = "($self, key, members)")]
-    fn zrem(&self, key: RedisValuePy, members: RedisValuePy) -> PyResult<PyObject> {
+    fn zrem(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::members")] members: RedisValuePy,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zrem(key, members);
         self.exec_cmd(redis_cmd)
     }
@@ -978,9 +1834,9 @@ impl ConnectionPool {
     #[pyo3(text_signature = "($self, key, min, max)")]
     fn zrembylex(
         &self,
-        key: RedisValuePy,
-        min: RedisValuePy,
-        max: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::min")] min: RedisValuePy,
+        #[pyo3(from_py_with = "arg::max")] max: RedisValuePy,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zrembylex(key, min, max);
         self.exec_cmd(redis_cmd)
@@ -988,7 +1844,12 @@ impl ConnectionPool {
 
     /// Remove all members in a sorted set within the given indexes.
     #[pyo3(text_signature = "($self, key, start, stop)")]
-    fn zremrangebyrank(&self, key: RedisValuePy, start: isize, stop: isize) -> PyResult<PyObject> {
+    fn zremrangebyrank(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        start: isize,
+        stop: isize,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zremrangebyrank(key, start, stop);
         self.exec_cmd(redis_cmd)
     }
@@ -997,9 +1858,9 @@ impl ConnectionPool {
     #[pyo3(text_signature = "($self, key, min, max)")]
     fn zrembyscore(
         &self,
-        key: RedisValuePy,
-        min: RedisValuePy,
-        max: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::min")] min: RedisValuePy,
+        #[pyo3(from_py_with = "arg::max")] max: RedisValuePy,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zrembyscore(key, min, max);
         self.exec_cmd(redis_cmd)
@@ -1007,97 +1868,130 @@ impl ConnectionPool {
 
     /// Return a range of members in a sorted set, by index, with scores ordered
     /// from high to low.
-    #[pyo3(text_signature = "($self, key, start, stop)")]
-    fn zrevrange(&self, key: RedisValuePy, start: isize, stop: isize) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, start, stop, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, start, stop, *, prefer = \"replica\")")]
+    fn zrevrange(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        start: isize,
+        stop: isize,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zrevrange(key, start, stop);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Return a range of members in a sorted set, by index, with scores ordered
     /// from high to low.
-    #[pyo3(text_signature = "($self, key, start, stop)")]
+    #[pyo3(signature = (key, start, stop, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, start, stop, *, prefer = \"replica\")")]
     fn zrevrange_withscores(
         &self,
-        key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
         start: isize,
         stop: isize,
+        prefer: Prefer,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zrevrange_withscores(key, start, stop);
-        self.query_cmd(redis_cmd)
+        self.read_cmd_as(redis_cmd, ResponseShape::Scored, prefer)
     }
 
     /// Return a range of members in a sorted set, by score.
-    #[pyo3(text_signature = "($self, key, max, min)")]
+    #[pyo3(signature = (key, max, min, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, max, min, *, prefer = \"replica\")")]
     fn zrevrangebyscore(
         &self,
-        key: RedisValuePy,
-        max: RedisValuePy,
-        min: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::max")] max: RedisValuePy,
+        #[pyo3(from_py_with = "arg::min")] min: RedisValuePy,
+        prefer: Prefer,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zrevrangebyscore(key, max, min);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Return a range of members in a sorted set, by score with scores.
-    #[pyo3(text_signature = "($self, key, max, min)")]
+    #[pyo3(signature = (key, max, min, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, max, min, *, prefer = \"replica\")")]
     fn zrevrangebyscore_withscores(
         &self,
-        key: RedisValuePy,
-        max: RedisValuePy,
-        min: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::max")] max: RedisValuePy,
+        #[pyo3(from_py_with = "arg::min")] min: RedisValuePy,
+        prefer: Prefer,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zrevrangebyscore_withscores(key, max, min);
-        self.query_cmd(redis_cmd)
+        self.read_cmd_as(redis_cmd, ResponseShape::Scored, prefer)
     }
 
     /// Return a range of members in a sorted set, by score with limit.
-    #[pyo3(text_signature = "($self, key, max, min, offset, count)")]
+    #[pyo3(signature = (key, max, min, offset, count, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, max, min, offset, count, *, prefer = \"replica\")")]
     fn zrevrangebyscore_limit(
         &self,
-        key: RedisValuePy,
-        max: RedisValuePy,
-        min: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::max")] max: RedisValuePy,
+        #[pyo3(from_py_with = "arg::min")] min: RedisValuePy,
         offset: isize,
         count: isize,
+        prefer: Prefer,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zrevrangebyscore_limit(key, max, min, offset, count);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Return a range of members in a sorted set, by score with limit with
     /// scores.
-    #[pyo3(text_signature = "($self, key, max, min, offset, count)")]
+    #[pyo3(signature = (key, max, min, offset, count, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, max, min, offset, count, *, prefer = \"replica\")")]
     fn zrevrangebyscore_limit_withscores(
         &self,
-        key: RedisValuePy,
-        max: RedisValuePy,
-        min: RedisValuePy,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::max")] max: RedisValuePy,
+        #[pyo3(from_py_with = "arg::min")] min: RedisValuePy,
         offset: isize,
         count: isize,
+        prefer: Prefer,
     ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zrevrangebyscore_limit_withscores(key, max, min, offset, count);
-        self.query_cmd(redis_cmd)
+        self.read_cmd_as(redis_cmd, ResponseShape::Scored, prefer)
     }
 
     /// Determine the index of a member in a sorted set, with scores ordered
     /// from high to low.
-    #[pyo3(text_signature = "($self, key, member)")]
-    fn zrevrank(&self, key: RedisValuePy, member: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, member, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, member, *, prefer = \"replica\")")]
+    fn zrevrank(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::member")] member: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zrevrank(key, member);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Get the score associated with the given member in a sorted set.
-    #[pyo3(text_signature = "($self, key, member)")]
-    fn zscore(&self, key: RedisValuePy, member: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, member, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, member, *, prefer = \"replica\")")]
+    fn zscore(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::member")] member: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zscore(key, member);
-        self.query_cmd(redis_cmd)
+        self.read_cmd_as(redis_cmd, ResponseShape::Float, prefer)
     }
 
     /// Unions multiple sorted sets and store the resulting sorted set in a new
     /// key using SUM as aggregation function.
     #[pyo3(text_signature = "($self, dstkey, keys)")]
-    fn zunionstore(&self, dstkey: RedisValuePy, keys: Vec<RedisValuePy>) -> PyResult<PyObject> {
+    fn zunionstore(
+        &self,
+        #[pyo3(from_py_with = "arg::dstkey")] dstkey: RedisValuePy,
+        #[pyo3(from_py_with = "arg::keys")] keys: Vec<RedisValuePy>,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zunionstore(dstkey, &keys);
         self.exec_cmd(redis_cmd)
     }
@@ -1105,7 +1999,11 @@ impl ConnectionPool {
     /// Unions multiple sorted sets and store the resulting sorted set in a new
     /// key using MIN as aggregation function.
     #[pyo3(text_signature = "($self, dstkey, keys)")]
-    fn zunionstore_min(&self, dstkey: RedisValuePy, keys: Vec<RedisValuePy>) -> PyResult<PyObject> {
+    fn zunionstore_min(
+        &self,
+        #[pyo3(from_py_with = "arg::dstkey")] dstkey: RedisValuePy,
+        #[pyo3(from_py_with = "arg::keys")] keys: Vec<RedisValuePy>,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zunionstore_min(dstkey, &keys);
         self.exec_cmd(redis_cmd)
     }
@@ -1113,257 +2011,236 @@ impl ConnectionPool {
     /// Unions multiple sorted sets and store the resulting sorted set in a new
     /// key using MAX as aggregation function.
     #[pyo3(text_signature = "($self, dstkey, keys)")]
-    fn zunionstore_max(&self, dstkey: RedisValuePy, keys: Vec<RedisValuePy>) -> PyResult<PyObject> {
+    fn zunionstore_max(
+        &self,
+        #[pyo3(from_py_with = "arg::dstkey")] dstkey: RedisValuePy,
+        #[pyo3(from_py_with = "arg::keys")] keys: Vec<RedisValuePy>,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::zunionstore_max(dstkey, &keys);
         self.exec_cmd(redis_cmd)
     }
 
     /// Adds the specified elements to the specified HyperLogLog.
     #[pyo3(text_signature = "($self, key, element)")]
-    fn pfadd(&self, key: RedisValuePy, element: RedisValuePy) -> PyResult<PyObject> {
+    fn pfadd(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        #[pyo3(from_py_with = "arg::element")] element: RedisValuePy,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::pfadd(key, element);
         self.exec_cmd(redis_cmd)
     }
 
     /// Return the approximated cardinality of the set(s) observed by the
     /// HyperLogLog at key(s).
-    #[pyo3(text_signature = "($self, key)")]
-    fn pfcount(&self, key: RedisValuePy) -> PyResult<PyObject> {
+    #[pyo3(signature = (key, *, prefer = Prefer::Replica))]
+    #[pyo3(text_signature = "($self, key, *, prefer = \"replica\")")]
+    fn pfcount(
+        &self,
+        #[pyo3(from_py_with = "arg::key")] key: RedisValuePy,
+        prefer: Prefer,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::pfcount(key);
-        self.query_cmd(redis_cmd)
+        self.read_cmd(redis_cmd, prefer)
     }
 
     /// Merge N different HyperLogLogs into a single one.
     #[pyo3(text_signature = "($self, dstkey, srckeys)")]
-    fn pfmerge(&self, dstkey: RedisValuePy, srckeys: RedisValuePy) -> PyResult<PyObject> {
+    fn pfmerge(
+        &self,
+        #[pyo3(from_py_with = "arg::dstkey")] dstkey: RedisValuePy,
+        #[pyo3(from_py_with = "arg::srckeys")] srckeys: RedisValuePy,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::pfmerge(dstkey, srckeys);
         self.exec_cmd(redis_cmd)
     }
 
     /// Posts a message to the given channel.
     #[pyo3(text_signature = "($self, channel, message)")]
-    fn publish(&self, channel: RedisValuePy, message: RedisValuePy) -> PyResult<PyObject> {
+    fn publish(
+        &self,
+        #[pyo3(from_py_with = "arg::channel")] channel: RedisValuePy,
+        #[pyo3(from_py_with = "arg::message")] message: RedisValuePy,
+    ) -> PyResult<PyObject> {
         let redis_cmd = Cmd::publish(channel, message);
         self.exec_cmd(redis_cmd)
     }
 
-    #[pyo3(text_signature = "($self)")]
-    fn pubsub(&mut self) -> PyResult<PyObject> {
-        let other_conns = self.pubsub_pool.clone();
-        let mut conns = self.pubsub_pool.lock().unwrap();
-        match conns.pop() {
-            Some(conn) => {
-                let ctx = PubSubContext {
-                    connection: Arc::new(TokioMutex::new(Some(conn))),
-                    pool: other_conns,
-                };
-
-                Ok(Python::with_gil(|py| ctx.into_py(py)))
-            }
-            None => Err(PoolEmpty::new_err("PubSub pool is empty")),
-        }
+    /// Posts a message to the given shard channel.
+    #[pyo3(text_signature = "($self, channel, message)")]
+    fn spublish(
+        &self,
+        #[pyo3(from_py_with = "arg::channel")] channel: RedisValuePy,
+        #[pyo3(from_py_with = "arg::message")] message: RedisValuePy,
+    ) -> PyResult<PyObject> {
+        let mut redis_cmd = redis::cmd("SPUBLISH");
+        redis_cmd.arg(channel).arg(message);
+        self.exec_cmd(redis_cmd)
     }
-}
 
-#[pyclass(module = "zangy")]
-struct PubSubContext {
-    connection: Arc<TokioMutex<Option<PubSub>>>,
-    pool: Arc<Mutex<Vec<PubSub>>>,
-}
+    /// Take a connection from the pubsub pool, raising `PoolEmpty` if none is
+    /// idle. Idle connections that are being health checked are waited for.
+    /// The connection is returned when the context manager exits. If it
+    /// is lost, it is re-established up to `max_reconnect_attempts` times and
+    /// all subscriptions are restored. If `max_pending` is set, at most that
+    /// many messages are buffered and `overflow` decides what happens to
+    /// further ones: `"drop_oldest"`, `"drop_newest"` or `"disconnect"`.
+    #[pyo3(signature = (
+        reconnect_event = false,
+        max_reconnect_attempts = 10,
+        max_pending = None,
+        overflow = "drop_oldest",
+    ))]
+    #[pyo3(
+        text_signature = "($self, reconnect_event = False, max_reconnect_attempts = 10, max_pending = None, overflow = \"drop_oldest\")"
+    )]
+    fn pubsub(
+        &self,
+        reconnect_event: bool,
+        max_reconnect_attempts: u32,
+        max_pending: Option<usize>,
+        overflow: &str,
+    ) -> PyResult<PyObject> {
+        self.lifecycle.ensure_open()?;
+        let options = pubsub::Options {
+            reconnect_event,
+            max_reconnect_attempts,
+            max_pending,
+            overflow: Overflow::from_name(overflow)?,
+            channel_prefix: self.channel_prefix(),
+        };
+
+        let ctx = self.pubsub_context(self.take_pubsub()?, &options);
+        Ok(Python::with_gil(|py| ctx.into_py(py)))
+    }
+
+    /// Like `pubsub`, but waits for a connection if none is idle. A new one is
+    /// opened instead if the pool holds fewer than `max_pubsub_size`. Raises
+    /// `PoolEmpty` if none became available within `timeout` seconds.
+    #[pyo3(signature = (
+        timeout = None,
+        reconnect_event = false,
+        max_reconnect_attempts = 10,
+        max_pending = None,
+        overflow = "drop_oldest",
+    ))]
+    #[pyo3(
+        text_signature = "($self, timeout = None, reconnect_event = False, max_reconnect_attempts = 10, max_pending = None, overflow = \"drop_oldest\")"
+    )]
+    fn acquire_pubsub(
+        &self,
+        timeout: Option<f64>,
+        reconnect_event: bool,
+        max_reconnect_attempts: u32,
+        max_pending: Option<usize>,
+        overflow: &str,
+    ) -> PyResult<PyObject> {
+        self.lifecycle.ensure_open()?;
+        let options = pubsub::Options {
+            reconnect_event,
+            max_reconnect_attempts,
+            max_pending,
+            overflow: Overflow::from_name(overflow)?,
+            channel_prefix: self.channel_prefix(),
+        };
+        let timeout = timeout
+            .map(Duration::try_from_secs_f64)
+            .transpose()
+            .map_err(|e| ArgumentError::new_err(format!("invalid timeout: {e}")))?;
 
-#[pymethods]
-impl PubSubContext {
-    /// Subscribes to a new channel.
-    #[pyo3(text_signature = "($self, channel)")]
-    fn subscribe(&self, channel: RedisValuePy) -> PyResult<PyObject> {
         let (fut, res_fut) = create_future()?;
-        let conn = self.connection.clone();
+        let pool = self.pubsub_pool.clone();
+        let lifecycle = self.lifecycle.clone();
+        let metrics = self.metrics.clone();
+        let info = self.client.get_connection_info().clone();
 
         RUNTIME.spawn(async move {
-            match *conn.lock().await {
-                Some(ref mut v) => {
-                    if let Err(e) = v.subscribe(channel).await {
-                        let desc = e.to_string();
-                        if let Err(e2) = set_fut_exc(&fut, RedisError::new_err(desc)) {
-                            eprintln!("{e2:?}");
-                        }
-                    } else {
-                        let _res = set_fut_result_none(&fut);
-                    };
-                }
-                None => {
-                    if let Err(e) = set_fut_exc(
-                        &fut,
-                        PubSubClosed::new_err("context manager has been exited"),
-                    ) {
-                        eprintln!("{e:?}");
-                    }
-                }
-            }
-        });
-
-        Ok(res_fut)
-    }
-
-    /// Subscribes to a new channel with a pattern.
-    #[pyo3(text_signature = "($self, pchannel)")]
-    fn psubscribe(&self, pchannel: RedisValuePy) -> PyResult<PyObject> {
-        let (fut, res_fut) = create_future()?;
-        let conn = self.connection.clone();
+            let conn = match timeout {
+                Some(timeout) => tokio::time::timeout(timeout, pool.take(&info))
+                    .await
+                    .unwrap_or_else(|_| {
+                        Err(PoolEmpty::new_err(
+                            "timed out waiting for a pubsub connection",
+                        ))
+                    }),
+                None => pool.take(&info).await,
+            };
 
-        RUNTIME.spawn(async move {
-            match *conn.lock().await {
-                Some(ref mut v) => {
-                    if let Err(e) = v.psubscribe(pchannel).await {
-                        let desc = e.to_string();
-                        if let Err(e2) = set_fut_exc(&fut, RedisError::new_err(desc)) {
-                            eprintln!("{e2:?}");
-                        }
-                    } else {
-                        let _res = set_fut_result_none(&fut);
-                    };
-                }
-                None => {
-                    if let Err(e) = set_fut_exc(
-                        &fut,
-                        PubSubClosed::new_err("context manager has been exited"),
-                    ) {
-                        eprintln!("{e:?}");
-                    }
+            let res = match conn {
+                Ok(conn) => {
+                    let ctx = PubSubContext::new(conn, pool, lifecycle, metrics, info, &options);
+                    Python::with_gil(|py| set_fut_result_with_gil(&fut, ctx.into_py(py), py))
                 }
+                Err(e) => set_fut_exc(&fut, e),
+            };
+            if let Err(e) = res {
+                eprintln!("{e:?}");
             }
         });
 
         Ok(res_fut)
     }
 
-    /// Unsubscribes from a channel.
-    #[pyo3(text_signature = "($self, channel)")]
-    fn unsubscribe(&self, channel: RedisValuePy) -> PyResult<PyObject> {
-        let (fut, res_fut) = create_future()?;
-        let conn = self.connection.clone();
-
-        RUNTIME.spawn(async move {
-            match *conn.lock().await {
-                Some(ref mut v) => {
-                    if let Err(e) = v.unsubscribe(channel).await {
-                        let desc = e.to_string();
-                        if let Err(e2) = set_fut_exc(&fut, RedisError::new_err(desc)) {
-                            eprintln!("{e2:?}");
-                        }
-                    } else {
-                        let _res = set_fut_result_none(&fut);
-                    };
-                }
-                None => {
-                    if let Err(e) = set_fut_exc(
-                        &fut,
-                        PubSubClosed::new_err("context manager has been exited"),
-                    ) {
-                        eprintln!("{e:?}");
-                    }
-                }
-            }
+    /// Listen for keyspace notifications on keys matching `pattern` in
+    /// database `db`, or in all databases if it is `None`. Unless `events` is
+    /// `None`, `notify-keyspace-events` is set to it first. Iterating the
+    /// returned context manager yields `KeyspaceEvent`s.
+    #[pyo3(signature = (pattern = "*", events = Some("KEA"), db = Some(0)))]
+    #[pyo3(text_signature = "($self, pattern = \"*\", events = \"KEA\", db = 0)")]
+    fn keyspace_events(
+        &self,
+        pattern: &str,
+        events: Option<&str>,
+        db: Option<u32>,
+    ) -> PyResult<PyObject> {
+        self.lifecycle.ensure_open()?;
+        let conn = self.take_pubsub()?;
+
+        let mut ctx = self.pubsub_context(
+            conn,
+            &pubsub::Options {
+                reconnect_event: false,
+                max_reconnect_attempts: 10,
+                max_pending: None,
+                overflow: Overflow::DropOldest,
+                channel_prefix: Vec::new(),
+            },
+        );
+
+        let (listener, channel) = KeyspaceListener::new(pattern, events, db, &self.key_prefix);
+        let config = events.map(|events| {
+            let mut cmd = redis::cmd("CONFIG");
+            cmd.arg("SET").arg("notify-keyspace-events").arg(events);
+            cmd
         });
+        let mut conn = self.connection()?.connection();
 
-        Ok(res_fut)
-    }
-
-    /// Unsubscribes from a channel with a pattern.
-    #[pyo3(text_signature = "($self, pchannel)")]
-    fn punsubscribe(&self, pchannel: RedisValuePy) -> PyResult<PyObject> {
-        let (fut, res_fut) = create_future()?;
-        let conn = self.connection.clone();
-
-        RUNTIME.spawn(async move {
-            match *conn.lock().await {
-                Some(ref mut v) => {
-                    if let Err(e) = v.psubscribe(pchannel).await {
-                        let desc = e.to_string();
-                        if let Err(e2) = set_fut_exc(&fut, RedisError::new_err(desc)) {
-                            eprintln!("{e2:?}");
-                        }
-                    } else {
-                        let _res = set_fut_result_none(&fut);
-                    };
-                }
-                None => {
-                    if let Err(e) = set_fut_exc(
-                        &fut,
-                        PubSubClosed::new_err("context manager has been exited"),
-                    ) {
-                        eprintln!("{e:?}");
-                    }
-                }
+        ctx.listen_keyspace(listener, channel, async move {
+            match config {
+                Some(cmd) => cmd.query_async(&mut conn).await,
+                None => Ok(()),
             }
         });
 
-        Ok(res_fut)
+        Ok(Python::with_gil(|py| ctx.into_py(py)))
     }
 
-    // Impossible to return Self in the ContextProtocol
-    // so we do it here
-    // https://github.com/PyO3/pyo3/issues/1205#issuecomment-778529199
-    fn __enter__(slf: Py<Self>) -> Py<Self> {
-        slf
-    }
-
-    #[allow(clippy::needless_pass_by_value)]
-    fn __exit__(&mut self, _ty: PyObject, _value: PyObject, _traceback: PyObject) {
-        let conn = self.connection.clone();
-        let pool = self.pool.clone();
-
-        RUNTIME.spawn(async move {
-            let conn = conn.lock().await.take().unwrap();
-            pool.lock().unwrap().push(conn);
-        });
-    }
-
-    fn __aiter__(slf: PyRef<Self>) -> PyRef<Self> {
-        slf
-    }
-
-    fn __anext__(&self) -> PyResult<Option<Py<PyAny>>> {
-        let (fut, res_fut) = create_future()?;
-        let conn = self.connection.clone();
-
-        RUNTIME.spawn(async move {
-            match *conn.lock().await {
-                Some(ref mut c) => match c.on_message().next().await {
-                    Some(m) => {
-                        // We need (channel, msg)
-                        let val: Value = m.get_payload().unwrap();
-                        let channel = m.get_channel_name();
-
-                        Python::with_gil(|py| {
-                            let channel_py: Py<PyAny> = channel.into_py(py);
-
-                            if let Err(e) = set_fut_result_with_gil(
-                                &fut,
-                                (channel_py, re_to_object(&val, py)).into_py(py),
-                                py,
-                            ) {
-                                eprintln!("{e:?}");
-                            };
-                        });
-                    }
-                    None => {
-                        if let Err(e2) = set_fut_result_none(&fut) {
-                            eprintln!("{e2:?}");
-                        }
-                    }
-                },
-                None => {
-                    if let Err(e) = set_fut_exc(
-                        &fut,
-                        PubSubClosed::new_err("context manager has been exited"),
-                    ) {
-                        eprintln!("{e:?}");
-                    }
-                }
-            }
-        });
-
-        Ok(Some(res_fut))
+    /// A lock on `name` that other clients using it respect, expiring after
+    /// `timeout` seconds unless it is released or extended first. Use it as
+    /// an async context manager, which waits up to `blocking_timeout` seconds
+    /// for the lock, checking every `sleep` seconds. See `Lock`.
+    #[pyo3(signature = (name, timeout, blocking_timeout = None, sleep = 0.1, auto_renew = false))]
+    #[pyo3(
+        text_signature = "($self, name, timeout, blocking_timeout = None, sleep = 0.1, auto_renew = False)"
+    )]
+    fn lock(
+        slf: Py<Self>,
+        name: String,
+        timeout: f64,
+        blocking_timeout: Option<f64>,
+        sleep: f64,
+        auto_renew: bool,
+    ) -> PyResult<Lock> {
+        Lock::new(slf, name, timeout, blocking_timeout, sleep, auto_renew)
     }
 }
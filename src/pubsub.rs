@@ -0,0 +1,853 @@
+use std::{
+    cmp::{max, min},
+    collections::{HashMap, HashSet, VecDeque},
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use pyo3::{
+    prelude::{pyclass, pymethods, PyObject, PyResult, Python},
+    types::{PyAnyMethods, PyIterator, PyTuple},
+    Bound, IntoPy, Py, PyAny, PyErr, PyRef,
+};
+use redis::{ConnectionInfo, RedisError, RedisResult, ToRedisArgs, Value};
+use tokio::{
+    io::{ReadHalf, WriteHalf},
+    sync::{oneshot, Mutex as TokioMutex},
+    task::JoinHandle,
+};
+
+use crate::{
+    asyncio::{create_future, set_fut_exc, set_fut_result_none, set_fut_result_with_gil},
+    buffer::{EventQueue, Overflow},
+    conversion::{extract_arg, re_to_object, RedisValuePy},
+    dispatch,
+    exceptions::{redis_error, ArgumentError, PubSubClosed, PubSubConnectionLost},
+    keyspace::KeyspaceListener,
+    lifecycle::Lifecycle,
+    metrics::Metrics,
+    pubsub_pool::PubSubPool,
+    resp::{self, write_cmd, FrameReader, Stream},
+    runtime::RUNTIME,
+};
+
+/// A message received on a subscribed channel.
+#[pyclass(module = "zangy", frozen, get_all)]
+pub struct Message {
+    /// Either `"message"`, `"pmessage"` for messages received through a
+    /// pattern subscription, `"smessage"` for messages received on a shard
+    /// channel or `"reconnected"` if the connection was re-established and
+    /// messages may have been missed.
+    kind: &'static str,
+    channel: String,
+    /// The pattern that matched the channel, if any.
+    pattern: Option<String>,
+    payload: PyObject,
+}
+
+impl Message {
+    pub fn from_raw(msg: &RawMessage, py: Python) -> Self {
+        Self {
+            kind: msg.kind,
+            channel: String::from_utf8_lossy(&msg.channel).into_owned(),
+            pattern: msg
+                .pattern
+                .as_ref()
+                .map(|p| String::from_utf8_lossy(p).into_owned()),
+            payload: re_to_object(&msg.payload, py),
+        }
+    }
+
+    fn reconnected(py: Python) -> Self {
+        Self {
+            kind: "reconnected",
+            channel: String::new(),
+            pattern: None,
+            payload: py.None(),
+        }
+    }
+}
+
+#[pymethods]
+impl Message {
+    // Allows unpacking a message into (channel, payload) like the tuples that
+    // were yielded previously
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        let pair: PyObject = (self.channel.clone(), self.payload.clone_ref(py)).into_py(py);
+        pair.into_bound(py).iter()
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        let payload = self.payload.bind(py).repr()?;
+        Ok(format!(
+            "Message(kind={:?}, channel={:?}, pattern={:?}, payload={payload})",
+            self.kind, self.channel, self.pattern
+        ))
+    }
+}
+
+/// A message as read from the connection, converted to a [`Message`] once the
+/// GIL is held.
+pub struct RawMessage {
+    pub kind: &'static str,
+    pub channel: Vec<u8>,
+    pub pattern: Option<Vec<u8>>,
+    pub payload: Value,
+}
+
+pub enum Event {
+    Message(RawMessage),
+    Reconnected,
+    Lost(RedisError),
+    /// The buffer was full and the connection has been closed.
+    Overflow,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Subscription {
+    Subscribe,
+    PSubscribe,
+    SSubscribe,
+    Unsubscribe,
+    PUnsubscribe,
+    SUnsubscribe,
+}
+
+impl Subscription {
+    fn command(self) -> &'static str {
+        match self {
+            Self::Subscribe => "SUBSCRIBE",
+            Self::PSubscribe => "PSUBSCRIBE",
+            Self::SSubscribe => "SSUBSCRIBE",
+            Self::Unsubscribe => "UNSUBSCRIBE",
+            Self::PUnsubscribe => "PUNSUBSCRIBE",
+            Self::SUnsubscribe => "SUNSUBSCRIBE",
+        }
+    }
+
+    /// The kind of the confirmation the server sends for each channel.
+    fn reply(self) -> &'static [u8] {
+        match self {
+            Self::Subscribe => b"subscribe",
+            Self::PSubscribe => b"psubscribe",
+            Self::SSubscribe => b"ssubscribe",
+            Self::Unsubscribe => b"unsubscribe",
+            Self::PUnsubscribe => b"punsubscribe",
+            Self::SUnsubscribe => b"sunsubscribe",
+        }
+    }
+}
+
+/// The channels a connection is subscribed to, so they can be restored after
+/// a reconnect.
+#[derive(Default)]
+struct Subscriptions {
+    channels: HashSet<Vec<u8>>,
+    patterns: HashSet<Vec<u8>>,
+    shard_channels: HashSet<Vec<u8>>,
+}
+
+impl Subscriptions {
+    /// Record a (un)subscription and return the number of confirmations the
+    /// server will send for it.
+    fn update(&mut self, action: Subscription, channels: &[Vec<u8>]) -> usize {
+        let set = match action {
+            Subscription::Subscribe | Subscription::Unsubscribe => &mut self.channels,
+            Subscription::PSubscribe | Subscription::PUnsubscribe => &mut self.patterns,
+            Subscription::SSubscribe | Subscription::SUnsubscribe => &mut self.shard_channels,
+        };
+
+        match action {
+            Subscription::Subscribe | Subscription::PSubscribe | Subscription::SSubscribe => {
+                set.extend(channels.iter().cloned());
+                channels.len()
+            }
+            _ if channels.is_empty() => {
+                // Unsubscribing from everything is confirmed once per channel,
+                // or once if there were none
+                let count = max(set.len(), 1);
+                set.clear();
+                count
+            }
+            _ => {
+                set.retain(|c| !channels.contains(c));
+                channels.len()
+            }
+        }
+    }
+
+    fn restore(&self) -> Vec<(Subscription, Vec<Vec<u8>>)> {
+        [
+            (Subscription::Subscribe, &self.channels),
+            (Subscription::PSubscribe, &self.patterns),
+            (Subscription::SSubscribe, &self.shard_channels),
+        ]
+        .into_iter()
+        .filter(|(_, set)| !set.is_empty())
+        .map(|(action, set)| (action, set.iter().cloned().collect()))
+        .collect()
+    }
+}
+
+/// A command waiting for its confirmations.
+struct Pending {
+    reply: &'static [u8],
+    remaining: usize,
+    /// The callers waiting for the confirmations. After a reconnect, the
+    /// callers of all unconfirmed commands wait for the last command that
+    /// restores the subscriptions.
+    done: Vec<oneshot::Sender<RedisResult<()>>>,
+}
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+type Reader = FrameReader<ReadHalf<Stream>>;
+
+/// State shared between a context and the task reading from its connection.
+struct Connection {
+    /// `None` once the context manager has been exited.
+    writer: TokioMutex<Option<WriteHalf<Stream>>>,
+    pending: Mutex<VecDeque<Pending>>,
+    subscriptions: Mutex<Subscriptions>,
+    info: ConnectionInfo,
+    metrics: Arc<Metrics>,
+    /// Prepended to all channels and patterns subscribed to.
+    channel_prefix: Vec<u8>,
+}
+
+impl Connection {
+    async fn update(&self, action: Subscription, channels: Vec<Vec<u8>>) -> PyResult<()> {
+        let done = {
+            let mut writer = self.writer.lock().await;
+            let Some(writer) = writer.as_mut() else {
+                return Err(closed());
+            };
+
+            let remaining = self.subscriptions.lock().unwrap().update(action, &channels);
+            let (tx, rx) = oneshot::channel();
+            self.pending.lock().unwrap().push_back(Pending {
+                reply: action.reply(),
+                remaining,
+                done: vec![tx],
+            });
+
+            let mut cmd = redis::cmd(action.command());
+            cmd.arg(channels);
+            write_cmd(writer, &cmd).await.map_err(|e| redis_error(&e))?;
+
+            rx
+        };
+
+        match done.await {
+            Ok(res) => res.map_err(|e| redis_error(&e)),
+            Err(_) => Err(PubSubConnectionLost::new_err(
+                "pubsub connection lost before the server replied",
+            )),
+        }
+    }
+
+    /// Handle a frame, returning `false` if the connection should be closed.
+    fn dispatch(&self, frame: Value, events: &EventQueue) -> bool {
+        let Value::Bulk(items) = frame else {
+            return true;
+        };
+        let kind = match items.first() {
+            Some(Value::Data(kind)) if kind == b"message" => "message",
+            Some(Value::Data(kind)) if kind == b"pmessage" => "pmessage",
+            Some(Value::Data(kind)) if kind == b"smessage" => "smessage",
+            Some(Value::Data(kind)) => {
+                self.ack(kind);
+                return true;
+            }
+            _ => return true,
+        };
+
+        let mut items = items.into_iter().skip(1);
+        let pattern = match kind {
+            "pmessage" => match items.next() {
+                Some(Value::Data(pattern)) => Some(pattern),
+                _ => return true,
+            },
+            _ => None,
+        };
+        let (Some(Value::Data(channel)), Some(payload)) = (items.next(), items.next()) else {
+            return true;
+        };
+        let strip = |name: Vec<u8>| match name.strip_prefix(self.channel_prefix.as_slice()) {
+            Some(stripped) => stripped.to_vec(),
+            None => name,
+        };
+        let (channel, pattern) = (strip(channel), pattern.map(strip));
+
+        events.push(Event::Message(RawMessage {
+            kind,
+            channel,
+            pattern,
+            payload,
+        }))
+    }
+
+    fn ack(&self, reply: &[u8]) {
+        let mut pending = self.pending.lock().unwrap();
+        let Some(front) = pending.front_mut() else {
+            return;
+        };
+        if front.reply != reply {
+            return;
+        }
+
+        front.remaining = front.remaining.saturating_sub(1);
+        if front.remaining == 0 {
+            for done in pending.pop_front().into_iter().flat_map(|p| p.done) {
+                let _res = done.send(Ok(()));
+            }
+        }
+    }
+
+    fn fail(&self, e: RedisError) {
+        let Some(front) = self.pending.lock().unwrap().pop_front() else {
+            return;
+        };
+
+        let mut done = front.done.into_iter();
+        let first = done.next();
+        for done in done {
+            let e = RedisError::from((e.kind(), "server error", e.to_string()));
+            let _res = done.send(Err(e));
+        }
+        if let Some(first) = first {
+            let _res = first.send(Err(e));
+        }
+    }
+
+    /// Open a new connection and restore all subscriptions on it.
+    async fn connect(&self) -> RedisResult<Reader> {
+        let stream = resp::connect(&self.info).await?;
+        let (reader, mut writer) = FrameReader::split(stream);
+
+        let mut current = self.writer.lock().await;
+        let restore = self.subscriptions.lock().unwrap().restore();
+
+        {
+            // Commands sent on the old connection will never be confirmed.
+            // Their changes are already part of the subscriptions, so their
+            // callers wait for these to be restored instead.
+            let mut pending = self.pending.lock().unwrap();
+            let mut waiting: Vec<_> = pending.drain(..).flat_map(|p| p.done).collect();
+            for (action, channels) in restore.iter().rev() {
+                pending.push_front(Pending {
+                    reply: action.reply(),
+                    remaining: channels.len(),
+                    done: std::mem::take(&mut waiting),
+                });
+            }
+            for done in waiting {
+                let _res = done.send(Ok(()));
+            }
+        }
+
+        for (action, channels) in restore {
+            let mut cmd = redis::cmd(action.command());
+            cmd.arg(channels);
+            write_cmd(&mut writer, &cmd).await?;
+        }
+
+        if current.is_some() {
+            *current = Some(writer);
+        }
+
+        Ok(reader)
+    }
+
+    /// Replace the connection with a new one, retrying with exponential backoff
+    /// up to `max_attempts` times.
+    async fn reconnect(&self, max_attempts: u32) -> RedisResult<Reader> {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempt = 1;
+
+        loop {
+            match self.connect().await {
+                Ok(reader) => {
+                    self.metrics.reconnected();
+                    return Ok(reader);
+                }
+                Err(e) if attempt >= max_attempts => return Err(e),
+                Err(_) => {
+                    tokio::time::sleep(backoff).await;
+                    backoff = min(backoff * 2, MAX_BACKOFF);
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Read frames until stopped or the connection is lost for good. Returns
+    /// the read half of the connection if it is still usable.
+    async fn run(
+        self: Arc<Self>,
+        mut reader: Reader,
+        events: &EventQueue,
+        mut stop: oneshot::Receiver<()>,
+        reconnect_event: bool,
+        max_reconnect_attempts: u32,
+    ) -> Option<Reader> {
+        loop {
+            let frame = tokio::select! {
+                _ = &mut stop => return Some(reader),
+                frame = reader.read_frame() => frame,
+            };
+
+            match frame {
+                Ok(Ok(frame)) => {
+                    if !self.dispatch(frame, events) {
+                        return None;
+                    }
+                }
+                Ok(Err(e)) => self.fail(e),
+                Err(_) => {
+                    let res = tokio::select! {
+                        _ = &mut stop => return None,
+                        res = self.reconnect(max_reconnect_attempts) => res,
+                    };
+
+                    match res {
+                        Ok(new_reader) => {
+                            reader = new_reader;
+                            if reconnect_event {
+                                events.push(Event::Reconnected);
+                            }
+                        }
+                        Err(e) => {
+                            // Fails the callers still waiting for confirmations
+                            self.pending.lock().unwrap().clear();
+                            events.push(Event::Lost(e));
+                            return None;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[pyclass(module = "zangy")]
+pub struct PubSubContext {
+    connection: Arc<Connection>,
+    events: Arc<EventQueue>,
+    stop: Option<oneshot::Sender<()>>,
+    reader: Option<JoinHandle<Option<Reader>>>,
+    pool: Arc<PubSubPool>,
+    lifecycle: Arc<Lifecycle>,
+    /// Set if keyspace notifications are yielded instead of messages.
+    listener: Option<Arc<KeyspaceListener>>,
+    /// Runs before the first message is read.
+    setup: Arc<TokioMutex<Option<JoinHandle<PyResult<()>>>>>,
+}
+
+pub struct Options {
+    /// Whether to yield a `"reconnected"` message after a reconnect.
+    pub reconnect_event: bool,
+    pub max_reconnect_attempts: u32,
+    /// The number of messages to buffer, unbounded if `None`.
+    pub max_pending: Option<usize>,
+    pub overflow: Overflow,
+    /// Prepended to all channels and patterns, and removed from those of
+    /// received messages.
+    pub channel_prefix: Vec<u8>,
+}
+
+impl PubSubContext {
+    /// Wrap a pooled connection. If the connection is lost, it is
+    /// re-established up to `max_reconnect_attempts` times.
+    pub fn new(
+        stream: Stream,
+        pool: Arc<PubSubPool>,
+        lifecycle: Arc<Lifecycle>,
+        metrics: Arc<Metrics>,
+        info: ConnectionInfo,
+        options: &Options,
+    ) -> Self {
+        metrics.checked_out();
+
+        let (reader, writer) = FrameReader::split(stream);
+        let connection = Arc::new(Connection {
+            writer: TokioMutex::new(Some(writer)),
+            pending: Mutex::new(VecDeque::new()),
+            subscriptions: Mutex::new(Subscriptions::default()),
+            info,
+            metrics,
+            channel_prefix: options.channel_prefix.clone(),
+        });
+        let events = Arc::new(EventQueue::new(options.max_pending, options.overflow));
+        let (stop_tx, stop_rx) = oneshot::channel();
+
+        let conn = connection.clone();
+        let queue = events.clone();
+        let (reconnect_event, max_reconnect_attempts) =
+            (options.reconnect_event, options.max_reconnect_attempts);
+        let reader = RUNTIME.spawn(async move {
+            let reader = conn
+                .run(
+                    reader,
+                    &queue,
+                    stop_rx,
+                    reconnect_event,
+                    max_reconnect_attempts,
+                )
+                .await;
+            queue.close();
+            reader
+        });
+
+        Self {
+            connection,
+            events,
+            stop: Some(stop_tx),
+            reader: Some(reader),
+            pool,
+            lifecycle,
+            listener: None,
+            setup: Arc::new(TokioMutex::new(None)),
+        }
+    }
+
+    /// Yield keyspace notifications parsed by `listener` from now on. `setup`
+    /// runs first, then the channel pattern is subscribed to.
+    pub fn listen_keyspace<F>(&mut self, listener: KeyspaceListener, channel: Vec<u8>, setup: F)
+    where
+        F: Future<Output = RedisResult<()>> + Send + 'static,
+    {
+        let conn = self.connection.clone();
+        let setup = RUNTIME.spawn(async move {
+            setup.await.map_err(|e| redis_error(&e))?;
+            conn.update(Subscription::PSubscribe, vec![channel]).await
+        });
+
+        self.listener = Some(Arc::new(listener));
+        self.setup = Arc::new(TokioMutex::new(Some(setup)));
+    }
+
+    /// Stop reading, unsubscribe from everything and return the connection
+    /// to the pool. If it was lost, a new one is opened in its place, unless
+    /// the pool has been closed.
+    fn release(&mut self) -> Option<JoinHandle<()>> {
+        let (Some(stop), Some(reader)) = (self.stop.take(), self.reader.take()) else {
+            return None;
+        };
+        let conn = self.connection.clone();
+        let pool = self.pool.clone();
+        let lifecycle = self.lifecycle.clone();
+
+        Some(RUNTIME.spawn(async move {
+            let writer = conn.writer.lock().await.take();
+            let _res = stop.send(());
+            let reader = reader.await;
+
+            if lifecycle.is_closed() {
+                return;
+            }
+
+            let stream = match (reader, writer) {
+                // Leave all channels so the connection can be reused
+                (Ok(Some(reader)), Some(writer)) => match resp::reset(reader, writer).await {
+                    Ok(stream) => Some(stream),
+                    Err(_) => resp::connect(&conn.info).await.ok(),
+                },
+                // The connection was lost, replace it
+                _ => resp::connect(&conn.info).await.ok(),
+            };
+
+            match stream {
+                Some(stream) if !lifecycle.is_closed() => pool.put(stream),
+                Some(_) => {}
+                None => pool.discard(),
+            }
+        }))
+    }
+
+    fn update(&self, action: Subscription, channels: &[RedisValuePy]) -> PyResult<PyObject> {
+        let (fut, res_fut) = create_future()?;
+        let conn = self.connection.clone();
+        let channels = channels
+            .to_redis_args()
+            .into_iter()
+            .map(|channel| [conn.channel_prefix.as_slice(), &channel].concat())
+            .collect();
+
+        RUNTIME.spawn(async move {
+            if let Err(e) = conn.update(action, channels).await {
+                if let Err(e2) = set_fut_exc(&fut, e) {
+                    eprintln!("{e2:?}");
+                }
+            } else {
+                let _res = set_fut_result_none(&fut);
+            }
+        });
+
+        Ok(res_fut)
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+#[pymethods]
+impl PubSubContext {
+    /// Subscribes to one or more channels.
+    #[pyo3(signature = (*channels))]
+    #[pyo3(text_signature = "($self, *channels)")]
+    fn subscribe(&self, channels: &Bound<'_, PyTuple>) -> PyResult<PyObject> {
+        self.update(
+            Subscription::Subscribe,
+            &extract_arg::<Vec<RedisValuePy>>(channels, "channels")?,
+        )
+    }
+
+    /// Subscribes to one or more channel patterns.
+    #[pyo3(signature = (*pchannels))]
+    #[pyo3(text_signature = "($self, *pchannels)")]
+    fn psubscribe(&self, pchannels: &Bound<'_, PyTuple>) -> PyResult<PyObject> {
+        self.update(
+            Subscription::PSubscribe,
+            &extract_arg::<Vec<RedisValuePy>>(pchannels, "pchannels")?,
+        )
+    }
+
+    /// Subscribes to one or more shard channels.
+    #[pyo3(signature = (*channels))]
+    #[pyo3(text_signature = "($self, *channels)")]
+    fn ssubscribe(&self, channels: &Bound<'_, PyTuple>) -> PyResult<PyObject> {
+        self.update(
+            Subscription::SSubscribe,
+            &extract_arg::<Vec<RedisValuePy>>(channels, "channels")?,
+        )
+    }
+
+    /// Unsubscribes from one or more channels, or from all channels if none
+    /// are given.
+    #[pyo3(signature = (*channels))]
+    #[pyo3(text_signature = "($self, *channels)")]
+    fn unsubscribe(&self, channels: &Bound<'_, PyTuple>) -> PyResult<PyObject> {
+        self.update(
+            Subscription::Unsubscribe,
+            &extract_arg::<Vec<RedisValuePy>>(channels, "channels")?,
+        )
+    }
+
+    /// Unsubscribes from one or more channel patterns, or from all patterns if
+    /// none are given.
+    #[pyo3(signature = (*pchannels))]
+    #[pyo3(text_signature = "($self, *pchannels)")]
+    fn punsubscribe(&self, pchannels: &Bound<'_, PyTuple>) -> PyResult<PyObject> {
+        self.update(
+            Subscription::PUnsubscribe,
+            &extract_arg::<Vec<RedisValuePy>>(pchannels, "pchannels")?,
+        )
+    }
+
+    /// Unsubscribes from one or more shard channels, or from all shard
+    /// channels if none are given.
+    #[pyo3(signature = (*channels))]
+    #[pyo3(text_signature = "($self, *channels)")]
+    fn sunsubscribe(&self, channels: &Bound<'_, PyTuple>) -> PyResult<PyObject> {
+        self.update(
+            Subscription::SUnsubscribe,
+            &extract_arg::<Vec<RedisValuePy>>(channels, "channels")?,
+        )
+    }
+
+    /// Read messages continuously and pass them to the callable in `handlers`
+    /// whose key is the message's pattern or channel. Handlers are called on
+    /// the event loop in batches of up to `batch_size` messages, collected for
+    /// at most `max_latency_ms`. Coroutine handlers are scheduled as tasks and
+    /// reading pauses while `max_in_flight` of them are unfinished. The
+    /// returned future completes once the context manager is exited.
+    #[pyo3(signature = (handlers, batch_size = 100, max_latency_ms = 10, max_in_flight = 1024))]
+    #[pyo3(
+        text_signature = "($self, handlers, batch_size = 100, max_latency_ms = 10, max_in_flight = 1024)"
+    )]
+    fn run(
+        &self,
+        handlers: HashMap<String, PyObject>,
+        batch_size: usize,
+        max_latency_ms: u64,
+        max_in_flight: usize,
+    ) -> PyResult<PyObject> {
+        if batch_size == 0 || max_in_flight == 0 {
+            return Err(ArgumentError::new_err(
+                "batch_size and max_in_flight must be positive",
+            ));
+        }
+
+        let (fut, res_fut) = create_future()?;
+        let events = self.events.clone();
+        let setup = self.setup.clone();
+        let handlers = handlers
+            .into_iter()
+            .map(|(k, v)| (k.into_bytes(), v))
+            .collect();
+        let options = dispatch::Options {
+            batch_size,
+            max_latency: Duration::from_millis(max_latency_ms),
+            max_in_flight,
+        };
+
+        RUNTIME.spawn(async move {
+            let mut res = wait_setup(&setup).await;
+            if res.is_ok() {
+                res = dispatch::run(events, handlers, options).await;
+            }
+
+            let res = match res {
+                Ok(()) => set_fut_result_none(&fut),
+                Err(e) => set_fut_exc(&fut, e),
+            };
+            if let Err(e) = res {
+                eprintln!("{e:?}");
+            }
+        });
+
+        Ok(res_fut)
+    }
+
+    /// The number of messages dropped because more than `max_pending` were
+    /// buffered.
+    #[getter]
+    fn dropped_messages(&self) -> u64 {
+        self.events.dropped()
+    }
+
+    /// The number of buffered messages.
+    #[getter]
+    fn pending_messages(&self) -> usize {
+        self.events.pending()
+    }
+
+    // Impossible to return Self in the ContextProtocol
+    // so we do it here
+    // https://github.com/PyO3/pyo3/issues/1205#issuecomment-778529199
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    fn __exit__(&mut self, _ty: PyObject, _value: PyObject, _traceback: PyObject) {
+        self.release();
+    }
+
+    /// Waits until the server confirmed the subscription of
+    /// `keyspace_events`, so no notification sent after it is missed.
+    fn __aenter__(slf: Py<Self>, py: Python) -> PyResult<PyObject> {
+        let (fut, res_fut) = create_future()?;
+        let setup = slf.borrow(py).setup.clone();
+
+        RUNTIME.spawn(async move {
+            let res = match wait_setup(&setup).await {
+                Ok(()) => Python::with_gil(|py| set_fut_result_with_gil(&fut, slf.into_py(py), py)),
+                Err(e) => set_fut_exc(&fut, e),
+            };
+            if let Err(e) = res {
+                eprintln!("{e:?}");
+            }
+        });
+
+        Ok(res_fut)
+    }
+
+    /// Like `__exit__`, but waits until the connection is back in the pool.
+    fn __aexit__(
+        &mut self,
+        _ty: PyObject,
+        _value: PyObject,
+        _traceback: PyObject,
+    ) -> PyResult<PyObject> {
+        let (fut, res_fut) = create_future()?;
+        let released = self.release();
+
+        RUNTIME.spawn(async move {
+            if let Some(released) = released {
+                let _res = released.await;
+            }
+
+            if let Err(e) = set_fut_result_none(&fut) {
+                eprintln!("{e:?}");
+            }
+        });
+
+        Ok(res_fut)
+    }
+
+    fn __aiter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __anext__(&self) -> PyResult<Option<Py<PyAny>>> {
+        let (fut, res_fut) = create_future()?;
+        let events = self.events.clone();
+        let listener = self.listener.clone();
+        let setup = self.setup.clone();
+
+        RUNTIME.spawn(async move {
+            if let Err(e) = wait_setup(&setup).await {
+                if let Err(e2) = set_fut_exc(&fut, e) {
+                    eprintln!("{e2:?}");
+                }
+                return;
+            }
+
+            let res = loop {
+                let event = events.recv().await;
+
+                if let (Some(Event::Message(msg)), Some(listener)) = (&event, &listener) {
+                    // Skip anything that is not a keyspace notification
+                    if let Some(ev) = listener.parse(msg) {
+                        break Python::with_gil(|py| {
+                            set_fut_result_with_gil(&fut, ev.into_py(py), py)
+                        });
+                    }
+                    continue;
+                }
+
+                break Python::with_gil(|py| match event {
+                    Some(Event::Message(msg)) => {
+                        let msg = Message::from_raw(&msg, py).into_py(py);
+                        set_fut_result_with_gil(&fut, msg, py)
+                    }
+                    Some(Event::Reconnected) => {
+                        let msg = Message::reconnected(py).into_py(py);
+                        set_fut_result_with_gil(&fut, msg, py)
+                    }
+                    Some(Event::Lost(e)) => set_fut_exc(
+                        &fut,
+                        PubSubConnectionLost::new_err(format!(
+                            "pubsub connection lost and reconnecting failed: {e}"
+                        )),
+                    ),
+                    Some(Event::Overflow) => set_fut_exc(&fut, overflowed()),
+                    None => set_fut_exc(&fut, closed()),
+                });
+            };
+
+            if let Err(e) = res {
+                eprintln!("{e:?}");
+            }
+        });
+
+        Ok(Some(res_fut))
+    }
+}
+
+fn closed() -> PyErr {
+    PubSubClosed::new_err("context manager has been exited")
+}
+
+/// Wait for the setup of a context to finish if it has not yet.
+async fn wait_setup(setup: &TokioMutex<Option<JoinHandle<PyResult<()>>>>) -> PyResult<()> {
+    match setup.lock().await.take() {
+        Some(setup) => setup.await.unwrap_or_else(|_| Err(closed())),
+        None => Ok(()),
+    }
+}
+
+pub fn overflowed() -> PyErr {
+    PubSubConnectionLost::new_err("pubsub connection closed because max_pending was exceeded")
+}
@@ -0,0 +1,180 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Condvar, Mutex,
+    },
+    time::Duration,
+};
+
+use pyo3::prelude::PyResult;
+use redis::ConnectionInfo;
+use tokio::sync::Semaphore;
+
+use crate::{
+    exceptions::{redis_error, PoolClosed},
+    resp::{self, Stream},
+};
+
+/// An idle connection with the round trip time of its last health check, if
+/// it has been checked since it was returned.
+struct Idle {
+    stream: Stream,
+    rtt: Option<Duration>,
+}
+
+struct State {
+    idle: VecDeque<Idle>,
+    /// The number of connections taken by a health check.
+    checking: usize,
+}
+
+/// The idle pubsub connections of a pool. The semaphore holds one permit per
+/// idle connection so that callers can wait for one to be returned. Permits
+/// are only added or taken while `state` is locked, so there are never more
+/// permits than idle connections.
+pub struct PubSubPool {
+    state: Mutex<State>,
+    /// Notified whenever a health check is done with a connection.
+    checked: Condvar,
+    available: Semaphore,
+    /// The number of connections, idle or in use.
+    size: AtomicUsize,
+    max_size: usize,
+}
+
+impl PubSubPool {
+    pub fn new(connections: Vec<Stream>, max_size: usize) -> Self {
+        let idle = connections
+            .into_iter()
+            .map(|stream| Idle { stream, rtt: None })
+            .collect::<VecDeque<_>>();
+        Self {
+            available: Semaphore::new(idle.len()),
+            size: AtomicUsize::new(idle.len()),
+            state: Mutex::new(State {
+                idle,
+                checking: 0,
+            }),
+            checked: Condvar::new(),
+            max_size,
+        }
+    }
+
+    /// Take an idle connection without waiting.
+    pub fn try_take(&self) -> Option<Stream> {
+        let mut state = self.state.lock().unwrap();
+        self.available.try_acquire().ok()?.forget();
+        state.idle.pop_back().map(|idle| idle.stream)
+    }
+
+    /// Wait until a health check is done with a connection. Returns `false`
+    /// right away if no connection is being checked.
+    pub fn wait_checks(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        if state.checking == 0 {
+            return false;
+        }
+        drop(self.checked.wait(state).unwrap());
+        true
+    }
+
+    /// Take the connection that has been idle the longest for a health check,
+    /// which hands it back with `checked`.
+    pub fn take_for_check(&self) -> Option<Stream> {
+        let mut state = self.state.lock().unwrap();
+        self.available.try_acquire().ok()?.forget();
+        let idle = state.idle.pop_front()?;
+        state.checking += 1;
+        Some(idle.stream)
+    }
+
+    /// Return a connection taken by `take_for_check` with the round trip time
+    /// of its PING, if it answered, or `None` if it could not be replaced.
+    pub fn checked(&self, stream: Option<Stream>, rtt: Option<Duration>) {
+        let mut state = self.state.lock().unwrap();
+        state.checking -= 1;
+        match stream {
+            Some(stream) if !self.available.is_closed() => {
+                state.idle.push_back(Idle { stream, rtt });
+                self.available.add_permits(1);
+            }
+            Some(_) => {}
+            None => self.discard(),
+        }
+        self.checked.notify_all();
+    }
+
+    /// The number of idle connections.
+    pub fn idle(&self) -> usize {
+        self.state.lock().unwrap().idle.len()
+    }
+
+    /// The round trip times of the idle connections, followed by `None` for
+    /// each connection that is in use or being checked.
+    pub fn rtts(&self) -> Vec<Option<Duration>> {
+        let state = self.state.lock().unwrap();
+        let mut rtts: Vec<_> = state.idle.iter().map(|idle| idle.rtt).collect();
+        let in_use = self.size.load(Ordering::Acquire).saturating_sub(rtts.len());
+        rtts.resize(rtts.len() + in_use, None);
+        rtts
+    }
+
+    /// Take an idle connection, open a new one if there are fewer than
+    /// `max_size` or wait for one to be returned.
+    pub async fn take(&self, info: &ConnectionInfo) -> PyResult<Stream> {
+        if let Some(stream) = self.try_take() {
+            return Ok(stream);
+        }
+
+        let reserved = self
+            .size
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |size| {
+                (size < self.max_size).then_some(size + 1)
+            })
+            .is_ok();
+        if reserved {
+            return resp::connect(info).await.map_err(|e| {
+                self.discard();
+                redis_error(&e)
+            });
+        }
+
+        self.available
+            .acquire()
+            .await
+            .map_err(|_| PoolClosed::new_err("pool has been closed"))?
+            .forget();
+        // The connection is gone if the pool was closed after the permit was
+        // taken
+        self.state
+            .lock()
+            .unwrap()
+            .idle
+            .pop_back()
+            .map(|idle| idle.stream)
+            .ok_or_else(|| PoolClosed::new_err("pool has been closed"))
+    }
+
+    /// Return a connection taken from the pool.
+    pub fn put(&self, stream: Stream) {
+        let mut state = self.state.lock().unwrap();
+        if self.available.is_closed() {
+            return;
+        }
+        state.idle.push_back(Idle { stream, rtt: None });
+        self.available.add_permits(1);
+    }
+
+    /// Forget a connection taken from the pool that could not be returned.
+    pub fn discard(&self) {
+        self.size.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    /// Close all idle connections and fail waiting callers.
+    pub fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        self.available.close();
+        state.idle.clear();
+    }
+}
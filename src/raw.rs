@@ -0,0 +1,70 @@
+use std::os::raw::{c_int, c_void};
+
+use pyo3::{
+    ffi,
+    prelude::{pyclass, pymethods, PyErr, PyResult, Python},
+    types::{PyAnyMethods, PyBytes, PyBytesMethods},
+    Bound, PyAny,
+};
+
+/// A value read with `raw_buffer=True`. It holds on to the memory the reply
+/// was read into and exposes it through the buffer protocol, so
+/// `memoryview(value)` or `numpy.frombuffer(value)` don't copy it. Use
+/// `bytes(value)` for a copy.
+#[pyclass(module = "zangy", frozen)]
+pub struct RawBuffer {
+    data: Vec<u8>,
+}
+
+impl RawBuffer {
+    pub fn new(data: Vec<u8>) -> Self {
+        Self { data }
+    }
+}
+
+#[pymethods]
+impl RawBuffer {
+    // The data is never modified or moved while the object is alive, and
+    // `PyBuffer_FillInfo` keeps a reference to it in the view
+    unsafe fn __getbuffer__(
+        slf: &Bound<'_, Self>,
+        view: *mut ffi::Py_buffer,
+        flags: c_int,
+    ) -> PyResult<()> {
+        let data = &slf.get().data;
+        let res = ffi::PyBuffer_FillInfo(
+            view,
+            slf.as_ptr(),
+            data.as_ptr() as *mut c_void,
+            data.len().try_into()?,
+            1,
+            flags,
+        );
+        if res == -1 {
+            return Err(PyErr::fetch(slf.py()));
+        }
+        Ok(())
+    }
+
+    fn __bytes__<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.data)
+    }
+
+    fn __len__(&self) -> usize {
+        self.data.len()
+    }
+
+    fn __eq__(&self, other: &Bound<'_, PyAny>) -> bool {
+        if let Ok(other) = other.downcast::<PyBytes>() {
+            other.as_bytes() == self.data
+        } else if let Ok(other) = other.downcast::<Self>() {
+            other.get().data == self.data
+        } else {
+            false
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("RawBuffer(<{} bytes>)", self.data.len())
+    }
+}
@@ -0,0 +1,242 @@
+//! A minimal RESP2 connection for pubsub. redis-rs' `aio::PubSub` can neither
+//! send arbitrary commands such as `SSUBSCRIBE` nor deliver `smessage` frames,
+//! so pubsub connections are driven by hand.
+
+use std::str::from_utf8;
+
+use redis::{Cmd, ConnectionAddr, ConnectionInfo, ErrorKind, RedisError, RedisResult, Value};
+use tokio::{
+    io::{split, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf},
+    net::TcpStream,
+};
+
+pub trait AsyncStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T> AsyncStream for T where T: AsyncRead + AsyncWrite + Unpin + Send {}
+
+pub type Stream = Box<dyn AsyncStream>;
+
+/// Open a connection and authenticate it.
+pub async fn connect(info: &ConnectionInfo) -> RedisResult<Stream> {
+    let mut stream: Stream = match info.addr {
+        ConnectionAddr::Tcp(ref host, port) => {
+            let stream = TcpStream::connect((host.as_str(), port)).await?;
+            stream.set_nodelay(true)?;
+            Box::new(stream)
+        }
+        #[cfg(unix)]
+        ConnectionAddr::Unix(ref path) => Box::new(tokio::net::UnixStream::connect(path).await?),
+        _ => {
+            return Err(RedisError::from((
+                ErrorKind::InvalidClientConfig,
+                "unsupported connection address for pubsub",
+            )))
+        }
+    };
+
+    if let Some(ref password) = info.redis.password {
+        let mut auth = redis::cmd("AUTH");
+        if let Some(ref username) = info.redis.username {
+            auth.arg(username);
+        }
+        auth.arg(password);
+
+        stream.write_all(&auth.get_packed_command()).await?;
+        let mut reader = FrameReader::new(stream);
+        reader.read().await?;
+        stream = reader.into_inner();
+    }
+
+    Ok(stream)
+}
+
+pub async fn write_cmd(writer: &mut WriteHalf<Stream>, cmd: &Cmd) -> RedisResult<()> {
+    writer.write_all(&cmd.get_packed_command()).await?;
+    Ok(())
+}
+
+/// Check that an idle connection still answers.
+pub async fn ping(stream: &mut Stream) -> RedisResult<()> {
+    stream
+        .write_all(&redis::cmd("PING").get_packed_command())
+        .await?;
+    FrameReader::new(stream).read().await?;
+    Ok(())
+}
+
+/// Leave all channels and wait until the server has confirmed it, so the
+/// connection can be handed out again.
+pub async fn reset(
+    mut reader: FrameReader<ReadHalf<Stream>>,
+    mut writer: WriteHalf<Stream>,
+) -> RedisResult<Stream> {
+    let mut pipe = redis::pipe();
+    pipe.cmd("UNSUBSCRIBE")
+        .cmd("PUNSUBSCRIBE")
+        .cmd("SUNSUBSCRIBE")
+        .cmd("PING");
+    writer.write_all(&pipe.get_packed_pipeline()).await?;
+
+    // Confirmations and messages that were already in flight arrive before
+    // the reply to PING, which is a plain status once no channels are left
+    while reader.read_frame().await? != Ok(Value::Status("PONG".to_string())) {}
+
+    Ok(reader.unsplit(writer))
+}
+
+/// Reads RESP2 frames from a connection. Reading is cancel safe, partially
+/// received frames are kept in the buffer.
+pub struct FrameReader<R> {
+    inner: R,
+    buf: Vec<u8>,
+    /// The buffer length below which the next frame is known to be
+    /// incomplete, so large frames are not parsed again on every read.
+    needed: usize,
+}
+
+impl FrameReader<Stream> {
+    /// Split a connection into a frame reader and a write half.
+    pub fn split(stream: Stream) -> (FrameReader<ReadHalf<Stream>>, WriteHalf<Stream>) {
+        let (read, write) = split(stream);
+        (FrameReader::new(read), write)
+    }
+}
+
+impl FrameReader<ReadHalf<Stream>> {
+    pub fn unsplit(self, writer: WriteHalf<Stream>) -> Stream {
+        self.inner.unsplit(writer)
+    }
+}
+
+impl<R: AsyncRead + Unpin> FrameReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: Vec::with_capacity(4096),
+            needed: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Read the next frame. The outer error is an I/O error, the inner one an
+    /// error reply from the server.
+    pub async fn read_frame(&mut self) -> RedisResult<RedisResult<Value>> {
+        loop {
+            if self.buf.len() >= self.needed {
+                match parse(&self.buf)? {
+                    Parsed::Frame(frame, len) => {
+                        self.buf.drain(..len);
+                        self.needed = 0;
+                        return Ok(frame);
+                    }
+                    Parsed::Incomplete(needed) => self.needed = needed,
+                }
+            }
+
+            if self.inner.read_buf(&mut self.buf).await? == 0 {
+                return Err(RedisError::from(std::io::Error::from(
+                    std::io::ErrorKind::UnexpectedEof,
+                )));
+            }
+        }
+    }
+
+    pub async fn read(&mut self) -> RedisResult<Value> {
+        self.read_frame().await?
+    }
+}
+
+fn parse_error() -> RedisError {
+    RedisError::from((ErrorKind::ParseError, "invalid RESP frame"))
+}
+
+fn server_error(line: &str) -> RedisError {
+    let (code, _) = line.split_once(' ').unwrap_or((line, ""));
+    let kind = match code {
+        "NOAUTH" | "WRONGPASS" => ErrorKind::AuthenticationFailed,
+        "READONLY" => ErrorKind::ReadOnly,
+        _ => ErrorKind::ResponseError,
+    };
+
+    RedisError::from((kind, "server error", line.to_string()))
+}
+
+/// Read a `\r\n` terminated line from the start of `buf`.
+fn line(buf: &[u8]) -> RedisResult<Option<(&str, usize)>> {
+    match buf.windows(2).position(|w| w == b"\r\n") {
+        Some(end) => {
+            let line = from_utf8(&buf[..end]).map_err(|_| parse_error())?;
+            Ok(Some((line, end + 2)))
+        }
+        None => Ok(None),
+    }
+}
+
+fn length(line: &str) -> RedisResult<i64> {
+    line.parse().map_err(|_| parse_error())
+}
+
+enum Parsed {
+    /// A frame and the number of bytes it spans.
+    Frame(RedisResult<Value>, usize),
+    /// The frame is incomplete and the buffer has to grow to at least this
+    /// many bytes before it can be.
+    Incomplete(usize),
+}
+
+/// Parse one frame from the start of `buf`.
+fn parse(buf: &[u8]) -> RedisResult<Parsed> {
+    let Some((&kind, rest)) = buf.split_first() else {
+        return Ok(Parsed::Incomplete(1));
+    };
+    let Some((line, mut pos)) = line(rest)? else {
+        return Ok(Parsed::Incomplete(buf.len() + 1));
+    };
+    pos += 1;
+
+    let value = match kind {
+        b'+' if line == "OK" => Value::Okay,
+        b'+' => Value::Status(line.to_string()),
+        b'-' => return Ok(Parsed::Frame(Err(server_error(line)), pos)),
+        b':' => Value::Int(length(line)?),
+        b'$' => match usize::try_from(length(line)?) {
+            Ok(len) => {
+                if buf.len() < pos + len + 2 {
+                    return Ok(Parsed::Incomplete(pos + len + 2));
+                }
+                let data = buf[pos..pos + len].to_vec();
+                pos += len + 2;
+                Value::Data(data)
+            }
+            Err(_) => Value::Nil,
+        },
+        b'*' => match usize::try_from(length(line)?) {
+            Ok(len) => {
+                let mut items = Vec::with_capacity(len);
+                let mut error = None;
+                for _ in 0..len {
+                    let (item, item_len) = match parse(&buf[pos..])? {
+                        Parsed::Frame(item, item_len) => (item, item_len),
+                        Parsed::Incomplete(needed) => return Ok(Parsed::Incomplete(pos + needed)),
+                    };
+                    pos += item_len;
+                    match item {
+                        Ok(item) => items.push(item),
+                        Err(e) => error = error.or(Some(e)),
+                    }
+                }
+                if let Some(e) = error {
+                    return Ok(Parsed::Frame(Err(e), pos));
+                }
+                Value::Bulk(items)
+            }
+            Err(_) => Value::Nil,
+        },
+        _ => return Err(parse_error()),
+    };
+
+    Ok(Parsed::Frame(Ok(value), pos))
+}
@@ -0,0 +1,226 @@
+use std::fmt;
+
+use pyo3::{
+    intern,
+    prelude::{PyObject, PyResult, Python, ToPyObject},
+    types::{
+        PyAnyMethods, PyBool, PyBoolMethods, PyBytes, PyBytesMethods, PyDict, PyDictMethods,
+        PyFloat, PyFloatMethods, PyInt, PyList, PyListMethods, PyString, PyStringMethods, PyTuple,
+        PyTupleMethods, PyTypeMethods,
+    },
+    Bound, PyAny,
+};
+use serde::{
+    de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor},
+    ser::{self, SerializeMap, SerializeSeq},
+    Deserializer, Serialize,
+};
+
+use crate::exceptions::ArgumentError;
+
+/// Encodes values written to redis and decodes values read from it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Serializer {
+    Json,
+    MsgPack,
+    Pickle,
+}
+
+impl Serializer {
+    pub fn from_name(name: &str) -> PyResult<Self> {
+        match name {
+            "json" => Ok(Self::Json),
+            "msgpack" => Ok(Self::MsgPack),
+            "pickle" => Ok(Self::Pickle),
+            _ => Err(ArgumentError::new_err(format!(
+                "unknown serializer {name:?}, expected one of \"json\", \"msgpack\" or \"pickle\""
+            ))),
+        }
+    }
+
+    pub fn encode(self, obj: &Bound<'_, PyAny>) -> PyResult<Vec<u8>> {
+        match self {
+            Self::Json => serde_json::to_vec(&PyValue(obj))
+                .map_err(|e| ArgumentError::new_err(format!("failed to encode JSON: {e}"))),
+            Self::MsgPack => rmp_serde::to_vec(&PyValue(obj))
+                .map_err(|e| ArgumentError::new_err(format!("failed to encode msgpack: {e}"))),
+            Self::Pickle => {
+                let py = obj.py();
+                py.import_bound(intern!(py, "pickle"))?
+                    .call_method1(intern!(py, "dumps"), (obj, -1))?
+                    .extract()
+            }
+        }
+    }
+
+    pub fn decode(self, data: &[u8], py: Python) -> PyResult<PyObject> {
+        match self {
+            Self::Json => {
+                let mut de = serde_json::Deserializer::from_slice(data);
+                let obj = PyObjectSeed(py)
+                    .deserialize(&mut de)
+                    .and_then(|obj| de.end().map(|()| obj));
+                obj.map_err(|e| ArgumentError::new_err(format!("failed to decode JSON: {e}")))
+            }
+            Self::MsgPack => {
+                // Reading advances `rest`, anything left over means that the
+                // value was not written by us
+                let mut rest = data;
+                let obj = PyObjectSeed(py)
+                    .deserialize(&mut rmp_serde::Deserializer::new(&mut rest))
+                    .map_err(|e| e.to_string())
+                    .and_then(|obj| match rest.len() {
+                        0 => Ok(obj),
+                        n => Err(format!("{n} trailing bytes")),
+                    });
+                obj.map_err(|e| ArgumentError::new_err(format!("failed to decode msgpack: {e}")))
+            }
+            Self::Pickle => Ok(py
+                .import_bound(intern!(py, "pickle"))?
+                .call_method1(intern!(py, "loads"), (PyBytes::new_bound(py, data),))?
+                .unbind()),
+        }
+    }
+}
+
+/// Serializes a Python object made up of `None`, bools, ints, floats, strings,
+/// bytes, lists, tuples and dicts.
+struct PyValue<'a, 'py>(&'a Bound<'py, PyAny>);
+
+impl Serialize for PyValue<'_, '_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let obj = self.0;
+
+        if obj.is_none() {
+            serializer.serialize_unit()
+        } else if let Ok(b) = obj.downcast::<PyBool>() {
+            serializer.serialize_bool(b.is_true())
+        } else if obj.is_instance_of::<PyInt>() {
+            if let Ok(i) = obj.extract::<i64>() {
+                serializer.serialize_i64(i)
+            } else if let Ok(u) = obj.extract::<u64>() {
+                serializer.serialize_u64(u)
+            } else {
+                Err(ser::Error::custom("integer does not fit into 64 bits"))
+            }
+        } else if let Ok(f) = obj.downcast::<PyFloat>() {
+            serializer.serialize_f64(f.value())
+        } else if let Ok(s) = obj.downcast::<PyString>() {
+            serializer.serialize_str(&s.to_cow().map_err(ser::Error::custom)?)
+        } else if let Ok(b) = obj.downcast::<PyBytes>() {
+            serializer.serialize_bytes(b.as_bytes())
+        } else if let Ok(l) = obj.downcast::<PyList>() {
+            let mut seq = serializer.serialize_seq(Some(l.len()))?;
+            for item in l.iter() {
+                seq.serialize_element(&PyValue(&item))?;
+            }
+            seq.end()
+        } else if let Ok(t) = obj.downcast::<PyTuple>() {
+            let mut seq = serializer.serialize_seq(Some(t.len()))?;
+            for item in t.iter() {
+                seq.serialize_element(&PyValue(&item))?;
+            }
+            seq.end()
+        } else if let Ok(d) = obj.downcast::<PyDict>() {
+            let mut map = serializer.serialize_map(Some(d.len()))?;
+            for (k, v) in d.iter() {
+                map.serialize_entry(&PyValue(&k), &PyValue(&v))?;
+            }
+            map.end()
+        } else {
+            let ty = obj
+                .get_type()
+                .name()
+                .map_or_else(|_| "?".to_string(), |n| n.to_string());
+            Err(ser::Error::custom(format!(
+                "object of type {ty} is not serializable"
+            )))
+        }
+    }
+}
+
+/// Deserializes any self-describing format straight into Python objects.
+struct PyObjectSeed<'py>(Python<'py>);
+
+impl<'de> DeserializeSeed<'de> for PyObjectSeed<'_> {
+    type Value = PyObject;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for PyObjectSeed<'_> {
+    type Value = PyObject;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(v.to_object(self.0))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(v.to_object(self.0))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(v.to_object(self.0))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(v.to_object(self.0))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(v.to_object(self.0))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(PyBytes::new_bound(self.0, v).to_object(self.0))
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E> {
+        Ok(self.0.None())
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(self.0.None())
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let list = PyList::empty_bound(self.0);
+        while let Some(item) = seq.next_element_seed(PyObjectSeed(self.0))? {
+            list.append(item).map_err(de::Error::custom)?;
+        }
+        Ok(list.to_object(self.0))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let dict = PyDict::new_bound(self.0);
+        while let Some((k, v)) = map.next_entry_seed(PyObjectSeed(self.0), PyObjectSeed(self.0))? {
+            dict.set_item(k, v).map_err(de::Error::custom)?;
+        }
+        Ok(dict.to_object(self.0))
+    }
+}
@@ -0,0 +1,131 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pyo3::{
+    intern,
+    prelude::{PyObject, PyResult, Python},
+    types::{PyAnyMethods, PyDict, PyDictMethods},
+};
+use redis::{Arg, Cmd, ConnectionAddr, ConnectionInfo, RedisError};
+
+use crate::asyncio::EVENT_LOOP;
+
+/// Reports a span for every command to a Python callback, following the
+/// OpenTelemetry semantic conventions for database clients.
+pub struct Tracer {
+    callback: PyObject,
+    /// Whether to include the statement with all arguments replaced by `?`.
+    statements: bool,
+}
+
+/// A command that is being traced.
+pub struct Span {
+    callback: PyObject,
+    /// The caller's `contextvars` context the callback runs in.
+    context: PyObject,
+    operation: String,
+    statement: Option<String>,
+    peer: ConnectionAddr,
+    start: u64,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| u64::try_from(d.as_nanos()).unwrap_or(u64::MAX))
+}
+
+impl Tracer {
+    pub fn new(callback: PyObject, statements: bool) -> Self {
+        Self {
+            callback,
+            statements,
+        }
+    }
+
+    /// Start a span for `cmd` sent to the server at `info`. Must be called
+    /// from the thread that issued the command so its context is captured.
+    pub fn start(&self, cmd: &Cmd, info: &ConnectionInfo) -> PyResult<Span> {
+        let mut args = cmd.args_iter();
+        let operation = match args.next() {
+            Some(Arg::Simple(name)) => String::from_utf8_lossy(name).to_ascii_uppercase(),
+            _ => String::from("UNKNOWN"),
+        };
+        let statement = self.statements.then(|| {
+            args.fold(operation.clone(), |mut statement, _| {
+                statement.push_str(" ?");
+                statement
+            })
+        });
+
+        Python::with_gil(|py| {
+            let context = py
+                .import_bound(intern!(py, "contextvars"))?
+                .call_method0(intern!(py, "copy_context"))?;
+
+            Ok(Span {
+                callback: self.callback.clone_ref(py),
+                context: context.unbind(),
+                operation,
+                statement,
+                peer: info.addr.clone(),
+                start: now(),
+            })
+        })
+    }
+}
+
+impl Span {
+    /// End the span and hand it to the callback on the event loop.
+    pub fn finish(self, error: Option<&RedisError>) {
+        let end = now();
+
+        let res = Python::with_gil(|py| {
+            let attributes = PyDict::new_bound(py);
+            attributes.set_item(intern!(py, "db.system"), intern!(py, "redis"))?;
+            attributes.set_item(intern!(py, "db.operation"), &self.operation)?;
+            if let Some(statement) = &self.statement {
+                attributes.set_item(intern!(py, "db.statement"), statement)?;
+            }
+            match &self.peer {
+                ConnectionAddr::Tcp(host, port) | ConnectionAddr::TcpTls { host, port, .. } => {
+                    attributes.set_item(intern!(py, "net.peer.name"), host)?;
+                    attributes.set_item(intern!(py, "net.peer.port"), port)?;
+                }
+                ConnectionAddr::Unix(path) => {
+                    attributes.set_item(intern!(py, "net.peer.name"), path.to_string_lossy())?;
+                    attributes.set_item(intern!(py, "net.transport"), intern!(py, "unix"))?;
+                }
+            }
+
+            let span = PyDict::new_bound(py);
+            span.set_item(intern!(py, "name"), &self.operation)?;
+            span.set_item(intern!(py, "kind"), intern!(py, "client"))?;
+            span.set_item(intern!(py, "start_time"), self.start)?;
+            span.set_item(intern!(py, "end_time"), end)?;
+            span.set_item(intern!(py, "attributes"), attributes)?;
+            span.set_item(
+                intern!(py, "status"),
+                if error.is_some() {
+                    intern!(py, "error")
+                } else {
+                    intern!(py, "ok")
+                },
+            )?;
+            span.set_item(intern!(py, "error"), error.map(ToString::to_string))?;
+
+            let kwargs = PyDict::new_bound(py);
+            kwargs.set_item(intern!(py, "context"), self.context)?;
+            EVENT_LOOP.bind(py).call_method(
+                intern!(py, "call_soon_threadsafe"),
+                (self.callback, span),
+                Some(&kwargs),
+            )?;
+
+            PyResult::Ok(())
+        });
+
+        if let Err(e) = res {
+            eprintln!("{e:?}");
+        }
+    }
+}
@@ -1,6 +1,28 @@
+import ast
+import asyncio
+import contextvars
+from datetime import datetime
+from decimal import Decimal
+import keyword
+from pathlib import Path
+
 import pytest
 
-from zangy import create_pool
+import zangy
+from zangy import (
+    ArgumentError,
+    ConnectionError,
+    KeyNamespace,
+    LockError,
+    LockNotOwnedError,
+    PoolClosed,
+    PoolEmpty,
+    RawBuffer,
+    ResponseError,
+    SlowLog,
+    WrongTypeError,
+    create_pool,
+)
 
 
 @pytest.fixture()
@@ -58,3 +80,554 @@ async def test_unsubscribe(client):
 async def test_bytes(client):
     await client.set("a", b"test")
     assert await client.get("a") == b"test"
+
+
+@pytest.mark.asyncio_cooperative
+async def test_hgetall(client):
+    await getattr(client, "del")("shape_hash")
+    await client.hset_multiple("shape_hash", [("a", 1), ("b", 2)])
+    assert await client.hgetall("shape_hash") == {b"a": b"1", b"b": b"2"}
+
+
+@pytest.mark.asyncio_cooperative
+async def test_zrange_withscores(client):
+    await getattr(client, "del")("shape_zset")
+    await client.zadd_multiple("shape_zset", [(1.5, "a"), (2, "b")])
+    assert await client.zrange_withscores("shape_zset", 0, -1) == [
+        (b"a", 1.5),
+        (b"b", 2.0),
+    ]
+    assert await client.zscore("shape_zset", "a") == 1.5
+    assert await client.zscore("shape_zset", "missing") is None
+
+
+@pytest.mark.asyncio_cooperative
+async def test_exists(client):
+    await client.set("shape_key", 1)
+    assert await client.exists("shape_key") is True
+    assert await client.sismember("shape_missing", "a") is False
+
+
+@pytest.mark.asyncio_cooperative
+async def test_json_serializer():
+    client = await create_pool("redis://localhost:6379", 1, 0, serializer="json")
+    value = {"a": [1, 2.5, None, True], "b": "c"}
+    await client.set("serialized", value)
+    assert await client.get("serialized") == value
+    await client.hset("serialized_hash", "field", [1, 2])
+    assert await client.hgetall("serialized_hash") == {b"field": [1, 2]}
+
+
+@pytest.mark.asyncio_cooperative
+async def test_msgpack_serializer(client):
+    pool = await create_pool("redis://localhost:6379", 1, 0, serializer="msgpack")
+    await pool.set("msgpacked", [1, "a", b"b"])
+    assert await pool.get("msgpacked") == [1, "a", b"b"]
+    await client.set("msgpack_legacy", b"hello")
+    assert await pool.get("msgpack_legacy") == b"hello"
+
+
+@pytest.mark.asyncio_cooperative
+async def test_compression():
+    client = await create_pool(
+        "redis://localhost:6379", 1, 0, compression="zstd", compression_threshold=16
+    )
+    value = b"a" * 4096
+    await client.set("compressed", value)
+    assert await client.get("compressed") == value
+    assert await client.strlen("compressed") < len(value)
+    await client.set("uncompressed", b"short")
+    assert await client.get("uncompressed") == b"short"
+    # A header claiming 2 GiB of lz4 data is not decompressed
+    bomb = b"\x00zgc\x02" + (2**31).to_bytes(4, "little") + b"\x00"
+    await client.set("compressed_bomb", bomb)
+    assert await client.get("compressed_bomb") == bomb
+
+
+@pytest.mark.asyncio_cooperative
+async def test_wrong_type(client):
+    await client.set("wrong_type", 1)
+    with pytest.raises(WrongTypeError) as exc_info:
+        await client.lpush("wrong_type", 1)
+    assert isinstance(exc_info.value, ResponseError)
+    assert exc_info.value.code == "WRONGTYPE"
+
+
+@pytest.mark.asyncio_cooperative
+async def test_connection_error():
+    with pytest.raises(ConnectionError) as exc_info:
+        await create_pool("redis://localhost:1", 1, 0)
+    assert exc_info.value.code is None
+    assert exc_info.value.detail is None
+
+
+@pytest.mark.asyncio_cooperative
+async def test_message(client):
+    with client.pubsub() as pubsub:
+        await pubsub.subscribe("message_a", "message_b")
+        await client.publish("message_b", "hello")
+        message = await pubsub.__anext__()
+        assert message.kind == "message"
+        assert message.channel == "message_b"
+        assert message.pattern is None
+        assert message.payload == b"hello"
+        channel, payload = message
+        assert (channel, payload) == ("message_b", b"hello")
+
+
+@pytest.mark.asyncio_cooperative
+async def test_pattern_message(client):
+    with client.pubsub() as pubsub:
+        await pubsub.psubscribe("pattern_*")
+        await client.publish("pattern_a", "hello")
+        message = await pubsub.__anext__()
+        assert message.kind == "pmessage"
+        assert message.channel == "pattern_a"
+        assert message.pattern == "pattern_*"
+
+
+@pytest.mark.asyncio_cooperative
+async def test_punsubscribe(client):
+    with client.pubsub() as pubsub:
+        await pubsub.psubscribe("unsub_*")
+        await pubsub.subscribe("unsub_control")
+        await pubsub.punsubscribe("unsub_*")
+        await client.publish("unsub_a", "dropped")
+        await client.publish("unsub_control", "kept")
+        message = await pubsub.__anext__()
+        assert message.channel == "unsub_control"
+        assert message.payload == b"kept"
+
+
+@pytest.mark.asyncio_cooperative
+async def test_shard_message(client):
+    with client.pubsub() as pubsub:
+        await pubsub.ssubscribe("shard_test")
+        await client.spublish("shard_test", "hello")
+        message = await pubsub.__anext__()
+        assert message.kind == "smessage"
+        assert message.channel == "shard_test"
+        assert message.payload == b"hello"
+        await pubsub.sunsubscribe("shard_test")
+
+
+@pytest.mark.asyncio_cooperative
+async def test_large_message(client):
+    payload = b"x" * 5_000_000
+    with client.pubsub() as pubsub:
+        await pubsub.subscribe("large_message")
+        await client.publish("large_message", payload)
+        message = await pubsub.__anext__()
+        assert message.payload == payload
+
+
+@pytest.mark.asyncio_cooperative
+async def test_keyspace_events(client):
+    async with client.keyspace_events("keyspace_test:*") as events:
+        await client.set("keyspace_test:a", "1")
+        event, key, db = await events.__anext__()
+        assert (event, key, db) == ("set", "keyspace_test:a", 0)
+
+
+@pytest.mark.asyncio_cooperative
+async def test_run(client):
+    received = []
+    done = asyncio.Event()
+
+    def handler(message):
+        received.append(message.payload)
+        done.set()
+
+    with client.pubsub() as pubsub:
+        await pubsub.subscribe("run_test")
+        runner = asyncio.ensure_future(pubsub.run({"run_test": handler}))
+        await client.publish("run_test", "hello")
+        await asyncio.wait_for(done.wait(), 1)
+
+    await runner
+    assert received == [b"hello"]
+
+
+@pytest.mark.asyncio_cooperative
+async def test_max_pending(client):
+    with client.pubsub(max_pending=2, overflow="drop_oldest") as pubsub:
+        await pubsub.subscribe("pending_test")
+        for i in range(5):
+            await client.publish("pending_test", i)
+        # Wait for the messages to arrive
+        while pubsub.dropped_messages < 3:
+            await asyncio.sleep(0.01)
+        assert pubsub.pending_messages == 2
+        message = await pubsub.__anext__()
+        assert message.payload == b"3"
+
+
+@pytest.mark.asyncio_cooperative
+async def test_async_pubsub_context(client):
+    async with client.pubsub() as pubsub:
+        await pubsub.subscribe("async_context")
+    # The connection is back in the pool and no longer subscribed
+    async with client.pubsub() as pubsub:
+        await client.publish("async_context", "dropped")
+        await pubsub.subscribe("async_context_control")
+        await client.publish("async_context_control", "kept")
+        message = await pubsub.__anext__()
+        assert message.channel == "async_context_control"
+
+
+@pytest.mark.asyncio_cooperative
+async def test_close():
+    async with await create_pool("redis://localhost:6379", 2, 1) as pool:
+        await pool.set("close_test", "1")
+    with pytest.raises(PoolClosed):
+        await pool.get("close_test")
+    with pytest.raises(PoolClosed):
+        await pool.get("close_test", prefer="replica")
+    with pytest.raises(PoolClosed):
+        pool.keyspace_events()
+
+
+@pytest.mark.asyncio_cooperative
+async def test_acquire_pubsub():
+    pool = await create_pool("redis://localhost:6379", 1, 1, max_pubsub_size=2)
+    first = await pool.acquire_pubsub()
+    second = await pool.acquire_pubsub()
+    with pytest.raises(PoolEmpty):
+        await pool.acquire_pubsub(timeout=0.1)
+    async with first:
+        pass
+    async with await pool.acquire_pubsub(timeout=1):
+        pass
+    async with second:
+        pass
+
+
+@pytest.mark.asyncio_cooperative
+async def test_health():
+    pool = await create_pool("redis://localhost:6379", 2, 1, health_check_interval=0.01)
+    # The only pubsub connection is often being checked, which is waited for
+    for _ in range(50):
+        async with pool.pubsub():
+            pass
+        await asyncio.sleep(0.005)
+    await asyncio.sleep(0.05)
+    health = pool.health()
+    assert [status["role"] for status in health] == ["primary"] * 2 + ["pubsub"]
+    assert all(status["healthy"] for status in health)
+    assert all(status["rtt"] is not None for status in health)
+
+
+@pytest.mark.asyncio_cooperative
+async def test_replicas():
+    pool = await create_pool("redis://localhost:6379", 2, 0, replicas=["redis://localhost:6379"])
+    await pool.set("replica", "value")
+    assert await pool.get("replica", prefer="primary") == b"value"
+    assert await pool.hgetall("missing", prefer="replica") == {}
+    assert [c["role"] for c in pool.health()] == ["primary"] * 2 + ["replica"] * 2
+    with pytest.raises(ArgumentError):
+        pool.get("replica", prefer="nearest")
+
+
+@pytest.mark.asyncio_cooperative
+async def test_stats():
+    pool = await create_pool("redis://localhost:6379", 2, 1)
+    await pool.set("stats", "value")
+    await pool.get("stats")
+    with pytest.raises(WrongTypeError):
+        await pool.lpush("stats", "value")
+    for name in ("made_up_1", "made_up_2"):
+        with pytest.raises(ResponseError):
+            await pool.execute(name)
+    stats = pool.stats()
+    assert stats["commands"]["GET"]["count"] == 1
+    assert stats["commands"]["LPUSH"]["errors"] == 1
+    assert stats["commands"]["OTHER"]["errors"] == 2
+    assert "MADE_UP_1" not in stats["commands"]
+    assert stats["commands"]["SET"]["p50"] <= stats["commands"]["SET"]["max"]
+    assert [c["in_flight"] for c in stats["connections"]] == [0, 0]
+    async with pool.pubsub():
+        pass
+    assert pool.stats()["pubsub_checkouts"] == 1
+    text = pool.prometheus()
+    assert 'zangy_commands_total{command="GET"} 1' in text
+    assert "# TYPE zangy_command_duration_seconds summary" in text
+
+
+@pytest.mark.asyncio_cooperative
+async def test_tracing():
+    request_id = contextvars.ContextVar("request_id")
+    spans = []
+    pool = await create_pool(
+        "redis://localhost:6379",
+        2,
+        0,
+        on_span=lambda span: spans.append((request_id.get(None), span)),
+        trace_statements=True,
+    )
+    request_id.set("abc")
+    await pool.set("traced", "value")
+    with pytest.raises(WrongTypeError):
+        await pool.lpush("traced", "value")
+    await asyncio.sleep(0)
+    (parent, span), (_, failed) = spans
+    assert parent == "abc"
+    assert span["name"] == "SET"
+    assert span["status"] == "ok"
+    assert span["start_time"] <= span["end_time"]
+    assert span["attributes"]["db.system"] == "redis"
+    assert span["attributes"]["db.statement"] == "SET ? ?"
+    assert span["attributes"]["net.peer.name"] == "localhost"
+    assert failed["status"] == "error"
+
+
+class Upper:
+    def __init__(self):
+        self.seen = []
+
+    def before(self, args):
+        self.seen.append(args[0])
+        if args[0] == b"DEL":
+            raise ValueError("no deletes")
+
+    def after(self, args, reply):
+        return reply.upper() if isinstance(reply, bytes) else reply
+
+
+@pytest.mark.asyncio_cooperative
+async def test_middleware(client):
+    hook = Upper()
+    pool = await create_pool(
+        "redis://localhost:6379", 2, 0, middleware=[KeyNamespace("mw:"), SlowLog(10), hook]
+    )
+    await pool.set("key", "value")
+    assert await client.get("mw:key") == b"value"
+    assert await pool.get("key") == b"VALUE"
+    with pytest.raises(ValueError):
+        await getattr(pool, "del")("key")
+    assert hook.seen == [b"SET", b"GET", b"DEL"]
+
+
+@pytest.mark.asyncio_cooperative
+async def test_key_prefix(client):
+    pool = await create_pool("redis://localhost:6379", 2, 1, key_prefix="svc:", prefix_channels=True)
+    await pool.mset([("prefixed_a", "1"), ("prefixed_b", "2")])
+    assert await client.execute("MGET", "svc:prefixed_a", "svc:prefixed_b") == [b"1", b"2"]
+    assert sorted(await pool.keys("prefixed_*")) == [b"prefixed_a", b"prefixed_b"]
+    await pool.zadd("prefixed_z", "m", 1)
+    await pool.zunionstore("prefixed_u", ["prefixed_z"])
+    assert await client.zcard("svc:prefixed_u") == 1
+    await client.execute("DEL", "svc:prefixed_s")
+    entry = await pool.xadd("prefixed_s", "*", [["field", "value"]])
+    assert await pool.xread("prefixed_s", "0", prefer="primary") == [
+        [b"prefixed_s", [[entry, [b"field", b"value"]]]]
+    ]
+    await client.execute("DEL", "svc:prefixed_l")
+    await pool.rpush("prefixed_l", "x")
+    assert await pool.execute("LMPOP", 1, "prefixed_l", "LEFT") == [b"prefixed_l", [b"x"]]
+    # Commands cut short before their key count are rejected by the server
+    for args in (("EVAL", "return 1"), ("ZUNIONSTORE", "prefixed_u"), ("ZUNION",)):
+        with pytest.raises(ResponseError):
+            await pool.execute(*args)
+    async with pool.pubsub() as pubsub:
+        await pubsub.subscribe("prefixed_channel")
+        await client.publish("svc:prefixed_channel", "hi")
+        async for message in pubsub:
+            assert message.channel == "prefixed_channel"
+            break
+
+
+@pytest.mark.asyncio_cooperative
+async def test_generated_commands(client):
+    await client.execute("DEL", "generated_stream", "generated_geo")
+    entry = await client.xadd("generated_stream", "*", [["field", "value"]])
+    assert await client.xlen("generated_stream") == 1
+    assert await client.xrange("generated_stream", "-", "+", count=1) == [
+        [entry, [b"field", b"value"]]
+    ]
+    await client.xgroup_create("generated_stream", "group", "0")
+    assert await client.type("generated_stream") == "stream"
+    await client.geoadd(
+        "generated_geo", [[13.361389, 38.115556, "Palermo"], [15.087269, 37.502669, "Catania"]]
+    )
+    assert round(float(await client.geodist("generated_geo", "Palermo", "Catania", unit="km"))) == 166
+    with pytest.raises(ArgumentError):
+        await client.geodist("generated_geo", "Palermo", "Catania", unit="parsec")
+
+
+@pytest.mark.asyncio_cooperative
+async def test_argument_types(client):
+    await client.set("types_none", None)
+    assert await client.get("types_none") == b""
+    await client.set("types_buffer", memoryview(b"view"))
+    assert await client.get("types_buffer") == b"view"
+    await client.set("types_strided", memoryview(b"abcdef")[::2])
+    assert await client.get("types_strided") == b"ace"
+    await client.set("types_bytearray", bytearray(b"array"))
+    assert await client.get("types_bytearray") == b"array"
+    await client.set("types_big", 2**70)
+    assert await client.get("types_big") == str(2**70).encode()
+    await client.set("types_decimal", Decimal("0.1"))
+    assert await client.get("types_decimal") == b"0.1"
+    await client.set("types_date", datetime(2024, 1, 2, 3, 4, 5))
+    assert await client.get("types_date") == b"2024-01-02T03:04:05"
+    await client.set("types_float", 0.1)
+    assert await client.get("types_float") == b"0.1"
+    await client.execute("DEL", "types_set")
+    await client.sadd("types_set", {"a", "b"})
+    assert await client.scard("types_set") == 2
+    await client.hset_multiple("types_hash", {"a": 1, "b": 2})
+    assert await client.hgetall("types_hash") == {b"a": b"1", b"b": b"2"}
+    await client.mset({"types_a": "1", "types_b": "2"})
+    assert await client.execute("MGET", "types_a", "types_b") == [b"1", b"2"]
+    with pytest.raises(ArgumentError, match="argument 'key'"):
+        await client.set(object(), "value")
+    with pytest.raises(ArgumentError, match="argument 'value'"):
+        await client.set("types_bad", [object()])
+    with pytest.raises(ArgumentError, match="argument 'id_selector'"):
+        await client.xadd("types_stream", object(), [["field", "value"]])
+    with pytest.raises(ArgumentError, match="argument 'args'"):
+        await client.execute("SET", object(), "value")
+
+
+@pytest.mark.asyncio_cooperative
+async def test_raw_buffer(client):
+    await client.set("raw_value", b"x" * 100_000)
+    value = await client.get("raw_value", raw_buffer=True)
+    assert isinstance(value, RawBuffer)
+    assert len(value) == 100_000
+    assert bytes(value)[:3] == b"xxx"
+    view = memoryview(value)
+    del value
+    assert view.readonly
+    assert bytes(view[:3]) == b"xxx"
+    # Views are sent through the buffer protocol, without copying when
+    # contiguous
+    await client.set("raw_copy", view)
+    assert await client.get("raw_copy") == b"x" * 100_000
+    await client.set("raw_copy", memoryview(b"abcdef")[::2])
+    assert await client.get("raw_copy") == b"ace"
+    assert bytes(await client.getrange("raw_value", 0, 1, raw_buffer=True)) == b"xx"
+    await client.execute("DEL", "raw_list")
+    await client.rpush("raw_list", ["a", "b"])
+    assert await client.lrange("raw_list", 0, -1, raw_buffer=True) == [b"a", b"b"]
+    await client.hset("raw_hash", "field", "value")
+    assert await client.hget("raw_hash", "field", raw_buffer=True) == b"value"
+    assert await client.hget("raw_hash", "missing", raw_buffer=True) is None
+
+
+@pytest.mark.asyncio_cooperative
+async def test_bulk(client):
+    summary = await client.bulk_set(((f"bulk_{i}", i) for i in range(25_000)), chunk_size=10_000)
+    assert summary == {"count": 25_000, "errors": []}
+    assert await client.get("bulk_24999") == b"24999"
+    await client.execute("DEL", "bulk_list")
+    summary = await client.bulk("RPUSH", (("bulk_list", i) for i in range(10)))
+    assert summary["count"] == 10
+    assert await client.llen("bulk_list") == 10
+    summary = await client.bulk("INCR", [["bulk_list"]])
+    assert isinstance(summary["errors"][0], WrongTypeError)
+
+
+@pytest.mark.asyncio_cooperative
+async def test_chunked_iterators(client):
+    await client.execute("DEL", "chunks_list", "chunks_zset", "chunks_hash")
+    await client.rpush("chunks_list", [str(i) for i in range(2500)])
+    chunks = [chunk async for chunk in client.lrange_iter("chunks_list", chunk_size=1000)]
+    assert [len(chunk) for chunk in chunks] == [1000, 1000, 500]
+    assert chunks[2][-1] == b"2499"
+    await client.zadd_multiple("chunks_zset", [(i, f"m{i}") for i in range(5)])
+    chunks = [chunk async for chunk in client.zrange_iter("chunks_zset", chunk_size=2, withscores=True)]
+    assert chunks == [[(b"m0", 0.0), (b"m1", 1.0)], [(b"m2", 2.0), (b"m3", 3.0)], [(b"m4", 4.0)]]
+    await client.hset_multiple("chunks_hash", {f"f{i}": i for i in range(100)})
+    fields = {}
+    async for chunk in client.hgetall_iter("chunks_hash", chunk_size=10):
+        fields.update(chunk)
+    assert len(fields) == 100
+    assert [chunk async for chunk in client.lrange_iter("chunks_missing")] == []
+
+
+@pytest.mark.asyncio_cooperative
+async def test_lock(client):
+    await client.execute("DEL", "lock_test")
+    async with client.lock("lock_test", 5) as lock:
+        assert await client.execute("PTTL", "lock_test") > 0
+        with pytest.raises(LockError):
+            async with client.lock("lock_test", 5, blocking_timeout=0.2):
+                pass
+        await lock.extend(10)
+        assert await client.execute("PTTL", "lock_test") > 5000
+    assert await client.get("lock_test") is None
+
+    lock = client.lock("lock_test", 5)
+    acquiring = lock.acquire()
+    with pytest.raises(LockError):
+        lock.acquire()
+    assert await acquiring
+    await lock.release()
+
+    lock = client.lock("lock_test", 0.1)
+    assert await lock.acquire()
+    await asyncio.sleep(0.2)
+    await client.set("lock_test", "other")
+    with pytest.raises(LockNotOwnedError):
+        await lock.release()
+    assert await client.get("lock_test") == b"other"
+    await client.execute("DEL", "lock_test")
+
+    lock = client.lock("lock_test", 0.3, auto_renew=True)
+    assert await lock.acquire()
+    await asyncio.sleep(0.6)
+    assert await client.lock("lock_test", 1).acquire(blocking=False) is False
+    await lock.release()
+
+    # Once renewing finds the lock taken over, it can be acquired again
+    lock = client.lock("lock_test", 0.3, auto_renew=True)
+    assert await lock.acquire()
+    await client.set("lock_test", "other")
+    await asyncio.sleep(0.3)
+    assert await lock.acquire(blocking=False) is False
+    await client.execute("DEL", "lock_test")
+
+    # As does an extend that finds it expired
+    lock = client.lock("lock_test", 0.1)
+    assert await lock.acquire()
+    await asyncio.sleep(0.2)
+    with pytest.raises(LockNotOwnedError):
+        await lock.extend()
+    assert await lock.acquire(blocking=False) is True
+    await lock.release()
+
+
+def test_stubs():
+    stub = ast.parse((Path(__file__).parent.parent / "zangy.pyi").read_text())
+    definitions = {
+        node.name: node for node in stub.body if isinstance(node, (ast.ClassDef, ast.FunctionDef))
+    }
+    assert {name for name in dir(zangy) if not name.startswith("_")} == set(definitions)
+
+    for name, node in definitions.items():
+        cls = getattr(zangy, name)
+        if not isinstance(node, ast.ClassDef) or issubclass(cls, Exception):
+            continue
+        members = {
+            item.name if isinstance(item, ast.FunctionDef) else item.target.id: item
+            for item in node.body
+            if isinstance(item, (ast.FunctionDef, ast.AnnAssign))
+        }
+        public = {member for member in dir(cls) if not member.startswith("_")}
+        public -= set(keyword.kwlist)
+        assert public == {member for member in members if not member.startswith("_")}
+
+        for member, item in members.items():
+            if not isinstance(item, ast.FunctionDef) or member.startswith("_"):
+                continue
+            if any(isinstance(d, ast.Name) and d.id == "property" for d in item.decorator_list):
+                continue
+            args = item.args
+            # Keywords like `from` are renamed to `from_` and positional-only
+            params = [a.arg[:-1] if keyword.iskeyword(a.arg[:-1]) else a.arg for a in args.posonlyargs]
+            params += [a.arg for a in args.args + [args.vararg] + args.kwonlyargs + [args.kwarg] if a]
+            # Not parsed with inspect, which rejects keywords
+            signature = getattr(cls, member).__text_signature__[1:-1]
+            names = [param.split("=")[0].strip().lstrip("*$") for param in signature.split(",")]
+            assert [n for n in names if n] == params, member
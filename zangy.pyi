@@ -0,0 +1,1490 @@
+# Generated by scripts/generate_stubs.py, do not edit.
+
+from datetime import date
+from decimal import Decimal
+from typing import (
+    AbstractSet,
+    Any,
+    Awaitable,
+    Callable,
+    Iterator,
+    Literal,
+    Mapping,
+    Sequence,
+    final,
+)
+
+from typing_extensions import Self
+
+RedisValue = (
+    None
+    | bool
+    | bytes
+    | bytearray
+    | memoryview
+    | str
+    | int
+    | float
+    | Decimal
+    | date
+    | Sequence["RedisValue"]
+    | AbstractSet["RedisValue"]
+    | Mapping["RedisValue", "RedisValue"]
+)
+Pairs = Mapping[RedisValue, Any] | Sequence[tuple[RedisValue, Any]]
+
+class ArgumentError(Exception): ...
+
+class RedisError(Exception):
+    code: str | None
+    detail: str | None
+
+class PoolEmpty(Exception): ...
+
+class PoolClosed(Exception): ...
+
+class PubSubClosed(Exception): ...
+
+class LockError(Exception): ...
+
+class LockNotOwnedError(LockError): ...
+
+class ConnectionError(RedisError): ...
+
+class TimeoutError(ConnectionError): ...
+
+class PubSubConnectionLost(ConnectionError): ...
+
+class AuthenticationError(RedisError): ...
+
+class ClientError(RedisError): ...
+
+class ResponseError(RedisError): ...
+
+class WrongTypeError(ResponseError): ...
+
+class NoScriptError(ResponseError): ...
+
+class ReadOnlyError(ResponseError): ...
+
+class BusyLoadingError(ResponseError): ...
+
+class BusyError(ResponseError): ...
+
+class ExecAbortError(ResponseError): ...
+
+class OutOfMemoryError(ResponseError): ...
+
+class ClusterError(ResponseError): ...
+
+class ClusterRedirectError(ClusterError): ...
+
+
+@final
+class ConnectionPool:
+    pool_size: int
+
+    def __aenter__(self) -> Awaitable[Self]: ...
+
+    def __aexit__(self, _ty: Any, _value: Any, _traceback: Any) -> Awaitable[None]: ...
+
+    def close(self) -> None:
+        """Stop accepting commands and close all idle connections. Connections
+        are closed once the commands running on them have finished and pubsub
+        connections once their context manager exits.
+        """
+
+    def wait_closed(self) -> Awaitable[None]:
+        """Wait until all commands running when the pool was closed have
+        finished.
+        """
+
+    def current(self) -> int:
+        """Returns the index of the next connection to be used in the pool."""
+
+    def health(self) -> list[dict[str, Any]]:
+        """Returns the result of the last health check of each connection as a
+        list of dicts with the keys `role` (`"primary"`, `"replica"` or
+        `"pubsub"`), `healthy` and `rtt`, the round trip time of the last PING
+        in seconds or `None` if it is not known yet. Pubsub connections are
+        only checked while idle, so their `rtt` is `None` while in use.
+        """
+
+    def stats(self) -> dict[str, Any]:
+        """Returns statistics about the pool as a dict with the keys `commands`,
+        mapping command names to their `count`, `errors` and latency
+        percentiles `p50`, `p90`, `p99`, `p999` and `max` in seconds,
+        `connections`, a list with the `role` and `in_flight` commands of each
+        connection, `reconnects` and `pubsub_checkouts`.
+        """
+
+    def prometheus(self, namespace: str = ...) -> str:
+        """Returns the statistics of `stats` in the Prometheus text exposition
+        format, with metric names prefixed by `namespace`.
+        """
+
+    def execute(self, *args: RedisValue) -> Awaitable[Any]:
+        """Execute a raw redis command."""
+
+    def set(self, key: RedisValue, value: Any) -> Awaitable[Any]:
+        """Set the string value of a key."""
+
+    def get(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+        raw_buffer: bool = ...,
+    ) -> Awaitable[Any]:
+        """Get the value of a key. If key is a list this becomes an `MGET`. With
+        `raw_buffer`, the value is returned as a `RawBuffer` instead of being
+        copied into `bytes` and decoded.
+        """
+
+    def keys(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Gets all keys matching pattern."""
+
+    def set_ex(self, key: RedisValue, value: Any, seconds: int) -> Awaitable[Any]:
+        """Set the value and expiration of a key."""
+
+    def mset(self, items: Pairs) -> Awaitable[Any]:
+        """Sets multiple keys to their values."""
+
+    def pset_ex(self, key: RedisValue, value: Any, milliseconds: int) -> Awaitable[Any]:
+        """Set the value and expiration in milliseconds of a key."""
+
+    def set_nx(self, key: RedisValue, value: Any) -> Awaitable[Any]:
+        """Set the value of a key, only if the key does not exist."""
+
+    def mset_nx(self, items: Pairs) -> Awaitable[Any]:
+        """Sets multiple keys to their values failing if at least one already
+        exists.
+        """
+
+    def bulk_set(
+        self,
+        iterable: Any,
+        ex: int | None = ...,
+        chunk_size: int = ...,
+    ) -> Awaitable[dict[str, Any]]:
+        """Set the keys of the `(key, value)` pairs in `iterable`, or of a dict, to
+        their values, expiring after `ex` seconds if given. Resolves to a dict
+        with the number of keys set as `count` and the `errors`. See `bulk`.
+        """
+
+    def bulk(self, command: str, iterable: Any, chunk_size: int = ...) -> Awaitable[dict[str, Any]]:
+        """Send `command` once for every item of `iterable`, with the item as its
+        arguments. Items are taken `chunk_size` at a time and every chunk is
+        pipelined over all connections before the next one is taken, so no
+        future is created per command. Resolves to a dict with the number of
+        commands sent as `count` and the `errors`, which holds at most one
+        error per pipeline. Like with `execute`, the arguments are not
+        serialized. Middleware `before` hooks run for every command, but since
+        the replies are dropped, `after` hooks, `SlowLog` and `on_span` are
+        not called.
+        """
+
+    def getset(self, key: RedisValue, value: Any) -> Awaitable[Any]:
+        """Set the string value of a key and return its old value."""
+
+    def getrange(
+        self,
+        key: RedisValue,
+        from_: int,
+        /,
+        to: int,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+        raw_buffer: bool = ...,
+    ) -> Awaitable[Any]:
+        """Get a range of bytes/substring from the value of a key. Negative values
+        provide an offset from the end of the value. With `raw_buffer`, it is
+        returned as a `RawBuffer`.
+        """
+
+    def setrange(self, key: RedisValue, offset: int, value: RedisValue) -> Awaitable[Any]:
+        """Overwrite the part of the value stored in key at the specified offset."""
+
+    # del(self, key: RedisValue) -> Awaitable[Any], called with getattr
+
+    def exists(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[bool]:
+        """Determine if a key exists."""
+
+    def expire(self, key: RedisValue, seconds: int) -> Awaitable[Any]:
+        """Set a key's time to live in seconds."""
+
+    def expire_at(self, key: RedisValue, ts: int) -> Awaitable[Any]:
+        """Set the expiration for a key as a UNIX timestamp."""
+
+    def pexpire(self, key: RedisValue, ms: int) -> Awaitable[Any]:
+        """Set a key's time to live in milliseconds."""
+
+    def pexpire_at(self, key: RedisValue, ts: int) -> Awaitable[Any]:
+        """Set the expiration for a key as a UNIX timestamp in milliseconds."""
+
+    def persist(self, key: RedisValue) -> Awaitable[Any]:
+        """Remove the expiration from a key."""
+
+    def ttl(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Get the expiration time of a key."""
+
+    def pttl(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Get the expiration time of a key in milliseconds."""
+
+    def rename(self, key: RedisValue, new_key: RedisValue) -> Awaitable[Any]:
+        """Rename a key."""
+
+    def rename_nx(self, key: RedisValue, new_key: RedisValue) -> Awaitable[Any]:
+        """Rename a key, only if the new key does not exist."""
+
+    def append(self, key: RedisValue, value: RedisValue) -> Awaitable[Any]:
+        """Append a value to a key."""
+
+    def incr(self, key: RedisValue, delta: RedisValue) -> Awaitable[Any]:
+        """Increment the numeric value of a key by the given amount. This issues a
+        `INCRBY` or `INCRBYFLOAT` depending on the type.
+        """
+
+    def setbit(self, key: RedisValue, offset: int, value: bool) -> Awaitable[Any]:
+        """Sets or clears the bit at offset in the string value stored at key."""
+
+    def getbit(
+        self,
+        key: RedisValue,
+        offset: int,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Returns the bit value at offset in the string value stored at key."""
+
+    def bitcount(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Count set bits in a string."""
+
+    def bitcount_range(
+        self,
+        key: RedisValue,
+        start: int,
+        end: int,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Count set bits in a string in a range."""
+
+    def bit_and(self, dstkey: RedisValue, srckeys: RedisValue) -> Awaitable[Any]:
+        """Perform a bitwise AND between multiple keys (containing string values)
+        and store the result in the destination key.
+        """
+
+    def bit_or(self, dstkey: RedisValue, srckeys: RedisValue) -> Awaitable[Any]:
+        """Perform a bitwise OR between multiple keys (containing string values)
+        and store the result in the destination key.
+        """
+
+    def bit_xor(self, dstkey: RedisValue, srckeys: RedisValue) -> Awaitable[Any]:
+        """Perform a bitwise XOR between multiple keys (containing string values)
+        and store the result in the destination key.
+        """
+
+    def bit_not(self, dstkey: RedisValue, srckeys: RedisValue) -> Awaitable[Any]:
+        """Perform a bitwise NOT of the key (containing string values) and store
+        the result in the destination key.
+        """
+
+    def strlen(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Get the length of the value stored in a key."""
+
+    def hget(
+        self,
+        key: RedisValue,
+        field: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+        raw_buffer: bool = ...,
+    ) -> Awaitable[Any]:
+        """Gets a single (or multiple) fields from a hash. With `raw_buffer`, the
+        values are returned as `RawBuffer`s.
+        """
+
+    def hdel(self, key: RedisValue, field: RedisValue) -> Awaitable[Any]:
+        """Deletes a single (or multiple) fields from a hash."""
+
+    def hset(self, key: RedisValue, field: RedisValue, value: Any) -> Awaitable[Any]:
+        """Sets a single field in a hash."""
+
+    def hset_nx(self, key: RedisValue, field: RedisValue, value: Any) -> Awaitable[Any]:
+        """Sets a single field in a hash if it does not exist."""
+
+    def hset_multiple(self, key: RedisValue, items: Pairs) -> Awaitable[Any]:
+        """Sets a multiple fields in a hash."""
+
+    def hincr(self, key: RedisValue, field: RedisValue, delta: RedisValue) -> Awaitable[Any]:
+        """Increments a value."""
+
+    def hexists(
+        self,
+        key: RedisValue,
+        field: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[bool]:
+        """Checks if a field in a hash exists."""
+
+    def hkeys(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Gets all the keys in a hash."""
+
+    def hvals(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Gets all the values in a hash."""
+
+    def hgetall(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[dict[Any, Any]]:
+        """Gets all the fields and values in a hash as a dict."""
+
+    def hgetall_iter(
+        self,
+        key: RedisValue,
+        *,
+        chunk_size: int = ...,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Chunks:
+        """Iterate over the fields and values of a hash with `HSCAN`, as dicts of
+        about `chunk_size` fields. A field may be returned more than once.
+        See `Chunks`.
+        """
+
+    def hlen(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Gets the length of a hash."""
+
+    def blpop(self, key: RedisValue, timeout: float) -> Awaitable[Any]:
+        """Remove and get the first element in a list, or block until one is
+        available.
+        """
+
+    def brpop(self, key: RedisValue, timeout: float) -> Awaitable[Any]:
+        """Remove and get the last element in a list, or block until one is
+        available.
+        """
+
+    def brpoplpush(self, srckey: RedisValue, dstkey: RedisValue, timeout: float) -> Awaitable[Any]:
+        """Pop a value from a list, push it to another list and return it; or block
+        until one is available.
+        """
+
+    def lindex(
+        self,
+        key: RedisValue,
+        index: int,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Get an element from a list by its index."""
+
+    def linsert_before(self, key: RedisValue, pivot: Any, value: Any) -> Awaitable[Any]:
+        """Insert an element before another element in a list."""
+
+    def linsert_after(self, key: RedisValue, pivot: Any, value: Any) -> Awaitable[Any]:
+        """Insert an element after another element in a list."""
+
+    def llen(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Returns the length of the list stored at key."""
+
+    def lpop(self, key: RedisValue, count: int | None = ...) -> Awaitable[Any]:
+        """Removes and returns the first element of the list stored at key."""
+
+    def lpush(self, key: RedisValue, value: Any) -> Awaitable[Any]:
+        """Insert all the specified values at the head of the list stored at key."""
+
+    def lpush_exists(self, key: RedisValue, value: Any) -> Awaitable[Any]:
+        """Inserts a value at the head of the list stored at key, only if key
+        already exists and holds a list.
+        """
+
+    def lrange(
+        self,
+        key: RedisValue,
+        start: int,
+        stop: int,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+        raw_buffer: bool = ...,
+    ) -> Awaitable[Any]:
+        """Returns the specified elements of the list stored at key. With
+        `raw_buffer`, they are returned as `RawBuffer`s.
+        """
+
+    def lrange_iter(
+        self,
+        key: RedisValue,
+        *,
+        chunk_size: int = ...,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Chunks:
+        """Iterate over the elements of a list in chunks of `chunk_size`
+        elements, see `Chunks`.
+        """
+
+    def lrem(self, key: RedisValue, count: int, value: Any) -> Awaitable[Any]:
+        """Removes the first count occurrences of elements equal to value from the
+        list stored at key.
+        """
+
+    def ltrim(self, key: RedisValue, start: int, stop: int) -> Awaitable[Any]:
+        """Trim an existing list so that it will contain only the specified range
+        of elements specified.
+        """
+
+    def lset(self, key: RedisValue, index: int, value: Any) -> Awaitable[Any]:
+        """Sets the list element at index to value."""
+
+    def rpop(self, key: RedisValue, count: int | None = ...) -> Awaitable[Any]:
+        """Removes and returns the last element of the list stored at key."""
+
+    def rpoplpush(self, key: RedisValue, dstkey: RedisValue) -> Awaitable[Any]:
+        """Pop a value from a list, push it to another list and return it."""
+
+    def rpush(self, key: RedisValue, value: Any) -> Awaitable[Any]:
+        """Insert all the specified values at the tail of the list stored at key."""
+
+    def rpush_exists(self, key: RedisValue, value: Any) -> Awaitable[Any]:
+        """Inserts value at the tail of the list stored at key, only if key already
+        exists and holds a list.
+        """
+
+    def sadd(self, key: RedisValue, member: RedisValue) -> Awaitable[Any]:
+        """Add one or more members to a set."""
+
+    def scard(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Get the number of members in a set."""
+
+    def sdiff(
+        self,
+        keys: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Subtract multiple sets."""
+
+    def sdiffstore(self, dstkey: RedisValue, keys: RedisValue) -> Awaitable[Any]:
+        """Subtract multiple sets and store the resulting set in a key."""
+
+    def sinter(
+        self,
+        keys: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Intersect multiple sets."""
+
+    def sinterstore(self, dstkey: RedisValue, keys: RedisValue) -> Awaitable[Any]:
+        """Intersect multiple sets and store the resulting set in a key."""
+
+    def sismember(
+        self,
+        key: RedisValue,
+        member: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[bool]:
+        """Determine if a given value is a member of a set."""
+
+    def smembers(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Get all the members in a set."""
+
+    def smove(self, srckey: RedisValue, dstkey: RedisValue, member: RedisValue) -> Awaitable[Any]:
+        """Move a member from one set to another."""
+
+    def spop(self, key: RedisValue) -> Awaitable[Any]:
+        """Remove and return a random member from a set."""
+
+    def srandmember(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Get one random member from a set."""
+
+    def srandmember_multiple(
+        self,
+        key: RedisValue,
+        count: int,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Get multiple random members from a set."""
+
+    def srem(self, key: RedisValue, member: RedisValue) -> Awaitable[Any]:
+        """Remove one or more members from a set."""
+
+    def sunion(
+        self,
+        keys: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Add multiple sets."""
+
+    def sunionstore(self, dstkey: RedisValue, keys: RedisValue) -> Awaitable[Any]:
+        """Add multiple sets and store the resulting set in a key."""
+
+    def zadd(self, key: RedisValue, member: RedisValue, score: RedisValue) -> Awaitable[Any]:
+        """Add one member to a sorted set, or update its score if it already
+        exists.
+        """
+
+    def zadd_multiple(
+        self,
+        key: RedisValue,
+        items: Sequence[tuple[RedisValue, RedisValue]],
+    ) -> Awaitable[Any]:
+        """Add multiple members to a sorted set, or update its score if it already
+        exists.
+        """
+
+    def zcard(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Get the number of members in a sorted set."""
+
+    def zcount(
+        self,
+        key: RedisValue,
+        min: RedisValue,
+        max: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Count the members in a sorted set with scores within the given values."""
+
+    def zincr(
+        self,
+        key: RedisValue,
+        member: RedisValue,
+        delta: RedisValue,
+    ) -> Awaitable[float | None]:
+        """Increments the member in a sorted set at key by delta and returns the
+        new score. If the member does not exist, it is added with delta as its
+        score.
+        """
+
+    def zinterstore(self, dstkey: RedisValue, keys: Sequence[RedisValue]) -> Awaitable[Any]:
+        """Intersect multiple sorted sets and store the resulting sorted set in a
+        new key using SUM as aggregation function.
+        """
+
+    def zinterstore_min(self, dstkey: RedisValue, keys: Sequence[RedisValue]) -> Awaitable[Any]:
+        """Intersect multiple sorted sets and store the resulting sorted set in a
+        new key using MIN as aggregation function.
+        """
+
+    def zinterstore_max(self, dstkey: RedisValue, keys: Sequence[RedisValue]) -> Awaitable[Any]:
+        """Intersect multiple sorted sets and store the resulting sorted set in a
+        new key using MAX as aggregation function.
+        """
+
+    def zlexcount(
+        self,
+        key: RedisValue,
+        min: RedisValue,
+        max: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Count the number of members in a sorted set between a given
+        lexicographical range.
+        """
+
+    def zpopmax(self, key: RedisValue, count: int) -> Awaitable[list[tuple[Any, float]]]:
+        """Removes and returns up to count members with the highest scores in a
+        sorted set.
+        """
+
+    def zpopmin(self, key: RedisValue, count: int) -> Awaitable[list[tuple[Any, float]]]:
+        """Removes and returns up to count members with the lowest scores in a
+        sorted set.
+        """
+
+    def zrange(
+        self,
+        key: RedisValue,
+        start: int,
+        stop: int,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Return a range of members in a sorted set, by index."""
+
+    def zrange_withscores(
+        self,
+        key: RedisValue,
+        start: int,
+        stop: int,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[list[tuple[Any, float]]]:
+        """Return a range of members in a sorted set, by index with scores."""
+
+    def zrange_iter(
+        self,
+        key: RedisValue,
+        *,
+        chunk_size: int = ...,
+        withscores: bool = ...,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Chunks:
+        """Iterate over the members of a sorted set by index in chunks of
+        `chunk_size` members, as `(member, score)` tuples if `withscores` is
+        set. See `Chunks`.
+        """
+
+    def zrangebylex(
+        self,
+        key: RedisValue,
+        min: RedisValue,
+        max: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Return a range of members in a sorted set, by lexicographical range."""
+
+    def zrangebylex_limit(
+        self,
+        key: RedisValue,
+        min: RedisValue,
+        max: RedisValue,
+        offset: int,
+        count: int,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Return a range of members in a sorted set, by lexicographical range with
+        offset and limit.
+        """
+
+    def zrevrangebylex(
+        self,
+        key: RedisValue,
+        max: RedisValue,
+        min: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Return a range of members in a sorted set, by lexicographical range."""
+
+    def zrevrangebylex_limit(
+        self,
+        key: RedisValue,
+        max: RedisValue,
+        min: RedisValue,
+        offset: int,
+        count: int,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Return a range of members in a sorted set, by lexicographical range with
+        offset and limit.
+        """
+
+    def zrangebyscore(
+        self,
+        key: RedisValue,
+        min: RedisValue,
+        max: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Return a range of members in a sorted set, by score."""
+
+    def zrangebyscore_withscores(
+        self,
+        key: RedisValue,
+        min: RedisValue,
+        max: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[list[tuple[Any, float]]]:
+        """Return a range of members in a sorted set, by score with scores."""
+
+    def zrangebyscore_limit(
+        self,
+        key: RedisValue,
+        min: RedisValue,
+        max: RedisValue,
+        offset: int,
+        count: int,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Return a range of members in a sorted set, by score with limit."""
+
+    def zrangebyscore_limit_withscores(
+        self,
+        key: RedisValue,
+        min: RedisValue,
+        max: RedisValue,
+        offset: int,
+        count: int,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[list[tuple[Any, float]]]:
+        """Return a range of members in a sorted set, by score with limit with
+        scores.
+        """
+
+    def zrank(
+        self,
+        key: RedisValue,
+        member: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Determine the index of a member in a sorted set."""
+
+    def zrem(self, key: RedisValue, members: RedisValue) -> Awaitable[Any]:
+        """Remove one or more members from a sorted set."""
+
+    def zrembylex(self, key: RedisValue, min: RedisValue, max: RedisValue) -> Awaitable[Any]:
+        """Remove all members in a sorted set between the given lexicographical
+        range.
+        """
+
+    def zremrangebyrank(self, key: RedisValue, start: int, stop: int) -> Awaitable[Any]:
+        """Remove all members in a sorted set within the given indexes."""
+
+    def zrembyscore(self, key: RedisValue, min: RedisValue, max: RedisValue) -> Awaitable[Any]:
+        """Remove all members in a sorted set within the given scores."""
+
+    def zrevrange(
+        self,
+        key: RedisValue,
+        start: int,
+        stop: int,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Return a range of members in a sorted set, by index, with scores ordered
+        from high to low.
+        """
+
+    def zrevrange_withscores(
+        self,
+        key: RedisValue,
+        start: int,
+        stop: int,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[list[tuple[Any, float]]]:
+        """Return a range of members in a sorted set, by index, with scores ordered
+        from high to low.
+        """
+
+    def zrevrangebyscore(
+        self,
+        key: RedisValue,
+        max: RedisValue,
+        min: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Return a range of members in a sorted set, by score."""
+
+    def zrevrangebyscore_withscores(
+        self,
+        key: RedisValue,
+        max: RedisValue,
+        min: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[list[tuple[Any, float]]]:
+        """Return a range of members in a sorted set, by score with scores."""
+
+    def zrevrangebyscore_limit(
+        self,
+        key: RedisValue,
+        max: RedisValue,
+        min: RedisValue,
+        offset: int,
+        count: int,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Return a range of members in a sorted set, by score with limit."""
+
+    def zrevrangebyscore_limit_withscores(
+        self,
+        key: RedisValue,
+        max: RedisValue,
+        min: RedisValue,
+        offset: int,
+        count: int,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[list[tuple[Any, float]]]:
+        """Return a range of members in a sorted set, by score with limit with
+        scores.
+        """
+
+    def zrevrank(
+        self,
+        key: RedisValue,
+        member: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Determine the index of a member in a sorted set, with scores ordered
+        from high to low.
+        """
+
+    def zscore(
+        self,
+        key: RedisValue,
+        member: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[float | None]:
+        """Get the score associated with the given member in a sorted set."""
+
+    def zunionstore(self, dstkey: RedisValue, keys: Sequence[RedisValue]) -> Awaitable[Any]:
+        """Unions multiple sorted sets and store the resulting sorted set in a new
+        key using SUM as aggregation function.
+        """
+
+    def zunionstore_min(self, dstkey: RedisValue, keys: Sequence[RedisValue]) -> Awaitable[Any]:
+        """Unions multiple sorted sets and store the resulting sorted set in a new
+        key using MIN as aggregation function.
+        """
+
+    def zunionstore_max(self, dstkey: RedisValue, keys: Sequence[RedisValue]) -> Awaitable[Any]:
+        """Unions multiple sorted sets and store the resulting sorted set in a new
+        key using MAX as aggregation function.
+        """
+
+    def pfadd(self, key: RedisValue, element: RedisValue) -> Awaitable[Any]:
+        """Adds the specified elements to the specified HyperLogLog."""
+
+    def pfcount(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Return the approximated cardinality of the set(s) observed by the
+        HyperLogLog at key(s).
+        """
+
+    def pfmerge(self, dstkey: RedisValue, srckeys: RedisValue) -> Awaitable[Any]:
+        """Merge N different HyperLogLogs into a single one."""
+
+    def publish(self, channel: RedisValue, message: RedisValue) -> Awaitable[Any]:
+        """Posts a message to the given channel."""
+
+    def spublish(self, channel: RedisValue, message: RedisValue) -> Awaitable[Any]:
+        """Posts a message to the given shard channel."""
+
+    def pubsub(
+        self,
+        reconnect_event: bool = ...,
+        max_reconnect_attempts: int = ...,
+        max_pending: int | None = ...,
+        overflow: str = ...,
+    ) -> PubSubContext:
+        """Take a connection from the pubsub pool, raising `PoolEmpty` if none is
+        idle. Idle connections that are being health checked are waited for.
+        The connection is returned when the context manager exits. If it
+        is lost, it is re-established up to `max_reconnect_attempts` times and
+        all subscriptions are restored. If `max_pending` is set, at most that
+        many messages are buffered and `overflow` decides what happens to
+        further ones: `"drop_oldest"`, `"drop_newest"` or `"disconnect"`.
+        """
+
+    def acquire_pubsub(
+        self,
+        timeout: float | None = ...,
+        reconnect_event: bool = ...,
+        max_reconnect_attempts: int = ...,
+        max_pending: int | None = ...,
+        overflow: str = ...,
+    ) -> Awaitable[Any]:
+        """Like `pubsub`, but waits for a connection if none is idle. A new one is
+        opened instead if the pool holds fewer than `max_pubsub_size`. Raises
+        `PoolEmpty` if none became available within `timeout` seconds.
+        """
+
+    def keyspace_events(
+        self,
+        pattern: str = ...,
+        events: str | None = ...,
+        db: int | None = ...,
+    ) -> PubSubContext:
+        """Listen for keyspace notifications on keys matching `pattern` in
+        database `db`, or in all databases if it is `None`. Unless `events` is
+        `None`, `notify-keyspace-events` is set to it first. Iterating the
+        returned context manager yields `KeyspaceEvent`s.
+        """
+
+    def lock(
+        self,
+        name: str,
+        timeout: float,
+        blocking_timeout: float | None = ...,
+        sleep: float = ...,
+        auto_renew: bool = ...,
+    ) -> Lock:
+        """A lock on `name` that other clients using it respect, expiring after
+        `timeout` seconds unless it is released or extended first. Use it as
+        an async context manager, which waits up to `blocking_timeout` seconds
+        for the lock, checking every `sleep` seconds. See `Lock`.
+        """
+
+    def bitfield(
+        self,
+        key: RedisValue,
+        *,
+        operation: Sequence[Sequence[RedisValue]] | None = ...,
+    ) -> Awaitable[Any]:
+        """Performs arbitrary bitfield integer operations on strings."""
+
+    def bitfield_ro(
+        self,
+        key: RedisValue,
+        *,
+        get_block: Sequence[Sequence[RedisValue]] | None = ...,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Performs arbitrary read-only bitfield integer operations on strings."""
+
+    def bitpos(
+        self,
+        key: RedisValue,
+        bit: int,
+        *,
+        range: Sequence[RedisValue] | None = ...,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Finds the first set (1) or clear (0) bit in a string."""
+
+    def geoadd(
+        self,
+        key: RedisValue,
+        data: Sequence[Sequence[RedisValue]],
+        *,
+        condition: str | None = ...,
+        change: bool = ...,
+    ) -> Awaitable[Any]:
+        """Adds one or more members to a geospatial index. The key is created if it doesn't exist."""
+
+    def geodist(
+        self,
+        key: RedisValue,
+        member1: RedisValue,
+        member2: RedisValue,
+        *,
+        unit: str | None = ...,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Returns the distance between two members of a geospatial index."""
+
+    def geohash(
+        self,
+        key: RedisValue,
+        *,
+        member: RedisValue | None = ...,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Returns members from a geospatial index as geohash strings."""
+
+    def geopos(
+        self,
+        key: RedisValue,
+        *,
+        member: RedisValue | None = ...,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Returns the longitude and latitude of members from a geospatial index."""
+
+    def geosearch(
+        self,
+        key: RedisValue,
+        from_: Sequence[RedisValue],
+        by: Sequence[RedisValue],
+        *,
+        order: str | None = ...,
+        count_block: Sequence[RedisValue] | None = ...,
+        withcoord: bool = ...,
+        withdist: bool = ...,
+        withhash: bool = ...,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Queries a geospatial index for members inside an area of a box or a circle."""
+
+    def json_del(self, key: RedisValue, *, path: RedisValue | None = ...) -> Awaitable[Any]:
+        """Deletes a value."""
+
+    def json_get(
+        self,
+        key: RedisValue,
+        *,
+        indent: RedisValue | None = ...,
+        newline: RedisValue | None = ...,
+        space: RedisValue | None = ...,
+        path: RedisValue | None = ...,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Gets the value at one or more paths in JSON serialized form."""
+
+    def json_set(
+        self,
+        key: RedisValue,
+        path: RedisValue,
+        value: RedisValue,
+        *,
+        condition: str | None = ...,
+    ) -> Awaitable[Any]:
+        """Sets or updates the JSON value at a path."""
+
+    def object_encoding(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Returns the internal encoding of a Redis object."""
+
+    def object_freq(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Returns the logarithmic access frequency counter of a Redis object."""
+
+    def object_idletime(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Returns the time since the last access to a Redis object."""
+
+    def object_refcount(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Returns the reference count of a value of a key."""
+
+    def type(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Determines the type of value stored at a key."""
+
+    def xack(self, key: RedisValue, group: RedisValue, id: RedisValue) -> Awaitable[Any]:
+        """Returns the number of messages that were successfully acknowledged by the consumer group member of a stream."""
+
+    def xadd(
+        self,
+        key: RedisValue,
+        id_selector: RedisValue,
+        data: Sequence[Sequence[RedisValue]],
+        *,
+        nomkstream: bool = ...,
+        trim: Sequence[RedisValue] | None = ...,
+    ) -> Awaitable[Any]:
+        """Appends a new message to a stream. Creates the key if it doesn't exist."""
+
+    def xdel(self, key: RedisValue, id: RedisValue) -> Awaitable[Any]:
+        """Returns the number of messages after removing them from a stream."""
+
+    def xgroup_create(
+        self,
+        key: RedisValue,
+        group: RedisValue,
+        id_selector: RedisValue,
+        *,
+        mkstream: bool = ...,
+        entries_read: int | None = ...,
+    ) -> Awaitable[Any]:
+        """Creates a consumer group."""
+
+    def xgroup_destroy(self, key: RedisValue, group: RedisValue) -> Awaitable[Any]:
+        """Destroys a consumer group."""
+
+    def xinfo_stream(
+        self,
+        key: RedisValue,
+        *,
+        full_block: Sequence[RedisValue] | None = ...,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Returns information about a stream."""
+
+    def xlen(
+        self,
+        key: RedisValue,
+        *,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Return the number of messages in a stream."""
+
+    def xpending(
+        self,
+        key: RedisValue,
+        group: RedisValue,
+        *,
+        filters: Sequence[RedisValue] | None = ...,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Returns the information and entries from a stream consumer group's pending entries list."""
+
+    def xrange(
+        self,
+        key: RedisValue,
+        start: RedisValue,
+        end: RedisValue,
+        *,
+        count: int | None = ...,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Returns the messages from a stream within a range of IDs."""
+
+    def xread(
+        self,
+        key: RedisValue,
+        id: RedisValue,
+        *,
+        count: int | None = ...,
+        milliseconds: int | None = ...,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Returns messages from multiple streams with IDs greater than the ones requested. Blocks until a message is available otherwise."""
+
+    def xreadgroup(
+        self,
+        group: RedisValue,
+        consumer: RedisValue,
+        key: RedisValue,
+        id: RedisValue,
+        *,
+        count: int | None = ...,
+        milliseconds: int | None = ...,
+        noack: bool = ...,
+    ) -> Awaitable[Any]:
+        """Returns new or historical messages from a stream for a consumer in a group. Blocks until a message is available otherwise."""
+
+    def xrevrange(
+        self,
+        key: RedisValue,
+        end: RedisValue,
+        start: RedisValue,
+        *,
+        count: int | None = ...,
+        prefer: Literal["replica", "primary"] = ...,
+    ) -> Awaitable[Any]:
+        """Returns the messages from a stream within a range of IDs in reverse order."""
+
+    def xtrim(
+        self,
+        key: RedisValue,
+        strategy: str,
+        threshold: RedisValue,
+        *,
+        operator: str | None = ...,
+        count: int | None = ...,
+    ) -> Awaitable[Any]:
+        """Deletes messages from the beginning of a stream."""
+
+
+@final
+class Message:
+    """A message received on a subscribed channel."""
+    kind: str
+    """Either `"message"`, `"pmessage"` for messages received through a
+    pattern subscription, `"smessage"` for messages received on a shard
+    channel or `"reconnected"` if the connection was re-established and
+    messages may have been missed.
+    """
+    channel: str
+    pattern: str | None
+    """The pattern that matched the channel, if any."""
+    payload: Any
+
+    def __iter__(self) -> Iterator[Any]: ...
+
+    def __repr__(self) -> str: ...
+
+
+@final
+class PubSubContext:
+    def subscribe(self, *channels: RedisValue) -> Awaitable[Any]:
+        """Subscribes to one or more channels."""
+
+    def psubscribe(self, *pchannels: RedisValue) -> Awaitable[Any]:
+        """Subscribes to one or more channel patterns."""
+
+    def ssubscribe(self, *channels: RedisValue) -> Awaitable[Any]:
+        """Subscribes to one or more shard channels."""
+
+    def unsubscribe(self, *channels: RedisValue) -> Awaitable[Any]:
+        """Unsubscribes from one or more channels, or from all channels if none
+        are given.
+        """
+
+    def punsubscribe(self, *pchannels: RedisValue) -> Awaitable[Any]:
+        """Unsubscribes from one or more channel patterns, or from all patterns if
+        none are given.
+        """
+
+    def sunsubscribe(self, *channels: RedisValue) -> Awaitable[Any]:
+        """Unsubscribes from one or more shard channels, or from all shard
+        channels if none are given.
+        """
+
+    def run(
+        self,
+        handlers: Mapping[str, Callable[[Message], Any]],
+        batch_size: int = ...,
+        max_latency_ms: int = ...,
+        max_in_flight: int = ...,
+    ) -> Awaitable[None]:
+        """Read messages continuously and pass them to the callable in `handlers`
+        whose key is the message's pattern or channel. Handlers are called on
+        the event loop in batches of up to `batch_size` messages, collected for
+        at most `max_latency_ms`. Coroutine handlers are scheduled as tasks and
+        reading pauses while `max_in_flight` of them are unfinished. The
+        returned future completes once the context manager is exited.
+        """
+
+    @property
+    def dropped_messages(self) -> int:
+        """The number of messages dropped because more than `max_pending` were
+        buffered.
+        """
+
+    @property
+    def pending_messages(self) -> int:
+        """The number of buffered messages."""
+
+    def __enter__(self) -> Self: ...
+
+    def __exit__(self, _ty: Any, _value: Any, _traceback: Any) -> None: ...
+
+    def __aenter__(self) -> Awaitable[Self]:
+        """Waits until the server confirmed the subscription of
+        `keyspace_events`, so no notification sent after it is missed.
+        """
+
+    def __aexit__(self, _ty: Any, _value: Any, _traceback: Any) -> Awaitable[None]:
+        """Like `__exit__`, but waits until the connection is back in the pool."""
+
+    def __aiter__(self) -> Self: ...
+
+    def __anext__(self) -> Awaitable[Message | KeyspaceEvent]: ...
+
+
+@final
+class KeyspaceEvent:
+    """A keyspace notification."""
+    event: str
+    """The name of the event, for example `"set"`, `"del"` or `"expired"`."""
+    key: str
+    db: int
+
+    def __iter__(self) -> Iterator[Any]: ...
+
+    def __repr__(self) -> str: ...
+
+
+@final
+class KeyNamespace:
+    """Prefixes the keys of all commands with a namespace and removes it from
+    key names in replies. The channels published to are prefixed as well if
+    `channels` is set.
+    """
+
+    def __init__(self, prefix: str, channels: bool = ...) -> None: ...
+
+
+@final
+class SlowLog:
+    """Logs commands that took longer than `threshold` seconds to the `zangy`
+    logger. The GIL is only taken for slow commands.
+    """
+
+    def __init__(self, threshold: float = ...) -> None: ...
+
+
+@final
+class RawBuffer:
+    """A value read with `raw_buffer=True`. It holds on to the memory the reply
+    was read into and exposes it through the buffer protocol, so
+    `memoryview(value)` or `numpy.frombuffer(value)` don't copy it. Use
+    `bytes(value)` for a copy.
+    """
+
+    def __buffer__(self, flags: int, /) -> memoryview: ...
+
+    def __bytes__(self) -> bytes: ...
+
+    def __len__(self) -> int: ...
+
+    def __eq__(self, other: Any) -> bool: ...
+
+    def __repr__(self) -> str: ...
+
+
+@final
+class Chunks:
+    """An async iterator that reads a collection in chunks of `chunk_size`
+    elements with one command each, returned by `lrange_iter`, `zrange_iter`
+    and `hgetall_iter`. Every chunk is converted as soon as it arrives, so
+    only one is held in memory at a time. Changes made to the collection
+    while iterating may cause elements to be skipped or returned twice.
+    """
+
+    def __aiter__(self) -> Self: ...
+
+    def __anext__(self) -> Awaitable[Any]:
+        """Resolves to the next chunk: a list for lists and sorted sets, with
+        `(member, score)` tuples if `withscores` is set, and a dict for
+        hashes.
+        """
+
+
+@final
+class Lock:
+    """A lock on a key that other clients respect, returned by `pool.lock`. It is
+    acquired with `SET NX PX` and a random token that has to match to release
+    or extend it, so a lock that expired and was acquired by someone else is
+    left alone. If `auto_renew` is set, the lock is extended in the background
+    while it is held.
+    """
+
+    def acquire(self, blocking: bool = ...) -> Awaitable[bool]:
+        """Try to acquire the lock, retrying every `sleep` seconds until
+        `blocking_timeout` has passed if `blocking` is set. Resolves to whether
+        the lock was acquired.
+        """
+
+    def release(self) -> Awaitable[None]:
+        """Release the lock. Raises `LockNotOwnedError` if it expired in the
+        meantime.
+        """
+
+    def extend(self, timeout: float | None = ...) -> Awaitable[None]:
+        """Reset the expiry of the lock to `timeout` seconds, or to the timeout
+        it was created with. Raises `LockNotOwnedError` if it expired in the
+        meantime.
+        """
+
+    def __aenter__(self) -> Awaitable[Self]:
+        """Acquires the lock, raising `LockError` if that did not succeed within
+        `blocking_timeout`.
+        """
+
+    def __aexit__(self, _ty: Any, _value: Any, _traceback: Any) -> Awaitable[None]:
+        """Releases the lock."""
+
+
+def create_pool(
+    address: str,
+    pool_size: int,
+    pubsub_size: int,
+    serializer: str | None = ...,
+    compression: str | None = ...,
+    compression_threshold: int = ...,
+    max_pubsub_size: int | None = ...,
+    health_check_interval: float | None = ...,
+    replicas: Sequence[str] = ...,
+    on_span: Callable[[dict[str, Any]], Any] | None = ...,
+    trace_statements: bool = ...,
+    middleware: Sequence[Any] = ...,
+    key_prefix: str | None = ...,
+    prefix_channels: bool = ...,
+) -> Awaitable[ConnectionPool]:
+    """Connect to a redis server at `address` and use up to `pool_size`
+    connections. If `serializer` is one of `"json"`, `"msgpack"` or `"pickle"`,
+    values are encoded with it when written and decoded when read. If
+    `compression` is `"zstd"` or `"lz4"`, values of at least
+    `compression_threshold` bytes are compressed. Up to `max_pubsub_size`
+    pubsub connections are opened on demand by `acquire_pubsub`. If
+    `health_check_interval` is set, all connections are PINGed every that many
+    seconds and replaced if they don't answer. For every address in `replicas`,
+    `pool_size` connections are opened that read-only commands are sent to.
+    If `on_span` is set, it is called on the event loop with a span for every
+    command, in the `contextvars` context the command was issued from. The
+    span includes the statement with its arguments masked if
+    `trace_statements` is set. `middleware` is a list of hooks that can
+    rewrite commands and their replies, see `KeyNamespace` and `SlowLog`.
+    If `key_prefix` is set, it is prepended to all keys, and to pubsub
+    channels if `prefix_channels` is set.
+    """